@@ -1,55 +1,223 @@
-use web_sys::{AudioContext, GainNode};
+use std::cell::{Cell,RefCell};
+use std::collections::{BTreeMap};
+use std::rc::Rc;
+use web_sys::{AudioContext, GainNode, StereoPannerNode};
 use yew::services::{ConsoleService};
 use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::{spawn_local};
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{AudioBuffer};
 
+use crate::dsp::{Dsp,DspFeature,DspSettings,DspStore};
+use crate::meter::{AudioLevel};
+use crate::jitter::{JitterBuffer,JitterStats,Schedule};
+use crate::pcm::{self,Pcm};
+use crate::recording::{Recording,RecordingInfo};
+use yew::format::{Json};
+use yew::services::storage::{Area, StorageService};
+
+const RAW_PCM_KEY: &str = "radio.audio.rawPcm";
+
+// Re-render the buffer stats every this many packets
+const STATS_INTERVAL: u64 = 25;
+
+// Audio of one subscribed receiver: decoded buffers go through the
+// receiver's DSP, then source -> gain -> panner -> mixer
+pub struct AudioChannel {
+    gain: GainNode,
+    panner: StereoPannerNode,
+    volume: f32,
+    pan: f32,
+    muted: bool,
+    // shared with the decoding tasks that schedule playback
+    jitter: Rc<RefCell<JitterBuffer>>,
+    dsp: Rc<RefCell<Dsp>>,
+    // output level after the DSP
+    level: Rc<Cell<AudioLevel>>,
+    // played buffers are also appended here while recording
+    recording: Rc<RefCell<Option<Recording>>>,
+    received: u64,
+}
+
+impl AudioChannel {
+    fn new(audio_ctx: &AudioContext, mixer: &GainNode, dsp: DspSettings) -> AudioChannel {
+        let gain = audio_ctx.create_gain().unwrap();
+        let panner = audio_ctx.create_stereo_panner().unwrap();
+        gain.connect_with_audio_node(&panner).unwrap();
+        panner.connect_with_audio_node(mixer).unwrap();
+
+        AudioChannel {
+            gain,
+            panner,
+            volume: 1.0,
+            pan: 0.0,
+            muted: false,
+            jitter: Rc::new(RefCell::new(JitterBuffer::new())),
+            dsp: Rc::new(RefCell::new(Dsp::new(dsp))),
+            level: Rc::new(Cell::new(AudioLevel::default())),
+            recording: Rc::new(RefCell::new(None)),
+            received: 0,
+        }
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    // -1.0 (left) to 1.0 (right)
+    pub fn pan(&self) -> f32 {
+        self.pan
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    pub fn stats(&self) -> JitterStats {
+        self.jitter.borrow().stats()
+    }
+
+    pub fn level(&self) -> AudioLevel {
+        self.level.get()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.borrow().is_some()
+    }
+
+    // Length of the current recording and whether it hit the size limit
+    pub fn recording_status(&self) -> Option<(f32, bool)> {
+        self.recording.borrow().as_ref().map(|r| (r.seconds(), r.is_full()))
+    }
+
+    fn disconnect(&self) {
+        self.gain.disconnect().unwrap();
+        self.panner.disconnect().unwrap();
+    }
+}
+
 pub struct AudioProvider {
     // audio playback
     audio_ctx: Option<AudioContext>,
+    // master volume, all receiver channels are mixed into this node
     gain: Option<GainNode>,
-    //pub analyser: AnalyserNode,
-    audio_pos: u64,
-    audio_start_time: f64,
-    subscribed_audio: Option<u32>,
+    channels: BTreeMap<u32, AudioChannel>,
+    // only this receiver is heard while set
+    solo: Option<u32>,
+    dsp: DspStore,
+    storage: StorageService,
+    // Play headerless audio frames as 16 bit PCM at pcm::RAW_SAMPLE_RATE.
+    // Off unless the user turns it on: SparkSDR's audio frame format is
+    // not documented and nothing in a headerless frame says it is PCM, so
+    // by default payloads we cannot identify still go to decodeAudioData
+    // rather than being played as noise.  WAV frames are decoded directly
+    // either way.
+    raw_pcm: bool,
 }
 
 impl AudioProvider {
     pub fn new() -> AudioProvider {
+        let storage = StorageService::new(Area::Local).expect("storage was disabled by the user");
+        let raw_pcm =
+            match storage.restore(RAW_PCM_KEY) {
+                Json(Ok(raw_pcm)) => raw_pcm,
+                _ => false,
+            };
         AudioProvider {
             audio_ctx: None,
             gain: None,
-            audio_pos: 0,
-            audio_start_time: 0.0,
-            subscribed_audio: None,
+            channels: BTreeMap::new(),
+            solo: None,
+            dsp: DspStore::new(),
+            storage,
+            raw_pcm,
         }
     }
 
-    pub fn receiving_audio(&self) -> Option<u32> {
-        self.subscribed_audio
+    pub fn raw_pcm(&self) -> bool {
+        self.raw_pcm
     }
 
-    pub fn set_subscribed(&mut self, receiver: Option<u32>) {
-        self.subscribed_audio = receiver;
-        match self.subscribed_audio {
-            Some(_) => (),
-            None => {
-                self.subscribed_audio = None;
-                self.audio_pos = 0;
-                self.audio_start_time = 0.0;
+    pub fn toggle_raw_pcm(&mut self) {
+        self.raw_pcm = !self.raw_pcm;
+        self.storage.store(RAW_PCM_KEY, Json(&self.raw_pcm));
+    }
+
+    pub fn is_subscribed(&self, receiver_id: u32) -> bool {
+        self.channels.contains_key(&receiver_id)
+    }
+
+    // Receivers we are receiving audio from
+    pub fn subscribed(&self) -> Vec<u32> {
+        self.channels.keys().cloned().collect()
+    }
+
+    pub fn channel(&self, receiver_id: u32) -> Option<&AudioChannel> {
+        self.channels.get(&receiver_id)
+    }
+
+    // Returns false if we are not receiving audio from the receiver
+    pub fn start_recording(&mut self, info: RecordingInfo) -> bool {
+        match self.channels.get(&info.receiver_id) {
+            Some(channel) => {
+                *channel.recording.borrow_mut() = Some(Recording::new(info));
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn stop_recording(&mut self, receiver_id: u32) -> Option<Recording> {
+        self.channels.get(&receiver_id).and_then(|channel| channel.recording.borrow_mut().take())
+    }
+
+    // Returns false if there is no audio context to play through
+    pub fn add_channel(&mut self, receiver_id: u32) -> bool {
+        match (&self.audio_ctx, &self.gain) {
+            (Some(audio_ctx), Some(mixer)) => {
+                if !self.channels.contains_key(&receiver_id) {
+                    self.channels.insert(receiver_id, AudioChannel::new(audio_ctx, mixer, self.dsp.get(receiver_id)));
+                    self.update_gains();
+                }
+                true
+            },
+            _ => {
+                ConsoleService::error("no audio context");
+                false
             }
         }
     }
 
+    pub fn remove_channel(&mut self, receiver_id: u32) {
+        if let Some(channel) = self.channels.remove(&receiver_id) {
+            channel.disconnect();
+        }
+        if self.solo == Some(receiver_id) {
+            self.solo = None;
+        }
+        self.update_gains();
+    }
+
+    pub fn clear(&mut self) {
+        for channel in self.channels.values() {
+            channel.disconnect();
+        }
+        self.channels.clear();
+        self.solo = None;
+    }
+
+    // Start scheduling playback from scratch (e.g. after a reconnect)
+    pub fn restart_playback(&mut self) {
+        for channel in self.channels.values() {
+            channel.jitter.borrow_mut().reset();
+        }
+    }
+
     pub fn create_audio_context(&mut self) {
         // audio channel
         let audio_ctx = web_sys::AudioContext::new().unwrap();
         let destination = audio_ctx.destination();
 
-        //let analyser = audio_ctx.create_analyser().unwrap();
-        //analyser.connect_with_audio_node(&destination).unwrap();
-
         let gain = audio_ctx.create_gain().unwrap();
         gain.gain().set_value(1.0);
         gain.connect_with_audio_node(&destination).unwrap();
@@ -58,28 +226,46 @@ impl AudioProvider {
         self.gain = Some(gain);
     }
 
-    pub fn import_audio_data(&mut self, data: js_sys::ArrayBuffer) {
-        match (self.audio_ctx(), self.gain()) {
-            (Some(audio_ctx), Some(gain)) => {
-                if self.audio_pos == 0 {
-                    self.audio_start_time = audio_ctx.current_time();
-                }
-                self.audio_pos += 1;
+    // Decode an audio frame payload (receiver header removed) and hand it
+    // to the receiver's jitter buffer, returns true when the buffer stats
+    // should be re-rendered.  PCM is copied straight into an AudioBuffer,
+    // only compressed payloads go through the browser's (asynchronous)
+    // decodeAudioData.
+    pub fn import_audio_data(&mut self, receiver_id: u32, data: &[u8]) -> bool {
+        let audio_ctx =
+            match self.audio_ctx() {
+                Some(audio_ctx) => audio_ctx,
+                None => return false,
+            };
+        let raw_pcm = self.raw_pcm;
+        let channel =
+            match self.channels.get_mut(&receiver_id) {
+                Some(channel) => channel,
+                None => return false,
+            };
+        channel.received += 1;
 
-                let audio_pos = self.audio_pos;
-                let start_time = self.audio_start_time;
+        let jitter = channel.jitter.clone();
+        let dsp = channel.dsp.clone();
+        let level = channel.level.clone();
+        let recording = channel.recording.clone();
+        let gain = channel.gain.clone();
 
+        match pcm::decode(data, raw_pcm) {
+            Some(pcm) => {
+                match pcm_buffer(&audio_ctx, &pcm) {
+                    Some(buffer) => play(&audio_ctx, &jitter, &dsp, &level, &recording, &gain, &buffer),
+                    None => ConsoleService::error("unable to create audio buffer"),
+                }
+            },
+            None => {
+                let data = js_sys::Uint8Array::from(data).buffer();
                 spawn_local(async move {
-                    let future = JsFuture::from(audio_ctx.decode_audio_data(&data.slice(5)).unwrap());
+                    let future = JsFuture::from(audio_ctx.decode_audio_data(&data).unwrap());
                     match future.await {
                         Ok(value) => {
                             if let Ok(decoded) = value.dyn_into::<AudioBuffer>() {
-                                let source = audio_ctx.create_buffer_source().unwrap();
-                                source.set_buffer(Some(&decoded));
-                                source.connect_with_audio_node(&gain).unwrap();
-                                source.set_loop(false);
-                                let play_time = start_time as f64 + (audio_pos as f64 * 512.0 / 48000.0) + 0.1;
-                                source.start_with_when(play_time).unwrap();
+                                play(&audio_ctx, &jitter, &dsp, &level, &recording, &gain, &decoded);
                             } else {
                                 ConsoleService::error("decoded audio not a valid audio buffer");
                             }
@@ -89,9 +275,9 @@ impl AudioProvider {
                         }
                     }
                 });
-            },
-            _ => ()
+            }
         }
+        channel.received % STATS_INTERVAL == 0
     }
 
     pub fn audio_ctx(&self) -> Option<AudioContext> {
@@ -127,5 +313,112 @@ impl AudioProvider {
         }
     }
 
+    pub fn set_volume(&mut self, receiver_id: u32, volume: f32) {
+        if let Some(channel) = self.channels.get_mut(&receiver_id) {
+            channel.volume = volume.max(0.0);
+        }
+        self.update_gains();
+    }
+
+    pub fn set_pan(&mut self, receiver_id: u32, pan: f32) {
+        if let Some(channel) = self.channels.get_mut(&receiver_id) {
+            channel.pan = pan.max(-1.0).min(1.0);
+            channel.panner.pan().set_value(channel.pan);
+        }
+    }
+
+    pub fn toggle_channel_mute(&mut self, receiver_id: u32) {
+        if let Some(channel) = self.channels.get_mut(&receiver_id) {
+            channel.muted = !channel.muted;
+        }
+        self.update_gains();
+    }
+
+    // DSP settings are kept for receivers we are not listening to as well
+    pub fn dsp_settings(&self, receiver_id: u32) -> DspSettings {
+        self.dsp.get(receiver_id)
+    }
+
+    // Drop the DSP settings of receivers that no longer exist
+    pub fn retain_dsp<F: Fn(u32) -> bool>(&mut self, keep: F) {
+        self.dsp.retain(keep);
+    }
+
+    pub fn toggle_dsp(&mut self, receiver_id: u32, feature: DspFeature) {
+        let settings = self.dsp.toggle(receiver_id, feature);
+        if let Some(channel) = self.channels.get(&receiver_id) {
+            channel.dsp.borrow_mut().set_settings(settings);
+        }
+    }
+
+    pub fn solo(&self) -> Option<u32> {
+        self.solo
+    }
+
+    pub fn toggle_solo(&mut self, receiver_id: u32) {
+        self.solo =
+            match self.solo {
+                Some(solo) if solo == receiver_id => None,
+                _ => Some(receiver_id),
+            };
+        self.update_gains();
+    }
+
+    // Apply volume, mute and solo to each channel's gain node
+    fn update_gains(&self) {
+        for (receiver_id, channel) in self.channels.iter() {
+            let silenced = channel.muted || (self.solo.is_some() && self.solo != Some(*receiver_id));
+            channel.gain.gain().set_value(if silenced { 0.0 } else { channel.volume });
+        }
+    }
+}
+
+// Copy decoded PCM into an AudioBuffer
+fn pcm_buffer(audio_ctx: &AudioContext, pcm: &Pcm) -> Option<AudioBuffer> {
+    if pcm.frames() == 0 {
+        return None;
+    }
+    let buffer = audio_ctx.create_buffer(pcm.channels.len() as u32, pcm.frames() as u32, pcm.sample_rate).ok()?;
+    for (i, samples) in pcm.channels.iter().enumerate() {
+        let mut samples = samples.clone();
+        buffer.copy_to_channel(&mut samples, i as i32).ok()?;
+    }
+    Some(buffer)
+}
+
+// Run the DSP over each channel of a buffer in place and meter the result
+fn process(dsp: &mut Dsp, level: &Cell<AudioLevel>, buffer: &AudioBuffer) {
+    let mut channels = Vec::new();
+    for i in 0..buffer.number_of_channels() {
+        if let Ok(mut samples) = buffer.get_channel_data(i) {
+            if dsp.is_active() {
+                dsp.process(i as usize, &mut samples, buffer.sample_rate());
+                if buffer.copy_to_channel(&mut samples, i as i32).is_err() {
+                    ConsoleService::error("unable to write processed audio");
+                }
+            }
+            channels.push(samples);
+        }
+    }
+    let mut current = level.get();
+    current.update(&channels);
+    level.set(current);
+}
 
-}
\ No newline at end of file
+// Queue a decoded buffer through the receiver's jitter buffer, processed
+// by its DSP (and record it if a recording is running)
+fn play(audio_ctx: &AudioContext, jitter: &Rc<RefCell<JitterBuffer>>, dsp: &Rc<RefCell<Dsp>>, level: &Cell<AudioLevel>, recording: &Rc<RefCell<Option<Recording>>>, gain: &GainNode, buffer: &AudioBuffer) {
+    let schedule = jitter.borrow_mut().schedule(audio_ctx.current_time(), buffer.duration());
+    if let Schedule::Play { when, rate } = schedule {
+        process(&mut dsp.borrow_mut(), level, buffer);
+        if let Some(recording) = recording.borrow_mut().as_mut() {
+            recording.push(buffer);
+        }
+        let source = audio_ctx.create_buffer_source().unwrap();
+        source.set_buffer(Some(buffer));
+        source.playback_rate().set_value(rate as f32);
+        source.connect_with_audio_node(gain).unwrap();
+        source.set_loop(false);
+        source.start_with_when(when).unwrap();
+    }
+}
@@ -0,0 +1,77 @@
+use ham_rs::{Band};
+
+// Commonly used calling/activity frequencies (Hz) and the receiver
+// mode to use with them
+pub struct BandPreset {
+    pub name: &'static str,
+    pub frequency: f64,
+    pub mode: &'static str,
+}
+
+pub const BAND_PRESETS: [BandPreset; 50] = [
+    BandPreset { name: "FT8", frequency: 1840000.0, mode: "FT8" },
+    BandPreset { name: "WSPR", frequency: 1836600.0, mode: "WSPR" },
+    BandPreset { name: "CW", frequency: 1810000.0, mode: "CW" },
+    BandPreset { name: "SSB", frequency: 1910000.0, mode: "LSB" },
+    BandPreset { name: "FT8", frequency: 3573000.0, mode: "FT8" },
+    BandPreset { name: "FT4", frequency: 3575000.0, mode: "FT4" },
+    BandPreset { name: "WSPR", frequency: 3568600.0, mode: "WSPR" },
+    BandPreset { name: "CW", frequency: 3560000.0, mode: "CW" },
+    BandPreset { name: "SSB", frequency: 3985000.0, mode: "LSB" },
+    // 60m is channelized (USB dial frequencies)
+    BandPreset { name: "FT8", frequency: 5357000.0, mode: "FT8" },
+    BandPreset { name: "SSB", frequency: 5330500.0, mode: "USB" },
+    BandPreset { name: "SSB", frequency: 5346500.0, mode: "USB" },
+    BandPreset { name: "SSB", frequency: 5357000.0, mode: "USB" },
+    BandPreset { name: "SSB", frequency: 5371500.0, mode: "USB" },
+    BandPreset { name: "SSB", frequency: 5403500.0, mode: "USB" },
+    BandPreset { name: "FT8", frequency: 7074000.0, mode: "FT8" },
+    BandPreset { name: "FT4", frequency: 7047500.0, mode: "FT4" },
+    BandPreset { name: "WSPR", frequency: 7038600.0, mode: "WSPR" },
+    BandPreset { name: "CW", frequency: 7030000.0, mode: "CW" },
+    BandPreset { name: "SSB", frequency: 7285000.0, mode: "LSB" },
+    BandPreset { name: "FT8", frequency: 10136000.0, mode: "FT8" },
+    BandPreset { name: "FT4", frequency: 10140000.0, mode: "FT4" },
+    BandPreset { name: "WSPR", frequency: 10138700.0, mode: "WSPR" },
+    BandPreset { name: "CW", frequency: 10116000.0, mode: "CW" },
+    BandPreset { name: "FT8", frequency: 14074000.0, mode: "FT8" },
+    BandPreset { name: "FT4", frequency: 14080000.0, mode: "FT4" },
+    BandPreset { name: "WSPR", frequency: 14095600.0, mode: "WSPR" },
+    BandPreset { name: "CW", frequency: 14060000.0, mode: "CW" },
+    BandPreset { name: "SSB", frequency: 14285000.0, mode: "USB" },
+    BandPreset { name: "FT8", frequency: 18100000.0, mode: "FT8" },
+    BandPreset { name: "FT4", frequency: 18104000.0, mode: "FT4" },
+    BandPreset { name: "WSPR", frequency: 18104600.0, mode: "WSPR" },
+    BandPreset { name: "CW", frequency: 18086000.0, mode: "CW" },
+    BandPreset { name: "FT8", frequency: 21074000.0, mode: "FT8" },
+    BandPreset { name: "FT4", frequency: 21140000.0, mode: "FT4" },
+    BandPreset { name: "WSPR", frequency: 21094600.0, mode: "WSPR" },
+    BandPreset { name: "CW", frequency: 21060000.0, mode: "CW" },
+    BandPreset { name: "SSB", frequency: 21385000.0, mode: "USB" },
+    BandPreset { name: "FT8", frequency: 24915000.0, mode: "FT8" },
+    BandPreset { name: "FT4", frequency: 24919000.0, mode: "FT4" },
+    BandPreset { name: "WSPR", frequency: 24924600.0, mode: "WSPR" },
+    BandPreset { name: "FT8", frequency: 28074000.0, mode: "FT8" },
+    BandPreset { name: "FT4", frequency: 28180000.0, mode: "FT4" },
+    BandPreset { name: "WSPR", frequency: 28124600.0, mode: "WSPR" },
+    BandPreset { name: "SSB", frequency: 28385000.0, mode: "USB" },
+    BandPreset { name: "FT8", frequency: 50313000.0, mode: "FT8" },
+    BandPreset { name: "FT4", frequency: 50318000.0, mode: "FT4" },
+    BandPreset { name: "FT8", frequency: 144174000.0, mode: "FT8" },
+    BandPreset { name: "SSB", frequency: 144200000.0, mode: "USB" },
+    BandPreset { name: "SSB", frequency: 432100000.0, mode: "USB" },
+];
+
+impl BandPreset {
+    // Band name (e.g. "20m") from ham_rs
+    pub fn band(&self) -> String {
+        match Band::new(self.frequency as i32).band() {
+            Some(band) => band.to_string(),
+            None => "unknown".to_string(),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        format!("{} {} ({} kHz)", self.band(), self.name, self.frequency / 1000.0)
+    }
+}
@@ -32,18 +32,45 @@ impl RGBAColour {
   }
 }
 
-/// ColourGradient allows you to create custom colour gradients for each
-/// PNG created.
-pub struct ColourGradient {
-  pub colours: Vec<RGBAColour>,
-  pub min: f32,
-  pub max: f32,
+/// Waterfall colour palettes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Palette {
+  Classic,
+  Grayscale,
+  Viridis,
+  HighContrast,
 }
 
-impl ColourGradient {
-  pub fn new() -> Self {
-    Self {
-      colours: vec![RGBAColour::new(0, 0, 0, 0),
+pub const PALETTES: [Palette; 4] = [Palette::Classic, Palette::Grayscale, Palette::Viridis, Palette::HighContrast];
+
+impl Default for Palette {
+  fn default() -> Self {
+    Palette::Classic
+  }
+}
+
+impl Palette {
+  pub fn new(name: &str) -> Self {
+    match name {
+      "Grayscale" => Palette::Grayscale,
+      "Viridis" => Palette::Viridis,
+      "High Contrast" => Palette::HighContrast,
+      _ => Palette::Classic,
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      Palette::Classic => "Classic",
+      Palette::Grayscale => "Grayscale",
+      Palette::Viridis => "Viridis",
+      Palette::HighContrast => "High Contrast",
+    }
+  }
+
+  pub fn colours(&self) -> Vec<RGBAColour> {
+    match self {
+      Palette::Classic => vec![RGBAColour::new(0, 0, 0, 0),
         // Purple
         RGBAColour::new(55, 0, 110, 0),
         // Blue
@@ -59,6 +86,48 @@ impl ColourGradient {
         RGBAColour::new(230, 160, 0, 0),
         // Red
         RGBAColour::new(255, 0, 0, 0)],
+      Palette::Grayscale => vec![RGBAColour::new(0, 0, 0, 0),
+        RGBAColour::new(255, 255, 255, 0)],
+      // Perceptually uniform, based on matplotlib's viridis
+      Palette::Viridis => vec![RGBAColour::new(68, 1, 84, 0),
+        RGBAColour::new(72, 40, 120, 0),
+        RGBAColour::new(62, 73, 137, 0),
+        RGBAColour::new(49, 104, 142, 0),
+        RGBAColour::new(38, 130, 142, 0),
+        RGBAColour::new(31, 158, 137, 0),
+        RGBAColour::new(53, 183, 121, 0),
+        RGBAColour::new(110, 206, 88, 0),
+        RGBAColour::new(181, 222, 43, 0),
+        RGBAColour::new(253, 231, 37, 0)],
+      // Blue to yellow (Okabe-Ito colours), distinguishable with
+      // the common forms of colour blindness
+      Palette::HighContrast => vec![RGBAColour::new(0, 0, 0, 0),
+        RGBAColour::new(0, 0, 139, 0),
+        RGBAColour::new(0, 114, 178, 0),
+        RGBAColour::new(86, 180, 233, 0),
+        RGBAColour::new(240, 228, 66, 0),
+        RGBAColour::new(230, 159, 0, 0),
+        RGBAColour::new(255, 255, 255, 0)],
+    }
+  }
+}
+
+/// ColourGradient allows you to create custom colour gradients for each
+/// PNG created.
+pub struct ColourGradient {
+  pub colours: Vec<RGBAColour>,
+  pub min: f32,
+  pub max: f32,
+}
+
+impl ColourGradient {
+  pub fn new() -> Self {
+    Self::from_palette(Palette::Classic)
+  }
+
+  pub fn from_palette(palette: Palette) -> Self {
+    Self {
+      colours: palette.colours(),
       min: 0.0,
       max: 1.0,
     }
@@ -70,21 +139,17 @@ impl ColourGradient {
     if value >= self.max {
       return self.colours.last().unwrap().clone();
     }
-    let mut ratio = value / self.max;
-    let width = 1.0 / (self.colours.len() as f32 - 1.0);
-    let mut i = 0;
-
-    // Find the "bin"
-    while ratio > width {
-      ratio -= width;
-      i += 1;
+    if value <= self.min || self.max <= self.min || value.is_nan() {
+      return self.colours[0].clone();
     }
 
-    ratio *= (self.colours.len() - 1) as f32;
+    // Position within the gradient, then find the "bin"
+    let position = (value - self.min) / (self.max - self.min) * (self.colours.len() - 1) as f32;
+    let i = (position.floor() as usize).min(self.colours.len() - 2);
+    let ratio = position - i as f32;
 
     assert!(0.0 <= ratio);
     assert!(ratio <= 1.0);
-    assert!(i < self.colours.len());
 
     let first = self.colours[i].clone();
     let second = self.colours[i + 1].clone();
@@ -109,3 +174,62 @@ impl ColourGradient {
     self.min = min
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn rgb(colour: &RGBAColour) -> (u8, u8, u8) {
+    (colour.r, colour.g, colour.b)
+  }
+
+  fn gradient(palette: Palette, min: f32, max: f32) -> ColourGradient {
+    let mut gradient = ColourGradient::from_palette(palette);
+    gradient.set_min(min);
+    gradient.set_max(max);
+    gradient
+  }
+
+  #[test]
+  fn boundaries_of_every_palette() {
+    for palette in PALETTES.iter() {
+      let colours = palette.colours();
+      let first = rgb(&colours[0]);
+      let last = rgb(colours.last().unwrap());
+      let gradient = gradient(*palette, 0.0, 255.0);
+      assert_eq!(rgb(&gradient.get_colour(0.0)), first, "{:?}", palette);
+      assert_eq!(rgb(&gradient.get_colour(-10.0)), first, "{:?}", palette);
+      assert_eq!(rgb(&gradient.get_colour(f32::NAN)), first, "{:?}", palette);
+      assert_eq!(rgb(&gradient.get_colour(255.0)), last, "{:?}", palette);
+      assert_eq!(rgb(&gradient.get_colour(1000.0)), last, "{:?}", palette);
+      // just inside the range is interpolated from the end colours
+      assert_eq!(rgb(&gradient.get_colour(0.001)), first, "{:?}", palette);
+      assert_eq!(rgb(&gradient.get_colour(254.999)), last, "{:?}", palette);
+    }
+  }
+
+  #[test]
+  fn honours_the_minimum() {
+    let gradient = gradient(Palette::Grayscale, 100.0, 200.0);
+    assert_eq!(rgb(&gradient.get_colour(50.0)), (0, 0, 0));
+    assert_eq!(rgb(&gradient.get_colour(100.0)), (0, 0, 0));
+    assert_eq!(rgb(&gradient.get_colour(150.0)), (128, 128, 128));
+    assert_eq!(rgb(&gradient.get_colour(200.0)), (255, 255, 255));
+  }
+
+  #[test]
+  fn interpolates_between_stops() {
+    let gradient = gradient(Palette::Classic, 0.0, 7.0);
+    // each unit is one stop of the 8 colour classic palette
+    assert_eq!(rgb(&gradient.get_colour(1.0)), (55, 0, 110));
+    assert_eq!(rgb(&gradient.get_colour(1.5)), (28, 0, 145));
+    assert_eq!(rgb(&gradient.get_colour(6.5)), (243, 80, 0));
+  }
+
+  #[test]
+  fn empty_range_uses_the_first_colour() {
+    let gradient = gradient(Palette::Viridis, 10.0, 10.0);
+    assert_eq!(rgb(&gradient.get_colour(9.0)), (68, 1, 84));
+    assert_eq!(rgb(&gradient.get_colour(10.0)), rgb(Palette::Viridis.colours().last().unwrap()));
+  }
+}
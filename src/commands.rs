@@ -0,0 +1,74 @@
+// Commands `sparkplug::Command` does not cover.  Serialized the same way
+// as sparkplug's commands: a "cmd" tag with the parameters under
+// SparkSDR's field naming.  Neither sparkplug nor SparkSDR's websocket
+// API documentation describe these commands, the names follow the
+// documented ones and have not been confirmed against SparkSDR.  They
+// are only sent with the experimental protocol extensions enabled
+// (Model::send_ext_command).
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "cmd")]
+pub enum ExtCommand {
+    #[serde(rename = "setFilter")]
+    SetFilter {
+        #[serde(rename = "ID")]
+        id: u32,
+        #[serde(rename = "FilterLow")]
+        filter_low: f32,
+        #[serde(rename = "FilterHigh")]
+        filter_high: f32,
+    },
+    // Key/unkey a radio, audio is sent as binary frames while keyed (see
+    // frame::encode_audio)
+    #[serde(rename = "setTransmit")]
+    SetTransmit {
+        #[serde(rename = "ID")]
+        id: u32,
+        #[serde(rename = "Transmit")]
+        transmit: bool,
+    },
+}
+
+// Parts of SparkSDR's responses that `sparkplug::CommandResponse` drops.
+// Parsed from the same text messages, anything else fails to parse and
+// is ignored.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "cmd")]
+pub enum ExtResponse {
+    #[serde(rename = "getRadiosResponse")]
+    Radios {
+        #[serde(rename = "Radios")]
+        radios: Vec<RadioInfo>,
+    },
+    #[serde(rename = "getReceiversResponse")]
+    Receivers {
+        #[serde(rename = "Receivers")]
+        receivers: Vec<ReceiverInfo>,
+    },
+}
+
+// A radio as reported by SparkSDR, including the properties
+// `sparkplug::Radio` does not keep
+#[derive(Deserialize, Debug, Clone)]
+pub struct RadioInfo {
+    #[serde(rename = "ID")]
+    pub id: u32,
+    // Not in SparkSDR's documented getRadiosResponse.  Radios that do not
+    // report it are treated as receive only, so transmit stays unavailable
+    // unless SparkSDR turns out to send it.
+    #[serde(rename = "CanTransmit", default)]
+    pub can_transmit: bool,
+    // everything else reported for the radio
+    #[serde(flatten)]
+    pub properties: serde_json::Map<String, serde_json::Value>,
+}
+
+// Radio a receiver belongs to.  `RadioID` is not part of SparkSDR's
+// documented getReceiversResponse, receivers reported without it are
+// shown as unassigned rather than placed on a radio by guessing.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ReceiverInfo {
+    #[serde(rename = "ID")]
+    pub id: u32,
+    #[serde(rename = "RadioID", default)]
+    pub radio_id: Option<u32>,
+}
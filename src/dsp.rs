@@ -0,0 +1,462 @@
+use std::collections::{BTreeMap};
+use yew::format::{Json};
+use yew::services::storage::{Area, StorageService};
+
+const SETTINGS_KEY: &str = "radio.dsp";
+
+// Spectral noise reduction: frame length (power of two) and hop,
+// smoothing of the bin powers, how fast the noise estimate follows a
+// rising level, over subtraction and the lowest bin gain
+const NR_FRAME: usize = 256;
+const NR_HOP: usize = NR_FRAME / 2;
+const NR_POWER_SMOOTHING: f32 = 0.7;
+const NR_NOISE_RISE: f32 = 0.002;
+const NR_OVERSUBTRACT: f32 = 2.0;
+const NR_GAIN_FLOOR: f32 = 0.1;
+// Auto notch adaptive filter (NLMS)
+const LMS_TAPS: usize = 32;
+const LMS_DELAY: usize = 16;
+const NOTCH_MU: f32 = 0.005;
+// AGC output level, largest gain and attack/decay time constants (seconds)
+const AGC_TARGET: f32 = 0.3;
+const AGC_MAX_GAIN: f32 = 100.0;
+const AGC_ATTACK: f32 = 0.002;
+const AGC_DECAY: f32 = 0.5;
+// CW peaking filter
+const CW_PITCH: f32 = 600.0;
+const CW_Q: f32 = 5.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DspFeature {
+    NoiseReduction,
+    AutoNotch,
+    Agc,
+    CwPeak,
+}
+
+pub const DSP_FEATURES: [DspFeature; 4] = [DspFeature::NoiseReduction, DspFeature::AutoNotch, DspFeature::Agc, DspFeature::CwPeak];
+
+impl DspFeature {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DspFeature::NoiseReduction => "NR",
+            DspFeature::AutoNotch => "ANF",
+            DspFeature::Agc => "AGC",
+            DspFeature::CwPeak => "CW",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            DspFeature::NoiseReduction => "Noise reduction",
+            DspFeature::AutoNotch => "Automatic notch filter",
+            DspFeature::Agc => "Automatic gain control",
+            DspFeature::CwPeak => "CW peaking filter",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DspSettings {
+    pub noise_reduction: bool,
+    pub auto_notch: bool,
+    pub agc: bool,
+    pub cw_peak: bool,
+}
+
+impl DspSettings {
+    pub fn enabled(&self, feature: DspFeature) -> bool {
+        match feature {
+            DspFeature::NoiseReduction => self.noise_reduction,
+            DspFeature::AutoNotch => self.auto_notch,
+            DspFeature::Agc => self.agc,
+            DspFeature::CwPeak => self.cw_peak,
+        }
+    }
+
+    pub fn toggle(&mut self, feature: DspFeature) {
+        match feature {
+            DspFeature::NoiseReduction => self.noise_reduction = !self.noise_reduction,
+            DspFeature::AutoNotch => self.auto_notch = !self.auto_notch,
+            DspFeature::Agc => self.agc = !self.agc,
+            DspFeature::CwPeak => self.cw_peak = !self.cw_peak,
+        }
+    }
+
+    fn any(&self) -> bool {
+        self.noise_reduction || self.auto_notch || self.agc || self.cw_peak
+    }
+}
+
+// DSP settings of each receiver, persisted between sessions
+pub struct DspStore {
+    storage: StorageService,
+    settings: BTreeMap<u32, DspSettings>,
+}
+
+impl DspStore {
+    pub fn new() -> DspStore {
+        let storage = StorageService::new(Area::Local).expect("storage was disabled by the user");
+        let settings = {
+            if let Json(Ok(settings)) = storage.restore(SETTINGS_KEY) {
+                settings
+            } else {
+                BTreeMap::new()
+            }
+        };
+        DspStore { storage, settings }
+    }
+
+    pub fn get(&self, receiver_id: u32) -> DspSettings {
+        self.settings.get(&receiver_id).cloned().unwrap_or_default()
+    }
+
+    pub fn toggle(&mut self, receiver_id: u32, feature: DspFeature) -> DspSettings {
+        let settings = self.settings.entry(receiver_id).or_default();
+        settings.toggle(feature);
+        let settings = *settings;
+        self.storage.store(SETTINGS_KEY, Json(&self.settings));
+        settings
+    }
+
+    // Forget receivers that went away, SparkSDR reuses their ids
+    pub fn retain<F: Fn(u32) -> bool>(&mut self, keep: F) {
+        let before = self.settings.len();
+        self.settings.retain(|receiver_id, _| keep(*receiver_id));
+        if self.settings.len() != before {
+            self.storage.store(SETTINGS_KEY, Json(&self.settings));
+        }
+    }
+}
+
+// In place radix-2 FFT, `re.len()` must be a power of two.  The inverse
+// transform is not scaled.
+fn fft(re: &mut [f32], im: &mut [f32], inverse: bool) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut len = 2;
+    while len <= n {
+        let angle = sign * 2.0 * std::f32::consts::PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (w_im, w_re) = (angle * k as f32).sin_cos();
+                let a = start + k;
+                let b = a + len / 2;
+                let t_re = re[b] * w_re - im[b] * w_im;
+                let t_im = re[b] * w_im + im[b] * w_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+// Spectral subtraction on overlapping sqrt-Hann windowed frames.  The
+// noise level of each bin follows the minimum of its (smoothed) power,
+// creeping up slowly so that it adapts when the band gets noisier.
+// Output is delayed by one frame.
+struct SpectralNr {
+    window: Vec<f32>,
+    input: Vec<f32>,
+    pos: usize,
+    output: Vec<f32>,
+    overlap: Vec<f32>,
+    power: Vec<f32>,
+    noise: Vec<f32>,
+    gain: Vec<f32>,
+}
+
+impl SpectralNr {
+    fn new() -> SpectralNr {
+        let bins = NR_FRAME / 2 + 1;
+        SpectralNr {
+            window: (0..NR_FRAME).map(|n| (std::f32::consts::PI * n as f32 / NR_FRAME as f32).sin()).collect(),
+            input: vec![0.0; NR_FRAME],
+            pos: NR_HOP,
+            output: vec![0.0; NR_HOP],
+            overlap: vec![0.0; NR_FRAME],
+            power: vec![0.0; bins],
+            noise: vec![f32::MAX; bins],
+            gain: vec![1.0; bins],
+        }
+    }
+
+    fn step(&mut self, x: f32) -> f32 {
+        let y = self.output[self.pos - NR_HOP];
+        self.input[self.pos] = x;
+        self.pos += 1;
+        if self.pos == NR_FRAME {
+            self.process_frame();
+            self.input.copy_within(NR_HOP.., 0);
+            self.pos = NR_HOP;
+        }
+        y
+    }
+
+    fn process_frame(&mut self) {
+        let mut re: Vec<f32> = self.input.iter().zip(self.window.iter()).map(|(x, w)| x * w).collect();
+        let mut im = vec![0.0; NR_FRAME];
+        fft(&mut re, &mut im, false);
+
+        for k in 0..self.gain.len() {
+            let power = re[k] * re[k] + im[k] * im[k];
+            self.power[k] = NR_POWER_SMOOTHING * self.power[k] + (1.0 - NR_POWER_SMOOTHING) * power;
+            if self.power[k] < self.noise[k] {
+                self.noise[k] = self.power[k];
+            } else {
+                self.noise[k] += NR_NOISE_RISE * (self.power[k] - self.noise[k]);
+            }
+            let gain = (1.0 - NR_OVERSUBTRACT * self.noise[k] / self.power[k].max(1e-12)).max(NR_GAIN_FLOOR);
+            self.gain[k] = 0.5 * (self.gain[k] + gain);
+
+            // real input, the upper half mirrors the lower
+            re[k] *= self.gain[k];
+            im[k] *= self.gain[k];
+            if k > 0 && k < NR_FRAME / 2 {
+                re[NR_FRAME - k] *= self.gain[k];
+                im[NR_FRAME - k] *= self.gain[k];
+            }
+        }
+
+        fft(&mut re, &mut im, true);
+        for (i, sample) in re.iter().enumerate() {
+            self.overlap[i] += sample / NR_FRAME as f32 * self.window[i];
+        }
+        self.output.copy_from_slice(&self.overlap[..NR_HOP]);
+        self.overlap.copy_within(NR_HOP.., 0);
+        for sample in self.overlap[NR_FRAME - NR_HOP..].iter_mut() {
+            *sample = 0.0;
+        }
+    }
+}
+
+// Normalized LMS predictor: predicts each sample from delayed previous
+// samples.  Steady tones (carriers, heterodynes) are predictable while
+// speech and noise mostly are not, so the prediction error is the input
+// with the tones removed.
+struct Lms {
+    mu: f32,
+    weights: Vec<f32>,
+    history: Vec<f32>,
+    pos: usize,
+}
+
+impl Lms {
+    fn new(mu: f32) -> Lms {
+        Lms {
+            mu,
+            weights: vec![0.0; LMS_TAPS],
+            history: vec![0.0; LMS_TAPS + LMS_DELAY],
+            pos: 0,
+        }
+    }
+
+    // Returns the prediction error
+    fn step(&mut self, x: f32) -> f32 {
+        let len = self.history.len();
+        let tap = |i: usize, pos: usize| (pos + len - LMS_DELAY - i) % len;
+        let mut prediction = 0.0;
+        let mut power = 1e-6;
+        for (i, w) in self.weights.iter().enumerate() {
+            let h = self.history[tap(i, self.pos)];
+            prediction += w * h;
+            power += h * h;
+        }
+        let error = x - prediction;
+        let step = self.mu * error / power;
+        for i in 0..LMS_TAPS {
+            let h = self.history[tap(i, self.pos)];
+            self.weights[i] += step * h;
+        }
+        self.history[self.pos] = x;
+        self.pos = (self.pos + 1) % len;
+        error
+    }
+}
+
+struct Agc {
+    envelope: f32,
+}
+
+impl Agc {
+    fn step(&mut self, x: f32, sample_rate: f32) -> f32 {
+        let level = x.abs();
+        let time = if level > self.envelope { AGC_ATTACK } else { AGC_DECAY };
+        self.envelope += (level - self.envelope) * (1.0 - (-1.0 / (time * sample_rate)).exp());
+        let gain = (AGC_TARGET / self.envelope.max(1e-6)).min(AGC_MAX_GAIN);
+        x * gain
+    }
+}
+
+// Peaking (constant 0 dB peak gain band pass) biquad
+struct Biquad {
+    sample_rate: f32,
+    b0: f32, b2: f32, a1: f32, a2: f32,
+    x1: f32, x2: f32, y1: f32, y2: f32,
+}
+
+impl Biquad {
+    fn band_pass(frequency: f32, q: f32, sample_rate: f32) -> Biquad {
+        let w0 = 2.0 * std::f32::consts::PI * frequency / sample_rate;
+        let alpha = w0.sin() / (2.0 * q);
+        let a0 = 1.0 + alpha;
+        Biquad {
+            sample_rate,
+            b0: alpha / a0, b2: -alpha / a0,
+            a1: -2.0 * w0.cos() / a0, a2: (1.0 - alpha) / a0,
+            x1: 0.0, x2: 0.0, y1: 0.0, y2: 0.0,
+        }
+    }
+
+    fn step(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.b2 * self.x2 - self.a1 * self.y1 - self.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
+
+// Filter state for one audio channel
+struct DspState {
+    nr: SpectralNr,
+    notch: Lms,
+    agc: Agc,
+    cw: Option<Biquad>,
+}
+
+impl DspState {
+    fn new() -> DspState {
+        DspState {
+            nr: SpectralNr::new(),
+            notch: Lms::new(NOTCH_MU),
+            agc: Agc { envelope: 0.0 },
+            cw: None,
+        }
+    }
+}
+
+// Client side processing of a receiver's audio
+pub struct Dsp {
+    settings: DspSettings,
+    // one state per audio channel (e.g. left/right)
+    states: Vec<DspState>,
+}
+
+impl Dsp {
+    pub fn new(settings: DspSettings) -> Dsp {
+        Dsp {
+            settings,
+            states: Vec::new(),
+        }
+    }
+
+    pub fn set_settings(&mut self, settings: DspSettings) {
+        self.settings = settings;
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.settings.any()
+    }
+
+    // Process the samples of one channel in place
+    pub fn process(&mut self, channel: usize, samples: &mut [f32], sample_rate: f32) {
+        while self.states.len() <= channel {
+            self.states.push(DspState::new());
+        }
+        let settings = self.settings;
+        let state = &mut self.states[channel];
+        if settings.cw_peak && state.cw.as_ref().map(|cw| cw.sample_rate != sample_rate).unwrap_or(true) {
+            state.cw = Some(Biquad::band_pass(CW_PITCH, CW_Q, sample_rate));
+        }
+
+        for sample in samples.iter_mut() {
+            let mut x = *sample;
+            if settings.auto_notch {
+                x = state.notch.step(x);
+            }
+            if settings.noise_reduction {
+                x = state.nr.step(x);
+            }
+            if let (true, Some(cw)) = (settings.cw_peak, state.cw.as_mut()) {
+                x = cw.step(x);
+            }
+            if settings.agc {
+                x = state.agc.step(x, sample_rate);
+            }
+            *sample = x.max(-1.0).min(1.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn fft_round_trips() {
+        let input: Vec<f32> = (0..64).map(|n| ((n * 7 % 13) as f32 - 6.0) / 6.0).collect();
+        let mut re = input.clone();
+        let mut im = vec![0.0; input.len()];
+        fft(&mut re, &mut im, false);
+        fft(&mut re, &mut im, true);
+        for (x, y) in input.iter().zip(re.iter()) {
+            assert!((x - y / input.len() as f32).abs() < 1e-4, "{} != {}", x, y);
+        }
+        assert!(im.iter().all(|v| v.abs() / (input.len() as f32) < 1e-4));
+    }
+
+    #[test]
+    fn fft_finds_a_tone() {
+        let n = 64;
+        let mut re: Vec<f32> = (0..n).map(|i| (2.0 * std::f32::consts::PI * 5.0 * i as f32 / n as f32).cos()).collect();
+        let mut im = vec![0.0; n];
+        fft(&mut re, &mut im, false);
+        let peak = (0..n / 2).max_by(|a, b| re[*a].hypot(im[*a]).partial_cmp(&re[*b].hypot(im[*b])).unwrap()).unwrap();
+        assert_eq!(peak, 5);
+        assert!((re[5].hypot(im[5]) - n as f32 / 2.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn notch_attenuates_a_steady_tone() {
+        let sample_rate = 48000.0;
+        let mut settings = DspSettings::default();
+        settings.toggle(DspFeature::AutoNotch);
+        let mut dsp = Dsp::new(settings);
+        let mut samples: Vec<f32> = (0..2 * sample_rate as usize)
+            .map(|i| 0.5 * (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / sample_rate).sin())
+            .collect();
+        let input = rms(&samples);
+        dsp.process(0, &mut samples, sample_rate);
+        let output = rms(&samples[samples.len() - 4800..]);
+        assert!(output < 0.2 * input, "tone only down to {} from {}", output, input);
+    }
+
+    #[test]
+    fn default_settings_are_inactive() {
+        let dsp = Dsp::new(DspSettings::default());
+        assert!(!dsp.is_active());
+    }
+}
@@ -0,0 +1,122 @@
+// Receiver filter edges are offsets (Hz) from the receiver frequency,
+// lower sideband modes use negative offsets
+pub struct FilterPreset {
+    pub name: &'static str,
+    pub low: f32,
+    pub high: f32,
+}
+
+// Narrowest filter the drag handles allow (Hz)
+pub const MIN_FILTER_WIDTH: f32 = 50.0;
+
+const SSB_USB: [FilterPreset; 4] = [
+    FilterPreset { name: "1.8k", low: 200.0, high: 2000.0 },
+    FilterPreset { name: "2.4k", low: 100.0, high: 2500.0 },
+    FilterPreset { name: "2.7k", low: 100.0, high: 2800.0 },
+    FilterPreset { name: "3.0k", low: 100.0, high: 3100.0 },
+];
+
+const SSB_LSB: [FilterPreset; 4] = [
+    FilterPreset { name: "1.8k", low: -2000.0, high: -200.0 },
+    FilterPreset { name: "2.4k", low: -2500.0, high: -100.0 },
+    FilterPreset { name: "2.7k", low: -2800.0, high: -100.0 },
+    FilterPreset { name: "3.0k", low: -3100.0, high: -100.0 },
+];
+
+const CW: [FilterPreset; 5] = [
+    FilterPreset { name: "50", low: -25.0, high: 25.0 },
+    FilterPreset { name: "100", low: -50.0, high: 50.0 },
+    FilterPreset { name: "250", low: -125.0, high: 125.0 },
+    FilterPreset { name: "500", low: -250.0, high: 250.0 },
+    FilterPreset { name: "1.0k", low: -500.0, high: 500.0 },
+];
+
+const AM: [FilterPreset; 3] = [
+    FilterPreset { name: "6k", low: -3000.0, high: 3000.0 },
+    FilterPreset { name: "8k", low: -4000.0, high: 4000.0 },
+    FilterPreset { name: "10k", low: -5000.0, high: 5000.0 },
+];
+
+const FM: [FilterPreset; 2] = [
+    FilterPreset { name: "10k", low: -5000.0, high: 5000.0 },
+    FilterPreset { name: "15k", low: -7500.0, high: 7500.0 },
+];
+
+const DIGITAL: [FilterPreset; 3] = [
+    FilterPreset { name: "2.5k", low: 200.0, high: 2700.0 },
+    FilterPreset { name: "3.0k", low: 0.0, high: 3000.0 },
+    FilterPreset { name: "4.0k", low: 0.0, high: 4000.0 },
+];
+
+// Filter presets for a receiver mode
+pub fn filter_presets(mode: &str) -> &'static [FilterPreset] {
+    match mode {
+        "USB" | "DigiU" => &SSB_USB,
+        "LSB" | "DigiL" => &SSB_LSB,
+        "CW" | "CWL" | "CWU" => &CW,
+        "AM" | "SAM" => &AM,
+        "FM" | "NFM" => &FM,
+        _ => &DIGITAL,
+    }
+}
+
+// Filter width label, e.g. "2.7k"
+pub fn width_name(low: f32, high: f32) -> String {
+    match (high - low).abs() {
+        width if width >= 1000.0 => format!("{:.1}k", width / 1000.0),
+        width => format!("{:.0}", width),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FilterEdge {
+    Low,
+    High,
+}
+
+// Move one edge of a filter to `offset` (Hz from the receiver
+// frequency), keeping at least MIN_FILTER_WIDTH between the edges
+pub fn move_edge(low: f32, high: f32, edge: FilterEdge, offset: f32) -> (f32, f32) {
+    match edge {
+        FilterEdge::Low => (offset.min(high - MIN_FILTER_WIDTH), high),
+        FilterEdge::High => (low, offset.max(low + MIN_FILTER_WIDTH)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves_one_edge() {
+        assert_eq!(move_edge(100.0, 2800.0, FilterEdge::Low, 300.0), (300.0, 2800.0));
+        assert_eq!(move_edge(100.0, 2800.0, FilterEdge::Low, -200.0), (-200.0, 2800.0));
+        assert_eq!(move_edge(100.0, 2800.0, FilterEdge::High, 3100.0), (100.0, 3100.0));
+        assert_eq!(move_edge(-2800.0, -100.0, FilterEdge::High, -300.0), (-2800.0, -300.0));
+    }
+
+    #[test]
+    fn edges_do_not_cross() {
+        // dragged past the other edge stops MIN_FILTER_WIDTH short of it
+        assert_eq!(move_edge(100.0, 2800.0, FilterEdge::Low, 5000.0), (2800.0 - MIN_FILTER_WIDTH, 2800.0));
+        assert_eq!(move_edge(100.0, 2800.0, FilterEdge::High, -1000.0), (100.0, 100.0 + MIN_FILTER_WIDTH));
+        assert_eq!(move_edge(-25.0, 25.0, FilterEdge::Low, 25.0), (-25.0, 25.0));
+    }
+
+    #[test]
+    fn keeps_the_minimum_width() {
+        let (low, high) = move_edge(100.0, 2800.0, FilterEdge::Low, 2790.0);
+        assert_eq!(high - low, MIN_FILTER_WIDTH);
+        let (low, high) = move_edge(100.0, 2800.0, FilterEdge::High, 2800.0 - MIN_FILTER_WIDTH + 1.0);
+        assert_eq!((low, high), (100.0, 2800.0 - MIN_FILTER_WIDTH + 1.0));
+        let (low, high) = move_edge(100.0, 2800.0, FilterEdge::High, 100.0 + MIN_FILTER_WIDTH);
+        assert_eq!(high - low, MIN_FILTER_WIDTH);
+    }
+
+    #[test]
+    fn width_names() {
+        assert_eq!(width_name(100.0, 2800.0), "2.7k");
+        assert_eq!(width_name(-2800.0, -100.0), "2.7k");
+        assert_eq!(width_name(-250.0, 250.0), "500");
+    }
+}
@@ -0,0 +1,237 @@
+use std::convert::TryInto;
+use std::fmt;
+
+// Binary frames from SparkSDR start with a one byte frame type
+// followed by the receiver id (big endian i32)
+//
+// Audio:    [type=1][receiver id][encoded audio ...]
+// Spectrum: [type=2][receiver id][start freq f64 LE][stop freq f64 LE][bins f32 LE ...]
+//
+// Transmit audio goes the other way as an audio frame carrying the radio
+// id and 16 bit little endian mono samples at pcm::RAW_SAMPLE_RATE.  This
+// is an assumption: SparkSDR's websocket API documentation does not
+// describe transmit audio, so the layout mirrors received audio with the
+// radio id where received frames carry the receiver id.  Only sent with
+// the experimental protocol extensions enabled.
+pub const FRAME_TYPE_AUDIO: u8 = 1;
+pub const FRAME_TYPE_SPECTRUM: u8 = 2;
+
+const HEADER_LEN: usize = 1 + 4;
+const SPECTRUM_HEADER_LEN: usize = HEADER_LEN + 8 + 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BinaryFrame {
+    Audio {
+        receiver_id: u32,
+        data: Vec<u8>,
+    },
+    Spectrum {
+        receiver_id: u32,
+        freq_start: f64,
+        freq_stop: f64,
+        bins: Vec<f32>,
+    },
+    Unknown {
+        frame_type: u8,
+        data: Vec<u8>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrameError {
+    // Frame contained no data at all
+    Empty,
+    // Frame is shorter than the header for its type
+    TooShort { frame_type: u8, expected: usize, actual: usize },
+    // Spectrum payload is not a whole number of f32 bins
+    MisalignedSpectrum { len: usize },
+    // Receiver id does not fit a u32
+    InvalidReceiverId(i32),
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrameError::Empty => write!(f, "empty binary frame"),
+            FrameError::TooShort { frame_type, expected, actual } =>
+                write!(f, "frame type {} too short: expected at least {} bytes, got {}", frame_type, expected, actual),
+            FrameError::MisalignedSpectrum { len } =>
+                write!(f, "spectrum payload of {} bytes is not a multiple of 4", len),
+            FrameError::InvalidReceiverId(id) => write!(f, "invalid receiver id: {}", id),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+impl BinaryFrame {
+    pub fn decode(data: &[u8]) -> Result<BinaryFrame, FrameError> {
+        let frame_type = *data.first().ok_or(FrameError::Empty)?;
+
+        match frame_type {
+            FRAME_TYPE_AUDIO => {
+                check_len(frame_type, data, HEADER_LEN)?;
+                Ok(BinaryFrame::Audio {
+                    receiver_id: receiver_id(data)?,
+                    data: data[HEADER_LEN..].to_vec(),
+                })
+            },
+            FRAME_TYPE_SPECTRUM => {
+                check_len(frame_type, data, SPECTRUM_HEADER_LEN)?;
+                let payload = &data[SPECTRUM_HEADER_LEN..];
+                if payload.len() % 4 != 0 {
+                    return Err(FrameError::MisalignedSpectrum { len: payload.len() });
+                }
+                Ok(BinaryFrame::Spectrum {
+                    receiver_id: receiver_id(data)?,
+                    freq_start: f64::from_le_bytes(data[5..13].try_into().unwrap()),
+                    freq_stop: f64::from_le_bytes(data[13..21].try_into().unwrap()),
+                    bins: payload.chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect(),
+                })
+            },
+            _ => Ok(BinaryFrame::Unknown { frame_type, data: data[1..].to_vec() }),
+        }
+    }
+
+    pub fn frame_type(&self) -> u8 {
+        match self {
+            BinaryFrame::Audio { .. } => FRAME_TYPE_AUDIO,
+            BinaryFrame::Spectrum { .. } => FRAME_TYPE_SPECTRUM,
+            BinaryFrame::Unknown { frame_type, .. } => *frame_type,
+        }
+    }
+
+    pub fn receiver_id(&self) -> Option<u32> {
+        match self {
+            BinaryFrame::Audio { receiver_id, .. } => Some(*receiver_id),
+            BinaryFrame::Spectrum { receiver_id, .. } => Some(*receiver_id),
+            BinaryFrame::Unknown { .. } => None,
+        }
+    }
+}
+
+// Build a transmit audio frame from samples in -1.0..1.0
+pub fn encode_audio(radio_id: u32, samples: &[f32]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(HEADER_LEN + samples.len() * 2);
+    data.push(FRAME_TYPE_AUDIO);
+    data.extend_from_slice(&(radio_id as i32).to_be_bytes());
+    for sample in samples.iter() {
+        // same scale pcm::decode uses
+        let sample = (sample * 32768.0).max(-32768.0).min(32767.0) as i16;
+        data.extend_from_slice(&sample.to_le_bytes());
+    }
+    data
+}
+
+fn check_len(frame_type: u8, data: &[u8], expected: usize) -> Result<(), FrameError> {
+    match data.len() {
+        actual if actual < expected => Err(FrameError::TooShort { frame_type, expected, actual }),
+        _ => Ok(()),
+    }
+}
+
+fn receiver_id(data: &[u8]) -> Result<u32, FrameError> {
+    let id = i32::from_be_bytes(data[1..5].try_into().unwrap());
+    if id < 0 {
+        Err(FrameError::InvalidReceiverId(id))
+    } else {
+        Ok(id as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(frame_type: u8, receiver_id: i32) -> Vec<u8> {
+        let mut data = vec![frame_type];
+        data.extend_from_slice(&receiver_id.to_be_bytes());
+        data
+    }
+
+    #[test]
+    fn decodes_audio_frame() {
+        let mut data = header(FRAME_TYPE_AUDIO, 7);
+        data.extend_from_slice(&[1, 2, 3, 4]);
+        assert_eq!(BinaryFrame::decode(&data), Ok(BinaryFrame::Audio { receiver_id: 7, data: vec![1, 2, 3, 4] }));
+    }
+
+    #[test]
+    fn decodes_spectrum_frame() {
+        let mut data = header(FRAME_TYPE_SPECTRUM, 0x0102_0304);
+        data.extend_from_slice(&14_000_000f64.to_le_bytes());
+        data.extend_from_slice(&14_350_000f64.to_le_bytes());
+        data.extend_from_slice(&(-120.5f32).to_le_bytes());
+        data.extend_from_slice(&(-73.0f32).to_le_bytes());
+        assert_eq!(BinaryFrame::decode(&data), Ok(BinaryFrame::Spectrum {
+            receiver_id: 0x0102_0304,
+            freq_start: 14_000_000.0,
+            freq_stop: 14_350_000.0,
+            bins: vec![-120.5, -73.0],
+        }));
+    }
+
+    #[test]
+    fn rejects_empty_frame() {
+        assert_eq!(BinaryFrame::decode(&[]), Err(FrameError::Empty));
+    }
+
+    #[test]
+    fn rejects_short_audio_header() {
+        assert_eq!(BinaryFrame::decode(&[FRAME_TYPE_AUDIO, 0, 0]),
+            Err(FrameError::TooShort { frame_type: FRAME_TYPE_AUDIO, expected: HEADER_LEN, actual: 3 }));
+    }
+
+    #[test]
+    fn rejects_short_spectrum_header() {
+        let mut data = header(FRAME_TYPE_SPECTRUM, 1);
+        data.extend_from_slice(&14_000_000f64.to_le_bytes());
+        assert_eq!(BinaryFrame::decode(&data),
+            Err(FrameError::TooShort { frame_type: FRAME_TYPE_SPECTRUM, expected: SPECTRUM_HEADER_LEN, actual: 13 }));
+    }
+
+    #[test]
+    fn rejects_misaligned_spectrum() {
+        let mut data = header(FRAME_TYPE_SPECTRUM, 1);
+        data.extend_from_slice(&0f64.to_le_bytes());
+        data.extend_from_slice(&0f64.to_le_bytes());
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+        assert_eq!(BinaryFrame::decode(&data), Err(FrameError::MisalignedSpectrum { len: 6 }));
+    }
+
+    #[test]
+    fn rejects_negative_receiver_id() {
+        assert_eq!(BinaryFrame::decode(&header(FRAME_TYPE_AUDIO, -1)), Err(FrameError::InvalidReceiverId(-1)));
+    }
+
+    #[test]
+    fn keeps_unknown_frame_types() {
+        let frame = BinaryFrame::decode(&[9, 1, 2]).unwrap();
+        assert_eq!(frame, BinaryFrame::Unknown { frame_type: 9, data: vec![1, 2] });
+        assert_eq!(frame.frame_type(), 9);
+        assert_eq!(frame.receiver_id(), None);
+    }
+
+    #[test]
+    fn encoded_audio_round_trips() {
+        let samples = [0.0, 0.5, -0.5, 1.0, -1.0];
+        let data = encode_audio(3, &samples);
+        match BinaryFrame::decode(&data) {
+            Ok(BinaryFrame::Audio { receiver_id, data }) => {
+                assert_eq!(receiver_id, 3);
+                let decoded: Vec<f32> = data.chunks_exact(2)
+                    .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0)
+                    .collect();
+                // full scale positive clips one step short of 1.0
+                assert_eq!(decoded, vec![0.0, 0.5, -0.5, 32767.0 / 32768.0, -1.0]);
+            },
+            other => panic!("unexpected frame: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn encoded_audio_is_clamped() {
+        let data = encode_audio(1, &[2.0, -2.0]);
+        assert_eq!(&data[HEADER_LEN..], &[0xff, 0x7f, 0x00, 0x80]);
+    }
+}
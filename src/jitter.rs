@@ -0,0 +1,250 @@
+// Playback scheduling for one receiver's audio.  Decoded packets are
+// queued back to back on the AudioContext clock, `latency` ahead of the
+// current time.  The target latency grows after an underrun and slowly
+// shrinks again while playback is stable, packets are dropped when too
+// far ahead and the playback rate is nudged to absorb clock drift
+// between SparkSDR and the sound card.
+
+// Target latency limits and the value used after a reset (seconds)
+const MIN_TARGET_LATENCY: f64 = 0.05;
+const MAX_TARGET_LATENCY: f64 = 0.5;
+const INITIAL_TARGET_LATENCY: f64 = 0.1;
+// Growth of the target latency after an underrun
+const UNDERRUN_GROWTH: f64 = 1.5;
+// Stable packets before the target latency is lowered, and by how much
+const STABLE_PACKETS: u64 = 500;
+const STABLE_SHRINK: f64 = 0.9;
+// Packets are dropped when the queue is longer than the target by this
+// much (or by the target itself, whichever is larger)
+const OVERRUN_MARGIN: f64 = 0.1;
+// Latency error (fraction of the target) before the rate is adjusted,
+// and the largest adjustment
+const DRIFT_TOLERANCE: f64 = 0.2;
+const MAX_RATE_ADJUST: f64 = 0.005;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JitterStats {
+    // queued audio ahead of the current time and the target (seconds)
+    pub latency: f64,
+    pub target: f64,
+    // length of the last packet (seconds)
+    pub packet_duration: f64,
+    pub packets: u64,
+    pub underruns: u64,
+    pub overruns: u64,
+    // playback rate used for drift correction
+    pub rate: f64,
+}
+
+pub enum Schedule {
+    // play at the given context time with the given playback rate
+    Play { when: f64, rate: f64 },
+    // too much queued already
+    Drop,
+}
+
+pub struct JitterBuffer {
+    // context time the queued audio runs out
+    next_time: Option<f64>,
+    target: f64,
+    stable: u64,
+    stats: JitterStats,
+}
+
+impl JitterBuffer {
+    pub fn new() -> JitterBuffer {
+        JitterBuffer {
+            next_time: None,
+            target: INITIAL_TARGET_LATENCY,
+            stable: 0,
+            stats: JitterStats { target: INITIAL_TARGET_LATENCY, rate: 1.0, ..JitterStats::default() },
+        }
+    }
+
+    // Start from scratch (e.g. after a reconnect), keeps the counters
+    pub fn reset(&mut self) {
+        self.next_time = None;
+        self.target = INITIAL_TARGET_LATENCY;
+        self.stable = 0;
+        self.stats.target = self.target;
+        self.stats.latency = 0.0;
+        self.stats.rate = 1.0;
+    }
+
+    pub fn stats(&self) -> JitterStats {
+        self.stats
+    }
+
+    // Schedule a decoded packet of `duration` seconds at context time `now`
+    pub fn schedule(&mut self, now: f64, duration: f64) -> Schedule {
+        self.stats.packets += 1;
+        self.stats.packet_duration = duration;
+
+        let next_time =
+            match self.next_time {
+                Some(next_time) if next_time >= now => next_time,
+                Some(_) => {
+                    // ran dry, leave a gap and queue further ahead
+                    self.stats.underruns += 1;
+                    self.stable = 0;
+                    self.target = (self.target * UNDERRUN_GROWTH).min(MAX_TARGET_LATENCY);
+                    now + self.target
+                },
+                None => now + self.target,
+            };
+
+        let latency = next_time - now;
+        self.stats.latency = latency;
+        self.stats.target = self.target;
+        if latency > self.target + OVERRUN_MARGIN.max(self.target) {
+            self.stats.overruns += 1;
+            self.next_time = Some(next_time);
+            return Schedule::Drop;
+        }
+
+        self.stable += 1;
+        if self.stable >= STABLE_PACKETS {
+            self.stable = 0;
+            self.target = (self.target * STABLE_SHRINK).max(MIN_TARGET_LATENCY);
+        }
+
+        // play slightly faster while more than the target is queued,
+        // slower while less is
+        let error = (latency - self.target) / self.target;
+        let rate =
+            if error.abs() > DRIFT_TOLERANCE {
+                1.0 + (error * MAX_RATE_ADJUST).max(-MAX_RATE_ADJUST).min(MAX_RATE_ADJUST)
+            } else {
+                1.0
+            };
+        self.stats.rate = rate;
+        self.next_time = Some(next_time + duration / rate);
+        Schedule::Play { when: next_time, rate }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(schedule: Schedule) -> (f64, f64) {
+        match schedule {
+            Schedule::Play { when, rate } => (when, rate),
+            Schedule::Drop => panic!("packet dropped"),
+        }
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn first_packet_is_queued_at_the_target() {
+        let mut buffer = JitterBuffer::new();
+        let (when, rate) = play(buffer.schedule(1.0, 0.02));
+        assert!(close(when, 1.0 + INITIAL_TARGET_LATENCY));
+        assert!(close(rate, 1.0));
+    }
+
+    #[test]
+    fn underrun_grows_the_target() {
+        let mut buffer = JitterBuffer::new();
+        play(buffer.schedule(0.0, 0.02));
+        // queue ran out at 0.12
+        let (when, _) = play(buffer.schedule(0.5, 0.02));
+        let target = INITIAL_TARGET_LATENCY * UNDERRUN_GROWTH;
+        assert!(close(when, 0.5 + target));
+        assert_eq!(buffer.stats().underruns, 1);
+        assert!(close(buffer.stats().target, target));
+
+        // never past the maximum
+        let mut now = 0.5;
+        for _ in 0..20 {
+            now += 10.0;
+            buffer.schedule(now, 0.02);
+        }
+        assert_eq!(buffer.stats().underruns, 21);
+        assert!(close(buffer.stats().target, MAX_TARGET_LATENCY));
+    }
+
+    #[test]
+    fn overrun_drops_packets() {
+        let mut buffer = JitterBuffer::new();
+        // 0.3 s queued after the first packet, the limit is 0.2 s
+        play(buffer.schedule(0.0, 0.2));
+        assert!(matches!(buffer.schedule(0.0, 0.2), Schedule::Drop));
+        assert_eq!(buffer.stats().overruns, 1);
+        assert!(close(buffer.stats().latency, 0.3));
+        // dropped packets do not add to the queue
+        assert!(matches!(buffer.schedule(0.0, 0.2), Schedule::Drop));
+        assert!(close(buffer.stats().latency, 0.3));
+        // playable again once the queue has drained
+        play(buffer.schedule(0.15, 0.02));
+        assert_eq!(buffer.stats().overruns, 2);
+    }
+
+    #[test]
+    fn stable_playback_shrinks_the_target() {
+        let mut buffer = JitterBuffer::new();
+        let duration = 0.02;
+        let mut now = 0.0;
+        for _ in 0..STABLE_PACKETS {
+            let (_, rate) = play(buffer.schedule(now, duration));
+            assert!(close(rate, 1.0));
+            now += duration;
+        }
+        play(buffer.schedule(now, duration));
+        assert!(close(buffer.stats().target, INITIAL_TARGET_LATENCY * STABLE_SHRINK));
+        assert_eq!(buffer.stats().underruns, 0);
+    }
+
+    #[test]
+    fn stable_shrink_stops_at_the_minimum() {
+        let mut buffer = JitterBuffer::new();
+        buffer.target = MIN_TARGET_LATENCY * 1.04;
+        let duration = 0.02;
+        let mut now = 0.0;
+        for _ in 0..=STABLE_PACKETS {
+            play(buffer.schedule(now, duration));
+            now += duration;
+        }
+        assert!(close(buffer.stats().target, MIN_TARGET_LATENCY));
+    }
+
+    #[test]
+    fn drift_adjusts_the_rate_within_limits() {
+        let mut buffer = JitterBuffer::new();
+        // within tolerance of the target
+        buffer.next_time = Some(1.0 + INITIAL_TARGET_LATENCY * 1.1);
+        let (_, rate) = play(buffer.schedule(1.0, 0.02));
+        assert!(close(rate, 1.0));
+
+        // nearly as far ahead as allowed, play faster
+        buffer.next_time = Some(2.0 + 0.19);
+        let (_, rate) = play(buffer.schedule(2.0, 0.02));
+        assert!(rate > 1.0 && rate <= 1.0 + MAX_RATE_ADJUST);
+        // the queue advances by the adjusted duration
+        assert!(close(buffer.next_time.unwrap(), 2.19 + 0.02 / rate));
+
+        // about to run dry, play as slow as allowed
+        buffer.next_time = Some(3.0);
+        let (when, rate) = play(buffer.schedule(3.0, 0.02));
+        assert!(close(when, 3.0));
+        assert!(close(rate, 1.0 - MAX_RATE_ADJUST));
+    }
+
+    #[test]
+    fn reset_keeps_the_counters() {
+        let mut buffer = JitterBuffer::new();
+        play(buffer.schedule(0.0, 0.02));
+        play(buffer.schedule(1.0, 0.02));
+        buffer.reset();
+        let stats = buffer.stats();
+        assert_eq!(stats.packets, 2);
+        assert_eq!(stats.underruns, 1);
+        assert!(close(stats.target, INITIAL_TARGET_LATENCY));
+        assert!(close(stats.rate, 1.0));
+        let (when, _) = play(buffer.schedule(5.0, 0.02));
+        assert!(close(when, 5.0 + INITIAL_TARGET_LATENCY));
+    }
+}
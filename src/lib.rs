@@ -6,8 +6,6 @@ use wasm_bindgen::prelude::*;
 use yew::{html, Component, ComponentLink, Html, ShouldRender};
 use yew::services::{ConsoleService};
 use yew_router::{Switch};
-use web_sys::{HtmlCanvasElement};
-use js_sys::{DataView};
 
 use ham_rs::lotw::LoTWStatus;
 use sparkplug::{Command,CommandResponse};
@@ -17,9 +15,30 @@ mod color;
 mod spot;
 mod audio;
 mod spectrum;
+mod reconnect;
+mod frame;
+mod panadapter;
+mod tuning;
+mod bands;
+mod memory;
+mod scanner;
+mod filters;
+mod commands;
+mod jitter;
+mod pcm;
+mod recording;
+mod dsp;
+mod meter;
+mod spot_filter;
+mod spot_history;
+mod station;
+mod transmit;
 
 use model::{Model,Msg,AppRoute};
 use spot::{SpotFilter};
+use spot_filter::{FilterContext};
+use frame::{BinaryFrame};
+use transmit::{TxError};
 
 impl Component for Model {
     type Message = Msg;
@@ -28,12 +47,7 @@ impl Component for Model {
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
             Msg::Connected => {
-                // When we first connect to SparkSDR gather some basic information
-                self.send_command(Command::GetReceivers);
-                self.send_command(Command::GetRadios);
-                self.send_command(Command::GetVersion);
-                // Also subscribe to spots
-                self.send_command(Command::SubscribeToSpots{ enable: true });
+                self.connection_opened();
 
                 // fetch the lotw users file
                 if !self.spots.has_lotw_users() {
@@ -57,23 +71,13 @@ impl Component for Model {
                     },
                     // spotResponse: new incoming spots
                     CommandResponse::Spots { spots } => {
-                        let cq_only = self.spots.cq_only_spot_filter_enabled();
+                        let receiver = self.default_receiver();
                         for spot in spots {
-                            if (cq_only && spot.is_cq()) || !cq_only {
-                                let current_rx_pass =
-                                    match self.default_receiver() {
-                                        Some(receiver) if self.spots.current_receiver_spot_filter_enabled() && receiver.has_spots() => {
-                                            if spot.current_rx(&receiver) { true } else { false }
-                                        },
-                                        _ => true,
-                                    };
-
-                                if current_rx_pass {
-                                    self.spots.add_spot(&self.link, spot, &self.import);
-                                }
-                            }
+                            self.log_spot_signal(&spot);
+                            let ctx = FilterContext { logs: self.import.as_ref(), log_generation: self.import_generation, receiver: receiver.as_ref() };
+                            self.spots.add_spot(&self.link, spot, &ctx);
                         }
-                        self.spots.trim_spots(100);
+                        self.prune_spots();
                     },
                     // ReceiverResponse: receiver updates (mode/frequency)
                     CommandResponse::ReceiverResponse{ id: receiver_id, frequency, mode, filter_low, filter_high } => {
@@ -82,50 +86,96 @@ impl Component for Model {
                 }
                 true
             },
-            Msg::EnableAudio => {
-                match self.audio.receiving_audio() {
-                    Some(_) => {
-                        self.unsubscribe_to_audio();
-                    },
-                    None => {
-                        self.subscribe_to_audio();
-                    }
-                }
+            Msg::ToggleAudio(receiver_id) => {
+                self.toggle_audio(receiver_id);
                 true
             },
             Msg::ReceivedAudio(data) => {
-                let view = DataView::new(&data, 0, data.byte_length() as usize);
-                let data_type = view.get_uint8(0);
-                let receiver_id = view.get_int32(1);
+                let data = js_sys::Uint8Array::new(&data).to_vec();
+                let frame = match BinaryFrame::decode(&data) {
+                    Ok(frame) => frame,
+                    Err(err) => {
+                        ConsoleService::error(&format!("invalid binary frame: {}", err));
+                        return false;
+                    }
+                };
 
-                match (data_type, self.audio.receiving_audio(), self.spectrum.receiving_spectrum()) {
-                    (1, Some(_), _) => {
-                        self.audio.import_audio_data(data);
+                match frame {
+                    BinaryFrame::Audio { receiver_id, data } if self.audio.is_subscribed(receiver_id) => {
+                        return self.audio.import_audio_data(receiver_id, &data);
                     },
-                    (2, _, Some(subscribed_spectrum)) => {// if subscribed_spectrum == (receiver_id as u32) => {
-                        match self.default_receiver() {
-                            Some(receiver) => {
-                                let freq_start = view.get_float64_endian(5, true).floor();
-                                let freq_stop = view.get_float64_endian(13, true).floor();
-                                self.spectrum.import_spectrum_data(data, freq_start, freq_stop);
-                            },
-                            None => () // should never happen
-                        }
+                    BinaryFrame::Spectrum { receiver_id, freq_start, freq_stop, bins } if self.spectrum.receiving_spectrum(receiver_id) => {
+                        let meter_changed = self.measure_spectrum(receiver_id, &bins, freq_start, freq_stop);
+                        let resized = self.spectrum.import_spectrum_data(receiver_id, &bins, freq_start.floor(), freq_stop.floor());
+                        return meter_changed || resized;
                     },
-                    (2, _, Some(_)) => (),
-                    (_, None, None) => {
+                    // late frames from a receiver we just unsubscribed from
+                    BinaryFrame::Spectrum { .. } => (),
+                    _ if self.audio.subscribed().is_empty() && self.spectrum.subscribed().is_empty() => {
                         ConsoleService::error("receiving binary data but not subscribed to anything");
                     },
-                    (dt, _, _) => {
-                        ConsoleService::error(&format!("unsupported data type: {}", dt));
+                    frame => {
+                        ConsoleService::error(&format!("unsupported data type: {}", frame.frame_type()));
                     }
                 }
                 false
             },
+            Msg::SetTraceMode(mode) => {
+                self.spectrum.set_trace_mode(mode);
+                true
+            },
+            Msg::SetReferenceLevel(reference) => {
+                self.spectrum.set_reference_level(reference);
+                true
+            },
+            Msg::SetWaterfallRange(range) => {
+                self.spectrum.set_range(range);
+                true
+            },
+            Msg::ToggleAutoLevels => {
+                self.spectrum.toggle_auto_levels();
+                true
+            },
+            Msg::SetPalette(palette) => {
+                self.spectrum.set_palette(palette);
+                true
+            },
             Msg::MuteUnmute => {
                 self.audio.toggle_mute();
                 true
             },
+            Msg::ToggleRecording => {
+                self.toggle_recording();
+                true
+            },
+            Msg::SetReceiverVolume(receiver_id, volume) => {
+                self.audio.set_volume(receiver_id, volume);
+                false
+            },
+            Msg::SetReceiverPan(receiver_id, pan) => {
+                self.audio.set_pan(receiver_id, pan);
+                false
+            },
+            Msg::ToggleReceiverMute(receiver_id) => {
+                self.audio.toggle_channel_mute(receiver_id);
+                true
+            },
+            Msg::ToggleReceiverSolo(receiver_id) => {
+                self.audio.toggle_solo(receiver_id);
+                true
+            },
+            Msg::ToggleRawPcm => {
+                self.audio.toggle_raw_pcm();
+                true
+            },
+            Msg::ToggleExperimental => {
+                self.toggle_experimental();
+                true
+            },
+            Msg::ToggleDsp(receiver_id, feature) => {
+                self.audio.toggle_dsp(receiver_id, feature);
+                true
+            },
             Msg::CommandResponse(Err(err)) => {
                 ConsoleService::error(&format!("command response error: {}", err));
                 false
@@ -140,13 +190,140 @@ impl Component for Model {
                     _ => ()
                 }
 
-                self.spots.cache_callsign_info(call, &self.import);
+                let receiver = self.default_receiver();
+                let ctx = FilterContext { logs: self.import.as_ref(), log_generation: self.import_generation, receiver: receiver.as_ref() };
+                self.spots.cache_callsign_info(call, &ctx);
                 true
             },
             Msg::CallsignInfoReady(Err(err)) => {
                 ConsoleService::error(&format!("callsign info error: {}", err));
                 false
             },
+            Msg::ToggleScanner => {
+                self.toggle_scanner();
+                true
+            },
+            Msg::ScannerTick => {
+                self.scanner_tick();
+                true
+            },
+            Msg::SkipScanChannel => {
+                self.skip_scan_channel();
+                true
+            },
+            Msg::SetScanStart(input) => {
+                self.set_scan_start(&input);
+                true
+            },
+            Msg::SetScanStop(input) => {
+                self.set_scan_stop(&input);
+                true
+            },
+            Msg::SetScanStep(step) => {
+                self.scanner.set_step(step);
+                true
+            },
+            Msg::SetSquelch(squelch) => {
+                self.scanner.set_squelch(squelch);
+                true
+            },
+            Msg::SetScanDwell(seconds) => {
+                self.scanner.set_dwell(seconds);
+                true
+            },
+            Msg::SetScanResume(seconds) => {
+                self.scanner.set_resume(seconds);
+                true
+            },
+            Msg::SetMemoryName(name) => {
+                self.memories.draft.name = name;
+                false
+            },
+            Msg::SetMemoryNotes(notes) => {
+                self.memories.draft.notes = notes;
+                false
+            },
+            Msg::SetMemoryTags(tags) => {
+                self.memories.draft.tags = tags;
+                false
+            },
+            Msg::SaveMemory => {
+                self.save_memory();
+                true
+            },
+            Msg::RecallMemory(index) => {
+                self.recall_memory(index);
+                true
+            },
+            Msg::DeleteMemory(index) => {
+                self.memories.remove(index);
+                true
+            },
+            Msg::SetMemoryTarget(receiver_id) => {
+                self.memories.set_target(receiver_id);
+                true
+            },
+            Msg::ImportMemories(files) => {
+                for file in files.into_iter() {
+                    self.read_memories_file(file);
+                }
+                false
+            },
+            Msg::MemoriesLoaded(data) => {
+                self.load_memories(data);
+                true
+            },
+            Msg::StartMemoryScan => {
+                self.start_memory_scan();
+                true
+            },
+            Msg::StopMemoryScan => {
+                self.memories.stop_scan();
+                true
+            },
+            Msg::MemoryScanStep => {
+                self.memory_scan_step();
+                true
+            },
+            Msg::SetMemoryScanInterval(seconds) => {
+                self.set_memory_scan_interval(seconds);
+                true
+            },
+            Msg::PttDown => {
+                self.start_transmit();
+                true
+            },
+            Msg::PttUp => {
+                self.stop_transmit();
+                true
+            },
+            Msg::PttTimeout => {
+                self.transmit_failed(TxError::Timeout);
+                true
+            },
+            Msg::MicrophoneReady(stream) => {
+                self.microphone_ready(stream);
+                true
+            },
+            Msg::TransmitError(err) => {
+                self.transmit_failed(TxError::Microphone(err));
+                true
+            },
+            Msg::TransmitTick => {
+                true
+            },
+            Msg::ClearTransmitError => {
+                self.clear_transmit_error();
+                true
+            },
+            Msg::ExtResponse(response) => {
+                self.set_ext_response(response);
+                true
+            },
+            Msg::ClearSignalLog => {
+                self.clear_signal_log();
+                true
+            },
             Msg::ClearSpots => {
                 self.spots.clear_spots();
                 true
@@ -156,13 +333,21 @@ impl Component for Model {
                 true
             },
             Msg::AddReceiver(radio_id) => {
-                self.send_command(Command::AddReceiver { id: radio_id });
+                self.add_receiver(radio_id);
                 false
             },
+            Msg::ToggleSpectrum(receiver_id) => {
+                self.toggle_spectrum(receiver_id);
+                true
+            },
             Msg::RemoveReceiver(receiver_id) => {
                 self.send_command(Command::RemoveReceiver{ id: receiver_id });
                 false
             },
+            Msg::RemoveRadioReceivers(radio_id) => {
+                self.remove_radio_receivers(radio_id);
+                false
+            },
             Msg::TogglePower(radio_id) => {
                 match self.get_radio_power_state(radio_id) {
                     Some(state) => {
@@ -182,6 +367,50 @@ impl Component for Model {
                 self.change_receiver_mode(receiver_id, mode);
                 true
             },
+            Msg::ApplyFilterPreset(receiver_id, preset) => {
+                self.apply_filter_preset(receiver_id, preset);
+                true
+            },
+            Msg::StartFilterDrag(receiver_id, edge) => {
+                self.start_filter_drag(receiver_id, edge);
+                false
+            },
+            Msg::EndFilterDrag => {
+                self.end_filter_drag()
+            },
+            Msg::TuneWaterfall(receiver_id, ratio) => {
+                self.tune_waterfall(receiver_id, ratio);
+                true
+            },
+            Msg::TuneStep(receiver_id, steps) => {
+                self.tune_step(receiver_id, steps);
+                true
+            },
+            Msg::TuneDefaultReceiver(steps) => {
+                match self.default_receiver() {
+                    Some(receiver) => {
+                        self.tune_step(receiver.id, steps);
+                        true
+                    },
+                    None => false
+                }
+            },
+            Msg::FlushFrequency => {
+                self.flush_frequency();
+                false
+            },
+            Msg::EnterFrequency(receiver_id, input) => {
+                self.enter_frequency(receiver_id, &input);
+                true
+            },
+            Msg::ApplyBandPreset(receiver_id, preset) => {
+                self.apply_band_preset(receiver_id, preset);
+                true
+            },
+            Msg::SetTuningStep(step) => {
+                self.set_tuning_step(step);
+                true
+            },
             Msg::FrequencyDown(receiver_id, digit) => {
                 self.frequency_down(receiver_id, digit);
                 true
@@ -201,10 +430,18 @@ impl Component for Model {
                 true
             },
             Msg::Disconnected => {
-                self.disconnect();
+                self.connection_lost();
                 ConsoleService::error("Disconnected");
                 true
             },
+            Msg::Disconnect => {
+                self.close();
+                true
+            },
+            Msg::Reconnect => {
+                self.reconnect();
+                true
+            },
             Msg::SetGain(gain) => {
                 self.audio.set_gain(gain);
                 true
@@ -253,6 +490,7 @@ impl Component for Model {
                     true => self.spots.remove_filter(SpotFilter::CQOnly).unwrap(),
                     false => self.spots.add_filter(SpotFilter::CQOnly),
                 }
+                self.refresh_spot_markers();
                 true
             },
             Msg::ToggleCountrySpotFilter => {
@@ -260,6 +498,7 @@ impl Component for Model {
                     true => self.spots.remove_filter(SpotFilter::NewCountry).unwrap(),
                     false => self.spots.add_filter(SpotFilter::NewCountry),
                 }
+                self.refresh_spot_markers();
                 true
             },
             Msg::ToggleStateSpotFilter => {
@@ -277,6 +516,7 @@ impl Component for Model {
                     },
                 }
                 self.spots.update_states_overlay_js();
+                self.refresh_spot_markers();
                 true
             },
             Msg::ToggleCurrentReceiverSpotFilter => {
@@ -284,6 +524,7 @@ impl Component for Model {
                     true => self.spots.remove_filter(SpotFilter::CurrentReceiver).unwrap(),
                     false => self.spots.add_filter(SpotFilter::CurrentReceiver),
                 }
+                self.refresh_spot_markers();
                 true
             },
             Msg::ToggleLoTWSpotFilter => {
@@ -291,8 +532,55 @@ impl Component for Model {
                     true => self.spots.remove_filter(SpotFilter::LoTW).unwrap(),
                     false => self.spots.add_filter(SpotFilter::LoTW),
                 }
+                self.refresh_spot_markers();
                 true
             }
+            Msg::SelectSpotFilterPreset(name) => {
+                self.select_spot_filter_preset(&name);
+                true
+            },
+            Msg::SetSpotFilterName(name) => {
+                self.spots.filter_presets.draft.name = name;
+                false
+            },
+            Msg::SetSpotFilterExpression(expression) => {
+                self.spots.filter_presets.draft.expression = expression;
+                false
+            },
+            Msg::SaveSpotFilterPreset => {
+                self.save_spot_filter_preset();
+                true
+            },
+            Msg::DeleteSpotFilterPreset => {
+                self.delete_spot_filter_preset();
+                true
+            },
+            Msg::PruneSpots => {
+                // the history is saved here rather than for every batch
+                // of spots
+                let failed = self.spots.history.save().is_err();
+                self.prune_spots() || failed
+            },
+            Msg::SetSpotRetention(retention) => {
+                self.set_spot_retention(retention);
+                true
+            },
+            Msg::SetHistoryCall(call) => {
+                self.spots.history.query.call = call;
+                true
+            },
+            Msg::SetHistoryBand(band) => {
+                self.spots.history.query.band = band;
+                true
+            },
+            Msg::ClearSpotHistory => {
+                self.spots.history.clear();
+                true
+            },
+            Msg::ToggleSpotAggregation => {
+                self.spots.toggle_aggregate();
+                true
+            },
             Msg::None => { false }
         }
     }
@@ -308,11 +596,7 @@ impl Component for Model {
     }
 
     fn rendered(&mut self, first_render: bool) {
-        let canvas = self.spectrum.canvas_node_ref.cast::<HtmlCanvasElement>().unwrap();
-        self.spectrum.canvas = Some(canvas);
-
-        let tmp_canvas = self.spectrum.tmp_canvas_node_ref.cast::<HtmlCanvasElement>().unwrap();
-        self.spectrum.tmp_canvas = Some(tmp_canvas);
+        self.spectrum.bind_canvases();
 
         if first_render {
             self.audio.create_audio_context();
@@ -321,18 +605,22 @@ impl Component for Model {
     }
 
     fn view(&self) -> Html {
+        let route = AppRoute::switch(self.route.clone());
         let (is_index, spectrum_style, map_style) =
-            match AppRoute::switch(self.route.clone()) {
+            match route {
                 Some(AppRoute::Index) | None => (true, "position:relative;margin-top:10px", "height:0px;overflow:hidden;"),
-                _ => (false, "height:110px;overflow:hidden;position:relative;margin-top:10px", ""),
+                Some(AppRoute::Memories) | Some(AppRoute::Radio(_)) => (false, "height:110px;overflow:hidden;position:relative;margin-top:10px", "height:0px;overflow:hidden;"),
+                Some(AppRoute::Map) => (false, "height:110px;overflow:hidden;position:relative;margin-top:10px", ""),
             };
 
-        match self.is_connected() {
+        match self.is_connected() || self.is_reconnecting() {
             false => self.disconnected_view(),
             true => {
                 html! {
                     <>
                         { self.navbar_view() }
+                        { self.reconnect_view() }
+                        { self.transmit_error_view() }
 
                         <div style="clear:both"></div>
 
@@ -341,21 +629,20 @@ impl Component for Model {
 
                         <div style="margin-left:15em;padding:0 10px 0 20px">
                             <div style=spectrum_style>
+                                { if is_index { self.panadapter_canvas(self.spectrum.primary()) } else { html! {} } }
                                 <div id="receiver-marker" style="display:none">
                                     <div></div>
                                 </div>
-                                <canvas id="waterfall" ref=self.spectrum.canvas_node_ref.clone() width="2048" height="200" style="position:relative;width:100%;height:200px;background-color: black" />
+                                { self.waterfall_canvas(self.spectrum.primary()) }
                             </div>
                             {
                                 if is_index {
                                     html! {
                                         <>
-                                            <table style="width:100%;border-left:2px solid #555;border-right:2px solid #555">
-                                                <tr>
-                                                    <th style="padding-left:10px" id="freq-start">{ self.spectrum.freq_start() }</th>
-                                                    <th style="text-align:right;padding-right:10px" id="freq-end">{ self.spectrum.freq_stop() }</th>
-                                                </tr>
-                                            </table>
+                                            { self.waterfall_range(self.spectrum.primary()) }
+                                            { self.spectrum_controls_view() }
+                                            { self.scanner_view() }
+                                            { self.pinned_waterfalls_view() }
                                             { self.spots_view() }
                                         </>
                                     }
@@ -363,12 +650,19 @@ impl Component for Model {
                                     html! { }
                                 }
                             }
+                            {
+                                match route {
+                                    Some(AppRoute::Memories) => self.memories_view(),
+                                    Some(AppRoute::Radio(radio_id)) => self.radio_view(radio_id),
+                                    _ => html! {},
+                                }
+                            }
                             <div style=map_style>
                                 <div id="map" style="width:100%;height:600px" class="has-background-light"> </div>
                             </div>
                         </div>
 
-                        <canvas ref=self.spectrum.tmp_canvas_node_ref.clone() width="2048" height="200" style="display:none;background-color: black ;" />
+                        { self.waterfall_tmp_canvases() }
 
                         { self.footer_view() }
                     </>
@@ -0,0 +1,381 @@
+use std::fmt;
+use std::time::Duration;
+use yew::{ComponentLink};
+use yew::format::{Json};
+use yew::services::{ConsoleService};
+use yew::services::interval::{IntervalService, IntervalTask};
+use yew::services::storage::{Area, StorageService};
+
+use crate::model::{Model,Msg};
+
+const MEMORIES_KEY: &str = "radio.memories";
+const CSV_HEADER: &str = "name,frequency,mode,filter_low,filter_high,notes,tags";
+pub const DEFAULT_SCAN_INTERVAL_SECS: u64 = 5;
+
+// A saved frequency/mode (channel)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Memory {
+    pub name: String,
+    pub frequency: f64,
+    pub mode: String,
+    pub filter_low: Option<f32>,
+    pub filter_high: Option<f32>,
+    #[serde(default)]
+    pub notes: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+// Values typed into the "save memory" form
+#[derive(Default)]
+pub struct MemoryDraft {
+    pub name: String,
+    pub notes: String,
+    pub tags: String,
+}
+
+impl MemoryDraft {
+    pub fn tags(&self) -> Vec<String> {
+        split_tags(&self.tags, ',')
+    }
+}
+
+// Memory scan: recall each memory in turn onto a receiver
+pub struct MemoryScan {
+    pub receiver_id: u32,
+    pub index: usize,
+    _task: IntervalTask,
+}
+
+#[derive(Debug)]
+pub enum MemoryImportError {
+    Json(serde_json::Error),
+    Csv { line: usize, reason: String },
+    UnknownFormat(String),
+}
+
+impl fmt::Display for MemoryImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MemoryImportError::Json(err) => write!(f, "invalid memories json: {}", err),
+            MemoryImportError::Csv { line, reason } => write!(f, "invalid memories csv (line {}): {}", line, reason),
+            MemoryImportError::UnknownFormat(name) => write!(f, "unsupported memories file: {} (csv or json only)", name),
+        }
+    }
+}
+
+pub struct MemoryBank {
+    storage: StorageService,
+    memories: Vec<Memory>,
+    pub draft: MemoryDraft,
+    // Receiver memories are recalled onto (default receiver if unset)
+    target: Option<u32>,
+    scan: Option<MemoryScan>,
+    scan_interval: u64,
+}
+
+impl MemoryBank {
+    pub fn new() -> MemoryBank {
+        let storage = StorageService::new(Area::Local).expect("storage was disabled by the user");
+        let memories = {
+            if let Json(Ok(memories)) = storage.restore(MEMORIES_KEY) {
+                memories
+            } else {
+                Vec::new()
+            }
+        };
+
+        MemoryBank {
+            storage,
+            memories,
+            draft: MemoryDraft::default(),
+            target: None,
+            scan: None,
+            scan_interval: DEFAULT_SCAN_INTERVAL_SECS,
+        }
+    }
+
+    pub fn memories(&self) -> &Vec<Memory> {
+        &self.memories
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Memory> {
+        self.memories.get(index)
+    }
+
+    pub fn add(&mut self, memory: Memory) {
+        // saving under an existing name replaces that memory
+        match self.memories.iter().position(|m| m.name == memory.name) {
+            Some(index) => self.memories[index] = memory,
+            None => self.memories.push(memory),
+        }
+        self.store();
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index < self.memories.len() {
+            self.memories.remove(index);
+            self.store();
+        }
+        if self.memories.is_empty() {
+            self.stop_scan();
+        }
+    }
+
+    // Add imported memories, returns the number of memories imported
+    pub fn import(&mut self, name: &str, content: &[u8]) -> Result<usize, MemoryImportError> {
+        let content = String::from_utf8_lossy(content);
+        let memories =
+            match name.rsplit('.').next().map(|ext| ext.to_lowercase()) {
+                Some(ext) if ext == "json" => serde_json::from_str::<Vec<Memory>>(&content).map_err(MemoryImportError::Json)?,
+                Some(ext) if ext == "csv" => from_csv(&content)?,
+                _ => return Err(MemoryImportError::UnknownFormat(name.to_string())),
+            };
+        let count = memories.len();
+        for memory in memories {
+            self.add(memory);
+        }
+        Ok(count)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.memories).unwrap()
+    }
+
+    pub fn to_csv(&self) -> String {
+        to_csv(&self.memories)
+    }
+
+    fn store(&mut self) {
+        self.storage.store(MEMORIES_KEY, Json(&self.memories));
+    }
+
+    pub fn target(&self) -> Option<u32> {
+        self.target
+    }
+
+    pub fn set_target(&mut self, receiver_id: u32) {
+        self.target = Some(receiver_id);
+    }
+
+    pub fn scan(&self) -> Option<&MemoryScan> {
+        self.scan.as_ref()
+    }
+
+    pub fn scan_interval(&self) -> u64 {
+        self.scan_interval
+    }
+
+    pub fn set_scan_interval(&mut self, seconds: u64, link: &ComponentLink<Model>) {
+        self.scan_interval = seconds.max(1);
+        // restart a running scan with the new interval
+        if let Some(scan) = &self.scan {
+            let (receiver_id, index) = (scan.receiver_id, scan.index);
+            self.start_scan(receiver_id, link);
+            if let Some(scan) = &mut self.scan {
+                scan.index = index;
+            }
+        }
+    }
+
+    pub fn start_scan(&mut self, receiver_id: u32, link: &ComponentLink<Model>) {
+        if self.memories.is_empty() {
+            ConsoleService::error("memory scan: no memories to scan");
+            return;
+        }
+        let callback = link.callback(|_| Msg::MemoryScanStep);
+        let task = IntervalService::spawn(Duration::from_secs(self.scan_interval), callback);
+        self.scan = Some(MemoryScan { receiver_id, index: 0, _task: task });
+    }
+
+    pub fn stop_scan(&mut self) {
+        self.scan = None;
+    }
+
+    // Advance the scan, returns the receiver and memory to recall
+    pub fn next_scan_step(&mut self) -> Option<(u32, Memory)> {
+        let len = self.memories.len();
+        let scan = self.scan.as_mut()?;
+        if len == 0 {
+            return None;
+        }
+        scan.index = (scan.index + 1) % len;
+        Some((scan.receiver_id, self.memories[scan.index].clone()))
+    }
+}
+
+fn split_tags(tags: &str, separator: char) -> Vec<String> {
+    tags.split(separator).map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect()
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn optional(value: Option<f32>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+pub fn to_csv(memories: &[Memory]) -> String {
+    let mut csv = String::from(CSV_HEADER);
+    csv.push('\n');
+    for memory in memories {
+        let row = vec![
+            csv_field(&memory.name),
+            memory.frequency.to_string(),
+            csv_field(&memory.mode),
+            optional(memory.filter_low),
+            optional(memory.filter_high),
+            csv_field(&memory.notes),
+            csv_field(&memory.tags.join(";")),
+        ];
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+// Split csv text into records of fields, each with the line it starts
+// on.  Quoted fields may contain commas, quotes ("") and line breaks.
+fn csv_records(csv: &str) -> Result<Vec<(usize, Vec<String>)>, MemoryImportError> {
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut start = 1;
+    let mut chars = csv.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            },
+            ('"', _) => quoted = !quoted,
+            (',', false) => fields.push(std::mem::replace(&mut field, String::new())),
+            // CRLF line endings
+            ('\r', _) if chars.peek() == Some(&'\n') => (),
+            ('\n', false) => {
+                fields.push(std::mem::replace(&mut field, String::new()));
+                records.push((start, std::mem::replace(&mut fields, Vec::new())));
+                line += 1;
+                start = line;
+            },
+            (c, _) => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            },
+        }
+    }
+    if quoted {
+        return Err(MemoryImportError::Csv { line: start, reason: "unterminated quoted field".to_string() });
+    }
+    if !field.is_empty() || !fields.is_empty() {
+        fields.push(field);
+        records.push((start, fields));
+    }
+    Ok(records)
+}
+
+pub fn from_csv(csv: &str) -> Result<Vec<Memory>, MemoryImportError> {
+    let mut memories = Vec::new();
+    for (i, (line, fields)) in csv_records(csv)?.into_iter().enumerate() {
+        if fields.iter().all(|f| f.trim().is_empty()) || (i == 0 && fields[0] == "name") {
+            continue;
+        }
+        let field = |n: usize| fields.get(n).map(|f| f.trim()).unwrap_or("");
+        let error = |reason: String| MemoryImportError::Csv { line, reason };
+
+        let frequency = field(1).parse::<f64>().map_err(|_| error(format!("invalid frequency: {}", field(1))))?;
+        let filter = |n: usize| -> Result<Option<f32>, MemoryImportError> {
+            match field(n) {
+                "" => Ok(None),
+                value => value.parse::<f32>().map(Some).map_err(|_| error(format!("invalid filter: {}", value))),
+            }
+        };
+        if field(0).is_empty() || field(2).is_empty() {
+            return Err(error("name and mode are required".to_string()));
+        }
+
+        memories.push(Memory {
+            name: field(0).to_string(),
+            frequency,
+            mode: field(2).to_string(),
+            filter_low: filter(3)?,
+            filter_high: filter(4)?,
+            notes: field(5).to_string(),
+            tags: split_tags(field(6), ';'),
+        });
+    }
+    Ok(memories)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory(name: &str, notes: &str, tags: &[&str]) -> Memory {
+        Memory {
+            name: name.to_string(),
+            frequency: 14074000.0,
+            mode: "USB".to_string(),
+            filter_low: Some(100.0),
+            filter_high: None,
+            notes: notes.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    fn csv_error(csv: &str) -> (usize, String) {
+        match from_csv(csv) {
+            Err(MemoryImportError::Csv { line, reason }) => (line, reason),
+            other => panic!("expected a csv error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let memories = vec![
+            memory("Net, Sunday", "said \"73\"", &["nets", "local, club"]),
+            memory("Beacon", "first line\nsecond line\n\nthird line", &[]),
+            memory("\"Quoted\"", "", &["dx"]),
+        ];
+        assert_eq!(from_csv(&to_csv(&memories)).unwrap(), memories);
+    }
+
+    #[test]
+    fn csv_crlf_line_endings() {
+        let csv = "name,frequency,mode,filter_low,filter_high,notes,tags\r\nFT8,14074000,USB,,,\"a\r\nb\",ft8\r\n";
+        let memories = from_csv(csv).unwrap();
+        assert_eq!(memories.len(), 1);
+        assert_eq!(memories[0].notes, "a\nb");
+        assert_eq!(memories[0].tags, vec!["ft8".to_string()]);
+    }
+
+    #[test]
+    fn csv_header_is_optional() {
+        let row = "FT8,14074000,USB,,,,";
+        assert_eq!(from_csv(&format!("{}\n{}\n", CSV_HEADER, row)).unwrap().len(), 1);
+        assert_eq!(from_csv(row).unwrap().len(), 1);
+        assert_eq!(from_csv(&format!("\n{}\n\n", row)).unwrap().len(), 1);
+        // only the first record can be a header
+        assert_eq!(csv_error(&format!("{}\n{}\n", row, CSV_HEADER)).0, 2);
+    }
+
+    #[test]
+    fn csv_errors_report_the_line() {
+        assert_eq!(csv_error("FT8,14.074 MHz,USB"), (1, "invalid frequency: 14.074 MHz".to_string()));
+        assert_eq!(csv_error("FT8,14074000,USB,low"), (1, "invalid filter: low".to_string()));
+        assert_eq!(csv_error("FT8,14074000,"), (1, "name and mode are required".to_string()));
+        // the record with a line break in its notes spans lines 2 and 3
+        let csv = format!("{}\nA,7074000,USB,,,\"one\ntwo\",\nB,x,USB\n", CSV_HEADER);
+        assert_eq!(csv_error(&csv), (4, "invalid frequency: x".to_string()));
+        assert_eq!(csv_error("A,7074000,USB,,,\"unterminated\nB,7074000,USB"), (1, "unterminated quoted field".to_string()));
+    }
+}
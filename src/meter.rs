@@ -0,0 +1,241 @@
+use chrono::{DateTime,Utc};
+use std::collections::{HashMap,VecDeque};
+
+// S9 and dB per S unit (IARU HF), spectrum bins are taken as dBm
+const S9_DBM: f32 = -73.0;
+const DB_PER_S_UNIT: f32 = 6.0;
+// Highest reading shown on the meter bar (S9+60)
+const METER_MAX_DBM: f32 = S9_DBM + 60.0;
+// Smoothing of successive spectrum readings
+const SIGNAL_SMOOTHING: f32 = 0.7;
+// Re-render the meters every this many readings of a receiver
+const RENDER_INTERVAL: u64 = 5;
+// Audio level floor, RMS smoothing and peak hold decay per packet (dB)
+pub const SILENCE_DBFS: f32 = -100.0;
+const RMS_SMOOTHING: f32 = 0.8;
+const PEAK_DECAY: f32 = 1.5;
+const MAX_LOG_ENTRIES: usize = 200;
+
+// Audio output level of a receiver (dBFS)
+#[derive(Clone, Copy, Debug)]
+pub struct AudioLevel {
+    pub rms: f32,
+    pub peak: f32,
+}
+
+impl Default for AudioLevel {
+    fn default() -> AudioLevel {
+        AudioLevel { rms: SILENCE_DBFS, peak: SILENCE_DBFS }
+    }
+}
+
+impl AudioLevel {
+    // Fold in the samples of a played packet, RMS is smoothed and the
+    // peak held with a slow decay
+    pub fn update(&mut self, channels: &[Vec<f32>]) {
+        let count = channels.iter().map(|c| c.len()).sum::<usize>();
+        if count == 0 {
+            return;
+        }
+        let square_sum = channels.iter().flat_map(|c| c.iter()).map(|s| s * s).sum::<f32>();
+        let peak = channels.iter().flat_map(|c| c.iter()).fold(0.0f32, |peak, s| peak.max(s.abs()));
+        let rms = to_dbfs((square_sum / count as f32).sqrt());
+        self.rms = RMS_SMOOTHING * self.rms + (1.0 - RMS_SMOOTHING) * rms;
+        self.peak = to_dbfs(peak).max(self.peak - PEAK_DECAY);
+    }
+}
+
+fn to_dbfs(amplitude: f32) -> f32 {
+    if amplitude > 0.0 { (20.0 * amplitude.log10()).max(SILENCE_DBFS) } else { SILENCE_DBFS }
+}
+
+// Signal report in S units, e.g. "S7" or "S9+20"
+pub fn s_units(dbm: f32) -> String {
+    let over = (dbm - S9_DBM).round();
+    if over >= 1.0 {
+        format!("S9+{:.0}", over)
+    } else {
+        format!("S{:.0}", (9.0 - (S9_DBM - dbm) / DB_PER_S_UNIT).max(0.0).floor())
+    }
+}
+
+// Position of a reading on the meter bar, S0 to S9+60 (0.0..1.0)
+pub fn s_meter_fraction(dbm: f32) -> f32 {
+    let s0 = S9_DBM - 9.0 * DB_PER_S_UNIT;
+    ((dbm - s0) / (METER_MAX_DBM - s0)).max(0.0).min(1.0)
+}
+
+// Position of an audio level on the meter bar (0.0..1.0)
+pub fn audio_fraction(dbfs: f32) -> f32 {
+    (1.0 - dbfs / SILENCE_DBFS).max(0.0).min(1.0)
+}
+
+// Power in a receiver's passband, from its spectrum (dBm)
+#[derive(Clone, Copy, Debug)]
+pub struct SignalLevel {
+    pub power: f32,
+    pub noise_floor: f32,
+    readings: u64,
+}
+
+impl SignalLevel {
+    pub fn snr(&self) -> f32 {
+        self.power - self.noise_floor
+    }
+}
+
+// Our reading of a receiver when a spot was decoded on it
+#[derive(Clone, Debug)]
+pub struct SignalReport {
+    pub time: DateTime<Utc>,
+    pub receiver_id: u32,
+    pub frequency: f32,
+    pub call: String,
+    pub spot_snr: f32,
+    pub power: f32,
+    pub snr: f32,
+    pub audio: Option<f32>,
+}
+
+pub struct Meters {
+    signals: HashMap<u32, SignalLevel>,
+    log: VecDeque<SignalReport>,
+}
+
+impl Meters {
+    pub fn new() -> Meters {
+        Meters {
+            signals: HashMap::new(),
+            log: VecDeque::new(),
+        }
+    }
+
+    pub fn signal(&self, receiver_id: u32) -> Option<SignalLevel> {
+        self.signals.get(&receiver_id).cloned()
+    }
+
+    // Add a spectrum reading, returns true when the meter should be
+    // re-rendered
+    pub fn measure(&mut self, receiver_id: u32, power: f32, noise_floor: f32) -> bool {
+        let level = self.signals.entry(receiver_id).or_insert(SignalLevel { power, noise_floor, readings: 0 });
+        level.power = SIGNAL_SMOOTHING * level.power + (1.0 - SIGNAL_SMOOTHING) * power;
+        level.noise_floor = SIGNAL_SMOOTHING * level.noise_floor + (1.0 - SIGNAL_SMOOTHING) * noise_floor;
+        level.readings += 1;
+        level.readings % RENDER_INTERVAL == 0
+    }
+
+    // Readings are stale after a retune
+    pub fn reset(&mut self, receiver_id: u32) {
+        self.signals.remove(&receiver_id);
+    }
+
+    // Drop readings of receivers that went away
+    pub fn retain<F: Fn(u32) -> bool>(&mut self, keep: F) {
+        self.signals.retain(|receiver_id, _| keep(*receiver_id));
+    }
+
+    pub fn clear(&mut self) {
+        self.signals.clear();
+    }
+
+    pub fn log(&self) -> &VecDeque<SignalReport> {
+        &self.log
+    }
+
+    pub fn clear_log(&mut self) {
+        self.log.clear();
+    }
+
+    pub fn add_report(&mut self, report: SignalReport) {
+        self.log.push_back(report);
+        while self.log.len() > MAX_LOG_ENTRIES {
+            self.log.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn s_units_boundaries() {
+        assert_eq!(s_units(S9_DBM), "S9");
+        // less than half a dB over S9 still reads S9
+        assert_eq!(s_units(S9_DBM + 0.4), "S9");
+        assert_eq!(s_units(S9_DBM + 0.5), "S9+1");
+        assert_eq!(s_units(S9_DBM + 20.0), "S9+20");
+        assert_eq!(s_units(S9_DBM - 0.5), "S8");
+        assert_eq!(s_units(S9_DBM - DB_PER_S_UNIT), "S8");
+        assert_eq!(s_units(S9_DBM - 3.0 * DB_PER_S_UNIT - 1.0), "S5");
+        assert_eq!(s_units(S9_DBM - 9.0 * DB_PER_S_UNIT), "S0");
+        assert_eq!(s_units(-200.0), "S0");
+    }
+
+    #[test]
+    fn s_meter_fraction_range() {
+        let s0 = S9_DBM - 9.0 * DB_PER_S_UNIT;
+        assert_eq!(s_meter_fraction(s0), 0.0);
+        assert_eq!(s_meter_fraction(s0 - 10.0), 0.0);
+        assert!(close(s_meter_fraction(S9_DBM), 54.0 / 114.0));
+        assert_eq!(s_meter_fraction(METER_MAX_DBM), 1.0);
+        assert_eq!(s_meter_fraction(METER_MAX_DBM + 10.0), 1.0);
+    }
+
+    #[test]
+    fn audio_fraction_range() {
+        assert_eq!(audio_fraction(0.0), 1.0);
+        assert_eq!(audio_fraction(3.0), 1.0);
+        assert!(close(audio_fraction(SILENCE_DBFS / 2.0), 0.5));
+        assert_eq!(audio_fraction(SILENCE_DBFS), 0.0);
+        assert_eq!(audio_fraction(SILENCE_DBFS - 20.0), 0.0);
+    }
+
+    #[test]
+    fn dbfs() {
+        assert_eq!(to_dbfs(1.0), 0.0);
+        assert!(close(to_dbfs(0.5), -6.0206));
+        assert!(close(to_dbfs(0.1), -20.0));
+        // silence and anything quieter than the floor
+        assert_eq!(to_dbfs(0.0), SILENCE_DBFS);
+        assert_eq!(to_dbfs(-0.5), SILENCE_DBFS);
+        assert_eq!(to_dbfs(1e-9), SILENCE_DBFS);
+    }
+
+    #[test]
+    fn audio_level_update() {
+        let mut level = AudioLevel::default();
+        assert_eq!(level.rms, SILENCE_DBFS);
+        assert_eq!(level.peak, SILENCE_DBFS);
+
+        // nothing played
+        level.update(&[]);
+        level.update(&[vec![]]);
+        assert_eq!(level.rms, SILENCE_DBFS);
+
+        // full scale, RMS is smoothed, the peak is not
+        level.update(&[vec![1.0, -1.0], vec![1.0, -1.0]]);
+        assert!(close(level.rms, RMS_SMOOTHING * SILENCE_DBFS));
+        assert_eq!(level.peak, 0.0);
+
+        // silence, the peak decays slowly
+        level.update(&[vec![0.0; 4]]);
+        assert!(close(level.rms, RMS_SMOOTHING * RMS_SMOOTHING * SILENCE_DBFS + (1.0 - RMS_SMOOTHING) * SILENCE_DBFS));
+        assert_eq!(level.peak, -PEAK_DECAY);
+
+        // held over a quieter packet
+        level.update(&[vec![0.5, -0.25]]);
+        assert_eq!(level.peak, -2.0 * PEAK_DECAY);
+
+        // and settles on silence
+        for _ in 0..1000 {
+            level.update(&[vec![0.0; 4]]);
+        }
+        assert!(close(level.rms, SILENCE_DBFS));
+        assert_eq!(level.peak, SILENCE_DBFS);
+    }
+}
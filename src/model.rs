@@ -8,8 +8,12 @@ use yew::format::{Json};
 use yew::services::reader::{File, FileData, ReaderService, ReaderTask};
 use yew::services::websocket::{WebSocketStatus};
 use yew::services::storage::{Area, StorageService};
-use web_sys::{WebSocket,BinaryType,MessageEvent};
+use yew::services::keyboard::{KeyboardService, KeyListenerHandle};
+use yew::services::interval::{IntervalService, IntervalTask};
+use web_sys::{WebSocket,BinaryType,MessageEvent,MediaStream};
 use std::str;
+use std::time::Duration;
+use std::collections::HashMap;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 
@@ -17,39 +21,97 @@ use ham_rs::{Call,Country,CountryInfo,LogEntry,Mode};
 use ham_rs::lotw::LoTWStatus;
 
 use sparkplug::{Command,CommandResponse,Receiver,Radio,Version,RECEIVER_MODES,Spot};
-use crate::spot::{SpotDB};
+use crate::spot::{Retention,SpotDB};
 use crate::audio::{AudioProvider};
-use crate::spectrum::{SpectrumProvider};
+use crate::spectrum::{self,SpectrumProvider,WATERFALL_HEIGHT,MIN_WATERFALL_RANGE};
+use crate::panadapter::{self,TraceMode,TRACE_MODES,PANADAPTER_HEIGHT};
+use crate::color::{Palette,PALETTES};
+use crate::tuning::{self,Tuner,DEFAULT_RANGE,TUNING_STEPS};
+use crate::bands::{BAND_PRESETS};
+use crate::reconnect::{ReconnectSupervisor};
+use crate::memory::{Memory,MemoryBank};
+use crate::scanner::{Scanner,ScanState};
+use crate::filters::{self,FilterEdge};
+use crate::commands::{ExtCommand,ExtResponse,RadioInfo};
+use crate::recording::{RecordingInfo,Recording};
+use crate::dsp::{DspFeature,DSP_FEATURES};
+use crate::meter::{self,Meters,SignalReport};
+use crate::transmit::{Transmitter,TxError,TxState};
+use crate::spot_filter::{FilterContext,FILTER_SYNTAX};
+use crate::station::{Station,MAX_FREQUENCIES_SHOWN};
 
 const LOGBOOK_KEY: &str = "radio.logs";
+// Send commands.rs' undocumented commands to SparkSDR
+const EXPERIMENTAL_KEY: &str = "radio.protocol.experimental";
+// How often spots are checked against the retention age
+const SPOT_PRUNE_SECS: u64 = 30;
+// Stations listed in the history when no call is looked up
+const RECENTLY_HEARD: usize = 10;
 
 pub struct Model {
     pub route_service: RouteService<()>,
     pub route: Route<()>,
     storage: StorageService,
+    // Undocumented protocol extensions (ExtCommand) are sent
+    experimental: bool,
     // Callbacks
     pub link: ComponentLink<Self>,
     // SparkSDR connection
     pub ws_location: String,
     wss: Option<WebSocket>,
+    // Reconnect with backoff when an established connection drops
+    reconnect: ReconnectSupervisor,
 
     // List of receivers from getReceivers command
     receivers: Vec<Receiver>,
     // List of radios from the getRadios command
     radios: Vec<Radio>,
+    // Receiver -> radio as reported in getReceiversResponse
+    reported_receiver_radios: HashMap<u32, u32>,
+    // Radio properties from getRadiosResponse
+    radio_info: HashMap<u32, RadioInfo>,
     // Version response from the getVersion command
     version: Option<Version>,
     // Currently selected receiver
     default_receiver: Option<u32>,
+    // Tuning step size and SetFrequency rate limiting
+    tuner: Tuner,
+    // Keyboard tuning shortcuts
+    _key_listener: KeyListenerHandle,
+    // Error from the last typed frequency
+    frequency_entry_error: Option<String>,
+    // Filter edge being dragged on a waterfall
+    filter_drag: Option<(u32, FilterEdge)>,
 
     pub spots: SpotDB,
+    // Ages out spots while no new ones arrive
+    _spot_prune: IntervalTask,
     pub audio: AudioProvider,
+    // Last finished recording (file name, object url)
+    recording_download: Option<(String, String)>,
     pub spectrum: SpectrumProvider,
+    // S-meter readings and the signal report log
+    meters: Meters,
+    // Microphone transmit (push to talk)
+    transmitter: Transmitter,
+    // Saved frequency/mode channels
+    pub memories: MemoryBank,
+    // Result of the last memory import
+    memory_message: Option<String>,
+    // Band scanner for the default receiver
+    pub scanner: Scanner,
+    // Error from the last scan range entry
+    scanner_error: Option<String>,
+    // Error from the last filter preset change
+    spot_filter_error: Option<String>,
 
     // Show/Hide receiver list
     show_receiver_list: bool,
     // Imported log file (ADIF format) for spot cross checking
     pub import: Option<Vec<LogEntry>>,
+    // Bumped whenever `import` changes, cached spot filter results
+    // depend on it
+    pub import_generation: u64,
     // Services for file importing (log file)
     reader: ReaderService,
     tasks: Vec<ReaderTask>,
@@ -60,13 +122,17 @@ pub struct Model {
 pub enum AppRoute {
     #[to = "/map"]
     Map,
+    #[to = "/memories"]
+    Memories,
+    #[to = "/radio/{id}"]
+    Radio(u32),
     #[to = "/"]
     Index,
 }
 
-// Currently only TextMsg is implemented and this is the
-// commands to and responses from SparkSDR.
-// BinaryMsg would be for future support for audio in/out
+// TextMsg carries the commands to and responses from SparkSDR,
+// BinaryMsg receiver audio/spectrum frames (transmit audio is sent
+// directly from the microphone capture, see transmit.rs)
 pub enum WebsocketMsgType {
     BinaryMsg(js_sys::ArrayBuffer),
     TextMsg(String)
@@ -81,8 +147,10 @@ pub enum Msg {
 
     // Websocket connection
     Connect,
+    Disconnect,
     Disconnected,
     Connected,
+    Reconnect,
     UpdateWebsocketAddress(String),
 
     // Command responses from SparkSDR (e.g. getReceiversResponse, getVersionResponse)
@@ -96,20 +164,43 @@ pub enum Msg {
     // Request change to receiver frequency
     FrequencyUp(u32, i32), // digit 0 - 8 
     FrequencyDown(u32, i32), // digit 0 - 8
+    // Click-to-tune, horizontal position (0..1) on a receiver's waterfall
+    TuneWaterfall(u32, f64),
+    // Move a receiver (or the default receiver) by a number of tuning steps
+    TuneStep(u32, i32),
+    TuneDefaultReceiver(i32),
+    // Send coalesced frequency changes
+    FlushFrequency,
+    // Typed frequency (e.g. "14.074 MHz")
+    EnterFrequency(u32, String),
+    // Set frequency and mode from BAND_PRESETS
+    ApplyBandPreset(u32, usize),
     // Request change to receiver mode
     ModeChanged(u32, Mode),
+    // Set receiver filter from the mode's filter presets
+    ApplyFilterPreset(u32, usize),
+    // Drag a filter edge on a receiver's waterfall, the
+    // filter is sent when the drag ends
+    StartFilterDrag(u32, FilterEdge),
+    EndFilterDrag,
+    // Recall a saved memory onto the memory target receiver
+    RecallMemory(usize),
     // Request to add a receiver to a radio
     AddReceiver(u32),
     // Request to remove a receiver
     RemoveReceiver(u32),
+    // Request to remove all receivers of a radio
+    RemoveRadioReceivers(u32),
     // Toggle radio power state
     TogglePower(u32),
     // Request change to the default receiver
     // Will change audio subscription (if subscribed)
     // Will change spectrum subscription
     SetDefaultReceiver(u32),
-    // Subscribe/Unsubscribe to default receivers audio channel
-    EnableAudio,
+    // Subscribe/Unsubscribe to a receiver's audio channel
+    ToggleAudio(u32),
+    // Show/Hide an additional waterfall for a receiver
+    ToggleSpectrum(u32),
 
     // Local only messages
 
@@ -123,7 +214,64 @@ pub enum Msg {
     // Control for client playback/volume
     SetGain(f32),
     MuteUnmute,
+    // Start/stop recording the default receiver's audio
+    ToggleRecording,
+    // Per receiver audio mixing
+    SetReceiverVolume(u32, f32),
+    SetReceiverPan(u32, f32),
+    ToggleReceiverMute(u32),
+    ToggleReceiverSolo(u32),
+    // Client side noise reduction/notch/AGC/CW filter of a receiver
+    ToggleDsp(u32, DspFeature),
+    // Audio frames are headerless PCM
+    ToggleRawPcm,
+    // Undocumented protocol extensions
+    ToggleExperimental,
+    ClearSignalLog,
+    // Push to talk
+    PttDown,
+    PttUp,
+    PttTimeout,
+    MicrophoneReady(MediaStream),
+    TransmitError(String),
+    TransmitTick,
+    ClearTransmitError,
+    // Radio details sparkplug does not parse (transmit capability)
+    ExtResponse(ExtResponse),
+    // Mouse wheel/keyboard tuning step (Hz)
+    SetTuningStep(f64),
+    // Panadapter trace (latest/peak hold/average)
+    SetTraceMode(TraceMode),
+    // Waterfall colour mapping
+    SetReferenceLevel(f32),
+    SetWaterfallRange(f32),
+    ToggleAutoLevels,
+    SetPalette(Palette),
     ClearSpots,
+    // Band scanner
+    ToggleScanner,
+    ScannerTick,
+    SkipScanChannel,
+    SetScanStart(String),
+    SetScanStop(String),
+    SetScanStep(f64),
+    SetSquelch(f32),
+    SetScanDwell(u64),
+    SetScanResume(u64),
+    // Memory bank
+    SetMemoryName(String),
+    SetMemoryNotes(String),
+    SetMemoryTags(String),
+    SaveMemory,
+    DeleteMemory(usize),
+    // Receiver memories are recalled onto (and scanned on)
+    SetMemoryTarget(u32),
+    ImportMemories(Vec<File>),
+    MemoriesLoaded(FileData),
+    StartMemoryScan,
+    StopMemoryScan,
+    MemoryScanStep,
+    SetMemoryScanInterval(u64),
 
     // Spot messages
 
@@ -139,6 +287,20 @@ pub enum Msg {
     ToggleCountrySpotFilter,
     ToggleCurrentReceiverSpotFilter,
     ToggleLoTWSpotFilter,
+    // Spot filter presets (filter expressions)
+    SelectSpotFilterPreset(String),
+    SetSpotFilterName(String),
+    SetSpotFilterExpression(String),
+    SaveSpotFilterPreset,
+    DeleteSpotFilterPreset,
+    // Spot retention and history
+    PruneSpots,
+    SetSpotRetention(Retention),
+    SetHistoryCall(String),
+    SetHistoryBand(String),
+    ClearSpotHistory,
+    // Spot table rows per decode or per station
+    ToggleSpotAggregation,
 }
 
 impl Model {
@@ -162,25 +324,60 @@ impl Model {
                 _ => None
             };
 
+        let experimental =
+            match storage.restore(EXPERIMENTAL_KEY) {
+                Json(Ok(experimental)) => experimental,
+                _ => false,
+            };
+
         let spot_db = SpotDB::new();
         spot_db.update_states_overlay_js();
+        let spot_prune = IntervalService::spawn(Duration::from_secs(SPOT_PRUNE_SECS), link.callback(|_| Msg::PruneSpots));
+
+        let key_listener = KeyboardService::register_key_down(&yew::utils::document(), link.callback(|e: KeyboardEvent| {
+            match tuning::key_steps(&e) {
+                Some(steps) => {
+                    e.prevent_default();
+                    Msg::TuneDefaultReceiver(steps)
+                },
+                None => Msg::None,
+            }
+        }));
 
         let model = Model {
             route_service,
             route,
             storage,
+            experimental,
             link,
             ws_location: "ws://localhost:4649/Spark".to_string(),
             wss: None,
+            reconnect: ReconnectSupervisor::new(),
             receivers: Vec::new(),
             radios: Vec::new(),
+            reported_receiver_radios: HashMap::new(),
+            radio_info: HashMap::new(),
             default_receiver: None,
+            tuner: Tuner::new(),
+            _key_listener: key_listener,
+            frequency_entry_error: None,
+            filter_drag: None,
             version: None,
             spots: spot_db,
+            _spot_prune: spot_prune,
             audio: AudioProvider::new(),
+            recording_download: None,
             spectrum: SpectrumProvider::new(),
+            meters: Meters::new(),
+            transmitter: Transmitter::new(),
+            memories: MemoryBank::new(),
+            memory_message: None,
+            scanner: Scanner::new(),
+            scanner_error: None,
+            spot_filter_error: None,
             show_receiver_list: false,
             import: entries,
+            import_generation: 0,
             reader: ReaderService::new(),
             tasks: Vec::new(),
         };
@@ -212,13 +409,35 @@ impl Model {
 
     // CommandResponse: getReceiversResponse
     pub fn set_receivers(&mut self, receivers: Vec<Receiver>) {
+        self.reported_receiver_radios.retain(|receiver_id, _| receivers.iter().any(|r| r.id == *receiver_id));
         self.receivers = receivers;
-        match self.default_receiver {
+        // drop audio channels of receivers that were removed
+        for receiver_id in self.audio.subscribed() {
+            if !self.receivers.iter().any(|r| r.id == receiver_id) {
+                self.audio.remove_channel(receiver_id);
+            }
+        }
+        let receivers = &self.receivers;
+        self.meters.retain(|receiver_id| receivers.iter().any(|r| r.id == receiver_id));
+        self.audio.retain_dsp(|receiver_id| receivers.iter().any(|r| r.id == receiver_id));
+        // drop pinned waterfalls of receivers that were removed
+        for receiver_id in self.spectrum.subscribed() {
+            if self.spectrum.primary() != Some(receiver_id) && !self.receivers.iter().any(|r| r.id == receiver_id) {
+                self.spectrum.remove(receiver_id);
+            }
+        }
+        // pick a new default receiver if we do not have one or if the
+        // previous one went away (e.g. SparkSDR restarted)
+        match self.default_receiver() {
             None => {
-                self.set_default_receiver(Some(self.receivers[0].id));
+                let first = self.receivers.first().map(|r| r.id);
+                self.set_default_receiver(first);
             },
             _ => ()
         }
+        for receiver_id in self.spectrum.subscribed() {
+            self.update_passband(receiver_id);
+        }
     }
 
     pub fn default_receiver(&self) -> Option<Receiver> {
@@ -247,15 +466,25 @@ impl Model {
     // CommandResponse: ReceiverResponse
     pub fn update_receiver(&mut self, receiver_id: u32, mode: Mode, frequency: f32, filter_low: f32, filter_high: f32) {
         if let Some(index) = self.receivers.iter().position(|i| i.id == receiver_id) {
+            if self.receivers[index].frequency != frequency {
+                self.meters.reset(receiver_id);
+            }
+            // the current receiver filter depends on the default receiver
+            let retuned = self.default_receiver == Some(receiver_id) &&
+                (self.receivers[index].frequency != frequency || self.receivers[index].mode != mode);
             self.receivers[index].frequency = frequency;
             self.receivers[index].mode = mode;
             self.receivers[index].filter_low = filter_low;
             self.receivers[index].filter_high = filter_high;
             let receiver = self.receivers[index].clone();
+            self.update_passband(receiver_id);
 
             let js = &format!("initWaterfallNav(\"{}\", {}, {}, {});", receiver.mode.mode(), receiver.frequency, receiver.filter_high, receiver.filter_low);
             ConsoleService::log(&format!("js: {}", js));
             js_sys::eval(&js).unwrap();
+            if retuned && self.spots.filters_use_receiver() {
+                self.refresh_spot_markers();
+            }
         } else {
             ConsoleService::error(&format!("Attempted to update a receiver that does not exist: {}", receiver_id));
         }
@@ -268,35 +497,161 @@ impl Model {
         }
     }
 
-    pub fn frequency_up(&mut self, receiver_id: u32, digit: i32) {
+    // Update our receiver and queue a (rate limited) SetFrequency command
+    pub fn set_frequency(&mut self, receiver_id: u32, frequency: f64) {
         if let Some(index) = self.receivers.iter().position(|i| i.id == receiver_id) {
-            if digit == 0 { self.receivers[index].frequency += 100000000.0 }
-            if digit == 1 { self.receivers[index].frequency += 10000000.0 }
-            if digit == 2 { self.receivers[index].frequency += 1000000.0 }
-            if digit == 3 { self.receivers[index].frequency += 100000.0 }
-            if digit == 4 { self.receivers[index].frequency += 10000.0 }
-            if digit == 5 { self.receivers[index].frequency += 1000.0 }
-            if digit == 6 { self.receivers[index].frequency += 100.0 }
-            if digit == 7 { self.receivers[index].frequency += 10.0 }
-            if digit == 8 { self.receivers[index].frequency += 1.0 }
+            let frequency = frequency.max(0.0);
+            self.receivers[index].frequency = frequency as f32;
+            self.tuner.request(&self.link, receiver_id, frequency);
+            self.update_passband(receiver_id);
+        }
+    }
 
-            self.send_command(Command::SetFrequency { frequency: (self.receivers[index].frequency as i32).to_string(), id: receiver_id });
+    // Send the most recent frequency requested for each receiver
+    pub fn flush_frequency(&mut self) {
+        for (receiver_id, frequency) in self.tuner.take_pending() {
+            self.send_command(Command::SetFrequency { frequency: (frequency as i64).to_string(), id: receiver_id });
         }
     }
 
-    pub fn frequency_down(&mut self, receiver_id: u32, digit: i32) {
+    // Move a receiver by a number of tuning steps
+    pub fn tune_step(&mut self, receiver_id: u32, steps: i32) {
+        if steps == 0 {
+            return;
+        }
+        if let Some(receiver) = self.receivers.iter().find(|r| r.id == receiver_id) {
+            let frequency = self.tuner.step_frequency(receiver.frequency as f64, steps);
+            self.set_frequency(receiver_id, frequency);
+        }
+    }
+
+    pub fn enter_frequency(&mut self, receiver_id: u32, input: &str) {
+        match tuning::parse_frequency(input, DEFAULT_RANGE) {
+            Ok(frequency) => {
+                self.frequency_entry_error = None;
+                self.set_frequency(receiver_id, frequency);
+            },
+            Err(err) => {
+                self.frequency_entry_error = Some(err.to_string());
+            }
+        }
+    }
+
+    pub fn apply_band_preset(&mut self, receiver_id: u32, preset: usize) {
+        if let Some(preset) = BAND_PRESETS.get(preset) {
+            match tuning::check_frequency(preset.frequency, DEFAULT_RANGE) {
+                Ok(frequency) => {
+                    self.frequency_entry_error = None;
+                    self.change_receiver_mode(receiver_id, Mode::new(preset.mode.to_string()));
+                    self.set_frequency(receiver_id, frequency);
+                },
+                Err(err) => {
+                    self.frequency_entry_error = Some(err.to_string());
+                }
+            }
+        }
+    }
+
+    // Update our receiver filter and send it to SparkSDR (offsets in Hz
+    // from the receiver frequency)
+    pub fn set_filter(&mut self, receiver_id: u32, filter_low: f32, filter_high: f32) {
+        if self.send_ext_command(ExtCommand::SetFilter { id: receiver_id, filter_low, filter_high }) {
+            self.update_filter(receiver_id, filter_low, filter_high);
+        }
+    }
+
+    fn update_filter(&mut self, receiver_id: u32, filter_low: f32, filter_high: f32) {
         if let Some(index) = self.receivers.iter().position(|i| i.id == receiver_id) {
-            if digit == 0 { self.receivers[index].frequency -= 100000000.0 }
-            if digit == 1 { self.receivers[index].frequency -= 10000000.0 }
-            if digit == 2 { self.receivers[index].frequency -= 1000000.0 }
-            if digit == 3 { self.receivers[index].frequency -= 100000.0 }
-            if digit == 4 { self.receivers[index].frequency -= 10000.0 }
-            if digit == 5 { self.receivers[index].frequency -= 1000.0 }
-            if digit == 6 { self.receivers[index].frequency -= 100.0 }
-            if digit == 7 { self.receivers[index].frequency -= 10.0 }
-            if digit == 8 { self.receivers[index].frequency -= 1.0 }
+            self.receivers[index].filter_low = filter_low;
+            self.receivers[index].filter_high = filter_high;
+            self.update_passband(receiver_id);
+
+            if self.default_receiver == Some(receiver_id) {
+                let receiver = &self.receivers[index];
+                let js = format!("initWaterfallNav(\"{}\", {}, {}, {});", receiver.mode.mode(), receiver.frequency, receiver.filter_high, receiver.filter_low);
+                js_sys::eval(&js).unwrap();
+            }
+        }
+    }
+
+    pub fn apply_filter_preset(&mut self, receiver_id: u32, preset: usize) {
+        let preset =
+            match self.receivers.iter().find(|r| r.id == receiver_id) {
+                Some(receiver) => filters::filter_presets(&receiver.mode.mode()).get(preset),
+                None => None,
+            };
+        if let Some(preset) = preset {
+            self.set_filter(receiver_id, preset.low, preset.high);
+        }
+    }
+
+    pub fn start_filter_drag(&mut self, receiver_id: u32, edge: FilterEdge) {
+        self.filter_drag = Some((receiver_id, edge));
+    }
 
-            self.send_command(Command::SetFrequency { frequency: (self.receivers[index].frequency as i32).to_string(), id: receiver_id });
+    // Move a filter edge to an absolute frequency (Hz), only updated
+    // locally until the drag ends
+    fn drag_filter_edge(&mut self, receiver_id: u32, edge: FilterEdge, frequency: f64) {
+        if let Some(receiver) = self.receivers.iter().find(|r| r.id == receiver_id) {
+            let offset = (frequency - receiver.frequency as f64).round() as f32;
+            let low = receiver.filter_low.min(receiver.filter_high);
+            let high = receiver.filter_low.max(receiver.filter_high);
+            let (low, high) = filters::move_edge(low, high, edge, offset);
+            self.update_filter(receiver_id, low, high);
+        }
+    }
+
+    pub fn end_filter_drag(&mut self) -> bool {
+        match self.filter_drag.take() {
+            Some((receiver_id, _)) => {
+                if let Some(receiver) = self.receivers.iter().find(|r| r.id == receiver_id) {
+                    let (filter_low, filter_high) = (receiver.filter_low, receiver.filter_high);
+                    self.send_ext_command(ExtCommand::SetFilter { id: receiver_id, filter_low, filter_high });
+                }
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn set_tuning_step(&mut self, step: f64) {
+        self.tuner.set_step(step);
+    }
+
+    // Tune to the frequency under the pointer on a receiver's waterfall
+    // (or move the filter edge being dragged)
+    pub fn tune_waterfall(&mut self, receiver_id: u32, ratio: f64) {
+        let (start, stop) =
+            match self.spectrum.waterfall(receiver_id) {
+                Some(waterfall) if waterfall.freq_stop() > waterfall.freq_start() => (waterfall.freq_start(), waterfall.freq_stop()),
+                _ => return,
+            };
+        if let Some((drag_id, edge)) = self.filter_drag {
+            if drag_id == receiver_id {
+                self.drag_filter_edge(receiver_id, edge, start + (stop - start) * ratio);
+            }
+            return;
+        }
+        if let Some(receiver) = self.receivers.iter().find(|r| r.id == receiver_id) {
+            let step = tuning::snap_step(&receiver.mode.mode());
+            let frequency = tuning::snap(start + (stop - start) * ratio, step);
+            if frequency != receiver.frequency as f64 {
+                self.set_frequency(receiver_id, frequency);
+            }
+        }
+    }
+
+    pub fn frequency_up(&mut self, receiver_id: u32, digit: i32) {
+        if let Some(receiver) = self.receivers.iter().find(|r| r.id == receiver_id) {
+            let frequency = receiver.frequency as f64 + tuning::digit_step(digit);
+            self.set_frequency(receiver_id, frequency);
+        }
+    }
+
+    pub fn frequency_down(&mut self, receiver_id: u32, digit: i32) {
+        if let Some(receiver) = self.receivers.iter().find(|r| r.id == receiver_id) {
+            let frequency = receiver.frequency as f64 - tuning::digit_step(digit);
+            self.set_frequency(receiver_id, frequency);
         }
     }
 
@@ -357,6 +712,8 @@ impl Model {
             }
         });
 
+        let cbext = self.link.callback(Msg::ExtResponse);
+
         let onmessage_callback = Closure::wrap(Box::new(move |e: MessageEvent| {
             if let Ok(abuf) = e.data().dyn_into::<js_sys::ArrayBuffer>() {
                 //let array = js_sys::Uint8Array::new(&abuf);
@@ -364,7 +721,11 @@ impl Model {
             } else if let Ok(_blob) = e.data().dyn_into::<web_sys::Blob>() {
                 ConsoleService::error("rig control: unexpected blob message from server");
             } else if let Ok(txt) = e.data().dyn_into::<js_sys::JsString>() {
-                cbout.emit(WebsocketMsgType::TextMsg(txt.into()));
+                let txt: String = txt.into();
+                if let Ok(ext) = serde_json::from_str::<ExtResponse>(&txt) {
+                    cbext.emit(ext);
+                }
+                cbout.emit(WebsocketMsgType::TextMsg(txt));
             } else {
                 ConsoleService::error("rig control: unexpected message from server");
             }
@@ -375,14 +736,100 @@ impl Model {
     }
 
     pub fn disconnect(&mut self) {
+        self.transmitter.stop();
+        self.transmitter.clear_capabilities();
         self.wss = None;
+        self.reconnect.disarm();
         self.receivers = Vec::new();
         self.radios = Vec::new();
+        self.reported_receiver_radios.clear();
+        self.radio_info.clear();
         self.version = None;
         self.default_receiver = None;
+        self.spectrum.clear();
+        self.scanner.stop();
+        self.memories.stop_scan();
+        self.audio.clear();
+        self.meters.clear();
         self.spots = SpotDB::new();
     }
 
+    // User requested disconnect, stop any reconnect attempts and
+    // close the current socket without triggering its callbacks
+    pub fn close(&mut self) {
+        // unkey while we still can
+        self.stop_transmit();
+        if let Some(ws) = &self.wss {
+            ws.set_onopen(None);
+            ws.set_onerror(None);
+            ws.set_onclose(None);
+            ws.set_onmessage(None);
+            if let Err(err) = ws.close() {
+                ConsoleService::error(&format!("unable to close connection: {:?}", err));
+            }
+        }
+        self.disconnect();
+    }
+
+    // Websocket connection opened (onopen)
+    pub fn connection_opened(&mut self) {
+        let resumed = self.reconnect.connected();
+
+        // When we first connect to SparkSDR gather some basic information
+        self.send_command(Command::GetReceivers);
+        self.send_command(Command::GetRadios);
+        self.send_command(Command::GetVersion);
+        // Also subscribe to spots
+        self.send_command(Command::SubscribeToSpots{ enable: true });
+
+        if resumed {
+            ConsoleService::log("connection restored, resubscribing");
+            self.resubscribe();
+        }
+        // never leave a radio keyed from a dropped connection
+        if let Some(radio_id) = self.transmitter.take_unkey_pending() {
+            ConsoleService::log(&format!("unkeying radio {} after reconnect", radio_id));
+            self.send_command(ExtCommand::SetTransmit { id: radio_id, transmit: false });
+        }
+    }
+
+    // Websocket connection closed or errored (onclose/onerror)
+    pub fn connection_lost(&mut self) {
+        if self.transmitter.is_active() {
+            self.transmitter.connection_lost();
+            self.transmitter.fail(TxError::NotConnected);
+        }
+        match self.reconnect.is_armed() {
+            true => {
+                // keep receivers, subscriptions and spots around
+                // so we can pick up where we left off
+                self.wss = None;
+                self.reconnect.schedule(&self.link);
+            },
+            false => self.disconnect(),
+        }
+    }
+
+    // Reconnect timer fired
+    pub fn reconnect(&mut self) {
+        self.reconnect.fired();
+        let addr = self.ws_location.to_string();
+        ConsoleService::log(&format!("Reconnecting to {}", addr));
+        self.connect(&addr);
+    }
+
+    // Replay spectrum and audio subscriptions we had before the
+    // connection was lost
+    fn resubscribe(&mut self) {
+        for rx_id in self.spectrum.subscribed() {
+            self.send_command(Command::SubscribeToSpectrum{ rx_id, enable: true });
+        }
+        self.audio.restart_playback();
+        for rx_id in self.audio.subscribed() {
+            self.send_command(Command::SubscribeToAudio{ rx_id, enable: true });
+        }
+    }
+
     pub fn is_connected(&self) -> bool {
         match self.wss {
             Some(_) => true,
@@ -390,7 +837,11 @@ impl Model {
         }
     }
 
-    pub fn send_command(&mut self, cmd: Command) {
+    pub fn is_reconnecting(&self) -> bool {
+        self.reconnect.is_reconnecting()
+    }
+
+    pub fn send_command<C: serde::Serialize>(&mut self, cmd: C) {
         let j = serde_json::to_string(&cmd).unwrap();
         if let Some(wss) = &self.wss {
             wss.send_with_str(&j).unwrap();
@@ -400,79 +851,267 @@ impl Model {
         }
     }
 
-    pub fn subscribe_to_audio(&mut self) {
-        match self.audio.receiving_audio() {
-            Some(previous_audio_channel) => {
-                self.send_command(Command::SubscribeToAudio{ rx_id: previous_audio_channel, enable: false });
-            },
-            None => ()
+    // Send one of the undocumented commands, returns false (and sends
+    // nothing) unless the experimental protocol extensions are enabled
+    pub fn send_ext_command(&mut self, cmd: ExtCommand) -> bool {
+        if !self.experimental {
+            ConsoleService::log(&format!("not sent, experimental protocol extensions are disabled: {:?}", cmd));
+            return false;
         }
-        let rx_id =
+        self.send_command(cmd);
+        true
+    }
+
+    pub fn toggle_experimental(&mut self) {
+        if self.experimental {
+            self.stop_transmit();
+        }
+        self.experimental = !self.experimental;
+        self.storage.store(EXPERIMENTAL_KEY, Json(&self.experimental));
+    }
+
+    // Add a receiver's audio to the mix
+    pub fn subscribe_to_audio(&mut self, receiver_id: u32) {
+        if self.audio.is_subscribed(receiver_id) || !self.receivers.iter().any(|r| r.id == receiver_id) {
+            return;
+        }
+        if self.audio.add_channel(receiver_id) {
+            self.send_command(Command::SubscribeToAudio{ rx_id: receiver_id, enable: true });
+            ConsoleService::log(&format!("subscribed to audio channel: {}", receiver_id));
+        }
+    }
+
+    pub fn unsubscribe_to_audio(&mut self, receiver_id: u32) {
+        self.finish_recording(receiver_id);
+        if self.audio.is_subscribed(receiver_id) {
+            self.send_command(Command::SubscribeToAudio{ rx_id: receiver_id, enable: false });
+            ConsoleService::log(&format!("unsubscribed to audio channel: {}", receiver_id));
+        }
+        self.audio.remove_channel(receiver_id);
+    }
+
+    // Record the default receiver (subscribing to its audio if needed)
+    pub fn toggle_recording(&mut self) {
+        let receiver =
             match self.default_receiver() {
-                Some(receiver) => {
-                    self.send_command(Command::SubscribeToAudio{ rx_id: receiver.id, enable: true });
-                    ConsoleService::log(&format!("subscribed to audio channel: {}", receiver.id));
-                    Some(receiver.id)
+                Some(receiver) => receiver,
+                None => return,
+            };
+        if self.audio.channel(receiver.id).map(|c| c.is_recording()).unwrap_or(false) {
+            self.finish_recording(receiver.id);
+            return;
+        }
+        self.subscribe_to_audio(receiver.id);
+        let info = RecordingInfo {
+            receiver_id: receiver.id,
+            frequency: receiver.frequency,
+            mode: receiver.mode.mode(),
+            started: chrono::Utc::now(),
+        };
+        if !self.audio.start_recording(info) {
+            ConsoleService::error(&format!("unable to record receiver {}: no audio", receiver.id));
+        }
+    }
+
+    // Stop a recording and offer it as a WAV download
+    fn finish_recording(&mut self, receiver_id: u32) {
+        if let Some(recording) = self.audio.stop_recording(receiver_id) {
+            match recording_url(&recording) {
+                Ok(url) => {
+                    if let Some((_, previous)) = self.recording_download.take() {
+                        web_sys::Url::revoke_object_url(&previous).unwrap();
+                    }
+                    self.recording_download = Some((recording.info().file_name(), url));
                 },
-                None => None,
+                Err(err) => {
+                    ConsoleService::error(&format!("unable to save recording: {:?}", err));
+                }
+            }
+        }
+    }
+
+    // Radio we may transmit on: the default receiver's, if we know for
+    // sure which radio that is and it reports transmit capability
+    fn transmit_radio(&self) -> Result<u32, TxError> {
+        if !self.experimental {
+            return Err(TxError::Disabled);
+        }
+        if !self.is_connected() {
+            return Err(TxError::NotConnected);
+        }
+        let radio_id =
+            match self.default_receiver.and_then(|receiver_id| self.confirmed_receiver_radio(receiver_id)) {
+                Some(radio_id) => radio_id,
+                None => return Err(TxError::NoRadio),
+            };
+        match self.transmitter.is_capable(radio_id) {
+            true => Ok(radio_id),
+            false => Err(TxError::NotCapable(radio_id)),
+        }
+    }
+
+    pub fn start_transmit(&mut self) {
+        match self.transmit_radio() {
+            Ok(radio_id) => self.transmitter.start(&self.link, radio_id),
+            Err(err) => self.transmitter.fail(err),
+        }
+    }
+
+    // Microphone access granted after PTT was pressed
+    pub fn microphone_ready(&mut self, stream: MediaStream) {
+        let result =
+            match &self.wss {
+                Some(ws) => self.transmitter.begin(&self.link, stream, ws),
+                None => Err(TxError::NotConnected),
             };
-        self.audio.set_subscribed(rx_id);
+        match result {
+            Ok(Some(radio_id)) => {
+                if !self.send_ext_command(ExtCommand::SetTransmit { id: radio_id, transmit: true }) {
+                    self.transmitter.stop();
+                    self.transmitter.fail(TxError::Disabled);
+                }
+            },
+            Ok(None) => (),
+            Err(err) => {
+                self.transmitter.stop();
+                self.transmitter.fail(err);
+            }
+        }
+    }
+
+    // Unkeying is sent even with the experimental protocol extensions
+    // disabled, a radio was only keyed while they were enabled
+    pub fn stop_transmit(&mut self) {
+        if let Some(radio_id) = self.transmitter.stop() {
+            self.send_command(ExtCommand::SetTransmit { id: radio_id, transmit: false });
+        }
+    }
+
+    pub fn transmit_failed(&mut self, err: TxError) {
+        self.stop_transmit();
+        self.transmitter.fail(err);
     }
 
-    pub fn unsubscribe_to_audio(&mut self) {
-        match self.audio.receiving_audio() {
-            Some(previous_audio_channel) => {
-                self.send_command(Command::SubscribeToAudio{ rx_id: previous_audio_channel, enable: false });
-                ConsoleService::log("unsubscribed to audio");
+    pub fn clear_transmit_error(&mut self) {
+        self.transmitter.clear_error();
+    }
+
+    pub fn set_ext_response(&mut self, response: ExtResponse) {
+        match response {
+            ExtResponse::Radios { radios } => {
+                self.transmitter.set_capabilities(&radios);
+                self.radio_info = radios.into_iter().map(|radio| (radio.id, radio)).collect();
+            },
+            ExtResponse::Receivers { receivers } => {
+                self.reported_receiver_radios = receivers.iter()
+                    .filter_map(|receiver| receiver.radio_id.map(|radio_id| (receiver.id, radio_id)))
+                    .collect();
             },
-            None => ()
         }
-        self.audio.set_subscribed(None);
+    }
+
+    pub fn toggle_audio(&mut self, receiver_id: u32) {
+        match self.audio.is_subscribed(receiver_id) {
+            true => self.unsubscribe_to_audio(receiver_id),
+            false => self.subscribe_to_audio(receiver_id),
+        }
     }
 
     pub fn set_default_receiver(&mut self, receiver: Option<u32>) {
         if self.default_receiver == receiver { /* do nothing */ }
         else {
-            // unsubscribe to old spectrum data
-            match self.spectrum.receiving_spectrum() {
-                Some(previous_subscription) => {
-                    self.send_command(Command::SubscribeToSpectrum{ rx_id: previous_subscription, enable: false });
-                },
-                None => ()
-            }
-
-            // subscribe to new spectrum data
             match receiver {
                 Some(receiver_id) => {
                     if let Some(index) = self.receivers.iter().position(|i| i.id == receiver_id) {
                         let receiver = self.receivers[index].clone();
-                        self.send_command(Command::SubscribeToSpectrum{ rx_id: receiver_id, enable: true });
-                        self.spectrum.set_subscribed(Some(receiver_id));
+
+                        // subscribe to new spectrum data (unless already pinned)
+                        if !self.spectrum.receiving_spectrum(receiver_id) {
+                            self.send_command(Command::SubscribeToSpectrum{ rx_id: receiver_id, enable: true });
+                        }
 
                         let js = format!("initWaterfallNav(\"{}\", {}, {}, {});", receiver.mode.mode(), receiver.frequency, receiver.filter_high, receiver.filter_low);
                         ConsoleService::log(&format!("js: {}", js));
                         js_sys::eval(&js).unwrap();
 
-                        // update default receiver
-                        self.default_receiver = Some(receiver_id);
+                        // unsubscribe to old spectrum data
+                        self.set_primary_spectrum(Some(receiver_id));
+                        self.update_passband(receiver_id);
 
-                        // switch audio subscriptions if already subscribed
-                        match self.audio.receiving_audio() {
-                            Some(_) => {
-                                self.subscribe_to_audio();
-                            },
-                            None => ()
+                        // the scanner only follows the receiver it was started on
+                        if self.scanner.receiver_id().is_some() && self.scanner.receiver_id() != Some(receiver_id) {
+                            self.scanner.stop();
                         }
+
+                        // update default receiver (audio subscriptions are
+                        // per receiver and stay as they are)
+                        self.default_receiver = Some(receiver_id);
                     } else {
                         ConsoleService::error(&format!("Attempted to set default receiver with invalid receiver id: {}", receiver_id));
                     }
                 },
                 None => {
                     self.default_receiver = None;
-                    self.unsubscribe_to_audio();
+                    self.scanner.stop();
+                    for receiver_id in self.audio.subscribed() {
+                        self.unsubscribe_to_audio(receiver_id);
+                    }
                     js_sys::eval("initWaterfallNav(null, null, null, null);").unwrap();
+                    self.set_primary_spectrum(None);
                 }
             }
+            self.refresh_spot_markers();
+        }
+    }
+
+    // Apply the retention policy to the spot table and map, returns true
+    // if any spots were dropped
+    pub fn prune_spots(&mut self) -> bool {
+        self.spots.prune(chrono::Utc::now())
+    }
+
+    pub fn set_spot_retention(&mut self, retention: Retention) {
+        self.spots.set_retention(retention);
+        self.prune_spots();
+    }
+
+    // Redraw the spot markers for the current filters
+    pub fn refresh_spot_markers(&self) {
+        let receiver = self.default_receiver();
+        let ctx = FilterContext { logs: self.import.as_ref(), log_generation: self.import_generation, receiver: receiver.as_ref() };
+        self.spots.refresh_markers(&ctx);
+    }
+
+    fn set_primary_spectrum(&mut self, receiver: Option<u32>) {
+        if let Some(previous_subscription) = self.spectrum.set_primary(receiver) {
+            self.send_command(Command::SubscribeToSpectrum{ rx_id: previous_subscription, enable: false });
+        }
+    }
+
+    // Keep the panadapter passband in sync with the receiver filter
+    fn update_passband(&mut self, receiver_id: u32) {
+        let passband =
+            self.receivers.iter().find(|r| r.id == receiver_id).map(|r| {
+                let low = r.frequency as f64 + r.filter_low as f64;
+                let high = r.frequency as f64 + r.filter_high as f64;
+                (low.min(high), low.max(high))
+            });
+        self.spectrum.set_passband(receiver_id, passband);
+    }
+
+    // Show/Hide an additional waterfall for a receiver
+    pub fn toggle_spectrum(&mut self, receiver_id: u32) {
+        let was_receiving = self.spectrum.receiving_spectrum(receiver_id);
+        self.spectrum.toggle_pinned(receiver_id);
+        match (was_receiving, self.spectrum.receiving_spectrum(receiver_id)) {
+            (false, true) => {
+                self.send_command(Command::SubscribeToSpectrum{ rx_id: receiver_id, enable: true });
+                self.update_passband(receiver_id);
+            },
+            (true, false) => {
+                self.send_command(Command::SubscribeToSpectrum{ rx_id: receiver_id, enable: false });
+            },
+            _ => ()
         }
     }
 
@@ -511,8 +1150,10 @@ impl Model {
                     }
                 }
                 self.import = Some(records);
+                self.import_generation += 1;
                 self.storage.store(LOGBOOK_KEY, Json(&self.import));
                 self.update_state_map_overlay();
+                self.refresh_spot_markers();
             },
             Err(e) => {
                 ConsoleService::error(&format!("unable to load adif: {}", e));
@@ -522,8 +1163,253 @@ impl Model {
 
     pub fn clear_adif_data(&mut self) {
         self.import = None;
+        self.import_generation += 1;
         self.storage.store(LOGBOOK_KEY, Json(&self.import));
         self.update_state_map_overlay();
+        self.refresh_spot_markers();
+    }
+
+    // Receiver memories are recalled onto, falls back to the default receiver
+    pub fn memory_target(&self) -> Option<u32> {
+        match self.memories.target() {
+            Some(receiver_id) if self.receivers.iter().any(|r| r.id == receiver_id) => Some(receiver_id),
+            _ => self.default_receiver,
+        }
+    }
+
+    // Save the default receiver's frequency/mode/filter as a memory
+    pub fn save_memory(&mut self) {
+        let receiver =
+            match self.default_receiver() {
+                Some(receiver) => receiver,
+                None => return,
+            };
+        let name =
+            match self.memories.draft.name.trim() {
+                "" => format!("{:.3} {}", receiver.frequency as f64 / 1000.0, receiver.mode.mode()),
+                name => name.to_string(),
+            };
+        self.memories.add(Memory {
+            name,
+            frequency: receiver.frequency as f64,
+            mode: receiver.mode.mode(),
+            filter_low: Some(receiver.filter_low),
+            filter_high: Some(receiver.filter_high),
+            notes: self.memories.draft.notes.trim().to_string(),
+            tags: self.memories.draft.tags(),
+        });
+        self.memories.draft = Default::default();
+    }
+
+    pub fn recall_memory(&mut self, index: usize) {
+        if let (Some(receiver_id), Some(memory)) = (self.memory_target(), self.memories.get(index).cloned()) {
+            self.apply_memory(receiver_id, &memory);
+        }
+    }
+
+    fn apply_memory(&mut self, receiver_id: u32, memory: &Memory) {
+        if let Some(receiver) = self.receivers.iter().find(|r| r.id == receiver_id) {
+            if receiver.mode.mode() != memory.mode {
+                self.change_receiver_mode(receiver_id, Mode::new(memory.mode.to_string()));
+            }
+            self.set_frequency(receiver_id, memory.frequency);
+            if let (Some(low), Some(high)) = (memory.filter_low, memory.filter_high) {
+                self.set_filter(receiver_id, low, high);
+            }
+        }
+    }
+
+    pub fn read_memories_file(&mut self, file: File) {
+        let task = {
+            let callback = self.link.callback(|data| Msg::MemoriesLoaded(data));
+            self.reader.read_file(file, callback).unwrap()
+        };
+        self.tasks.push(task);
+    }
+
+    pub fn load_memories(&mut self, data: FileData) {
+        self.memory_message =
+            match self.memories.import(&data.name, &data.content) {
+                Ok(count) => Some(format!("Imported {} memories from {}", count, data.name)),
+                Err(err) => {
+                    ConsoleService::error(&format!("memory import error: {}", err));
+                    Some(err.to_string())
+                }
+            };
+    }
+
+    pub fn start_memory_scan(&mut self) {
+        if let Some(receiver_id) = self.memory_target() {
+            self.memories.start_scan(receiver_id, &self.link);
+            if let Some(memory) = self.memories.get(0).cloned() {
+                self.apply_memory(receiver_id, &memory);
+            }
+        }
+    }
+
+    pub fn memory_scan_step(&mut self) {
+        if let Some((receiver_id, memory)) = self.memories.next_scan_step() {
+            if self.receivers.iter().any(|r| r.id == receiver_id) {
+                self.apply_memory(receiver_id, &memory);
+            } else {
+                // scanned receiver was removed
+                self.memories.stop_scan();
+            }
+        }
+    }
+
+    pub fn set_memory_scan_interval(&mut self, seconds: u64) {
+        self.memories.set_scan_interval(seconds, &self.link);
+    }
+
+    // Start/stop scanning with the default receiver
+    pub fn toggle_scanner(&mut self) {
+        if self.scanner.is_running() {
+            self.scanner.stop();
+        } else if let Some(receiver) = self.default_receiver() {
+            let frequency = self.scanner.start(&self.link, receiver.id, receiver.frequency as f64);
+            self.set_frequency(receiver.id, frequency);
+        }
+    }
+
+    pub fn scanner_tick(&mut self) {
+        if let (Some(receiver_id), Some(frequency)) = (self.scanner.receiver_id(), self.scanner.tick(js_sys::Date::now())) {
+            self.set_frequency(receiver_id, frequency);
+        }
+    }
+
+    pub fn skip_scan_channel(&mut self) {
+        if let (Some(receiver_id), Some(frequency)) = (self.scanner.receiver_id(), self.scanner.skip()) {
+            self.set_frequency(receiver_id, frequency);
+        }
+    }
+
+    // Passband power of a receiver from its spectrum, for the S-meter and
+    // the scanner squelch.  Returns true when the meter should be
+    // re-rendered.
+    pub fn measure_spectrum(&mut self, receiver_id: u32, bins: &[f32], start: f64, stop: f64) -> bool {
+        let scanning = self.scanner.receiver_id() == Some(receiver_id);
+        let (low, high) =
+            match self.receivers.iter().find(|r| r.id == receiver_id) {
+                Some(r) => {
+                    // the scanner knows where it tuned before we hear back
+                    let frequency = if scanning { self.scanner.frequency() } else { r.frequency as f64 };
+                    let low = frequency + r.filter_low as f64;
+                    let high = frequency + r.filter_high as f64;
+                    (low.min(high), low.max(high))
+                },
+                None => return false,
+            };
+        match (spectrum::passband_power(bins, start, stop, low, high), panadapter::noise_floor(bins)) {
+            (Some(power), Some(floor)) => {
+                // squelch on the level per bin above the floor of a bin
+                if scanning {
+                    self.scanner.measure(power.mean - floor);
+                }
+                // the meter reads the whole passband against the noise in it
+                let passband_noise = floor + 10.0 * (power.bins as f32).log10();
+                self.meters.measure(receiver_id, power.total, passband_noise)
+            },
+            _ => false,
+        }
+    }
+
+    // Log our readings for a spot decoded on one of our receivers, to
+    // compare with the spot's reported SNR
+    pub fn log_spot_signal(&mut self, spot: &Spot) {
+        let receiver_id =
+            match self.receivers.iter().find(|r| r.frequency == spot.tuned_frequency && r.mode == spot.mode) {
+                Some(receiver) => receiver.id,
+                None => return,
+            };
+        if let Some(signal) = self.meters.signal(receiver_id) {
+            self.meters.add_report(SignalReport {
+                time: chrono::Utc::now(),
+                receiver_id,
+                frequency: spot.frequency as f32,
+                call: spot.call.call().to_string(),
+                spot_snr: spot.snr as f32,
+                power: signal.power,
+                snr: signal.snr(),
+                audio: self.audio.channel(receiver_id).map(|c| c.level().rms),
+            });
+        }
+    }
+
+    // "" selects no preset
+    pub fn select_spot_filter_preset(&mut self, name: &str) {
+        let name = if name.is_empty() { None } else { Some(name) };
+        self.spot_filter_error = self.spots.filter_presets.activate(name).err().map(|err| err.to_string());
+        self.refresh_spot_markers();
+    }
+
+    pub fn save_spot_filter_preset(&mut self) {
+        self.spot_filter_error = self.spots.filter_presets.save().err().map(|err| err.to_string());
+        self.refresh_spot_markers();
+    }
+
+    pub fn delete_spot_filter_preset(&mut self) {
+        let name = self.spots.filter_presets.draft.name.trim().to_string();
+        self.spots.filter_presets.remove(&name);
+        self.spots.filter_presets.draft = Default::default();
+        self.spot_filter_error = None;
+        self.refresh_spot_markers();
+    }
+
+    pub fn clear_signal_log(&mut self) {
+        self.meters.clear_log();
+    }
+
+    pub fn set_scan_start(&mut self, input: &str) {
+        let stop = self.scanner.settings().stop;
+        self.scanner_error =
+            match tuning::parse_frequency(input, DEFAULT_RANGE) {
+                Ok(start) if self.scanner.set_range(start, stop) => None,
+                Ok(_) => Some("scan start must be below the scan stop".to_string()),
+                Err(err) => Some(err.to_string()),
+            };
+    }
+
+    pub fn set_scan_stop(&mut self, input: &str) {
+        let start = self.scanner.settings().start;
+        self.scanner_error =
+            match tuning::parse_frequency(input, DEFAULT_RANGE) {
+                Ok(stop) if self.scanner.set_range(start, stop) => None,
+                Ok(_) => Some("scan stop must be above the scan start".to_string()),
+                Err(err) => Some(err.to_string()),
+            };
+    }
+
+    pub fn add_receiver(&mut self, radio_id: u32) {
+        self.send_command(Command::AddReceiver { id: radio_id });
+    }
+
+    // Receivers known to belong to a radio
+    pub fn radio_receivers(&self, radio_id: u32) -> Vec<&Receiver> {
+        self.receivers.iter().filter(|r| self.confirmed_receiver_radio(r.id) == Some(radio_id)).collect()
+    }
+
+    // Receivers we cannot place on a radio
+    pub fn unassigned_receivers(&self) -> Vec<&Receiver> {
+        self.receivers.iter().filter(|r| self.confirmed_receiver_radio(r.id).is_none()).collect()
+    }
+
+    // Radio a receiver is known to belong to: reported by SparkSDR, or
+    // the only radio there is.  Receivers are never placed on a radio by
+    // guessing.
+    pub fn confirmed_receiver_radio(&self, receiver_id: u32) -> Option<u32> {
+        match (self.reported_receiver_radios.get(&receiver_id), self.radios.len()) {
+            (Some(radio_id), _) => Some(*radio_id),
+            (None, 1) => Some(self.radios[0].id),
+            _ => None,
+        }
+    }
+
+    pub fn remove_radio_receivers(&mut self, radio_id: u32) {
+        let receivers: Vec<u32> = self.radio_receivers(radio_id).iter().map(|r| r.id).collect();
+        for receiver_id in receivers {
+            self.send_command(Command::RemoveReceiver { id: receiver_id });
+        }
     }
 
     pub fn get_radio_power_state(&self, radio_id: u32) -> Option<bool> {
@@ -562,6 +1448,351 @@ impl Model {
         }
     }
 
+    // Waterfall canvas for a receiver, the primary waterfall gets
+    // the "waterfall" id used by the receiver marker (static/js/app.js)
+    pub fn waterfall_canvas(&self, receiver_id: Option<u32>) -> Html {
+        let waterfall = receiver_id.and_then(|id| self.spectrum.waterfall(id));
+        let is_primary = receiver_id.is_some() && receiver_id == self.spectrum.primary();
+        let height = WATERFALL_HEIGHT.to_string();
+        let style = format!("position:relative;width:100%;height:{}px;background-color: black", WATERFALL_HEIGHT);
+        match (waterfall, receiver_id) {
+            (Some(waterfall), Some(receiver_id)) => {
+                let id = if is_primary { "waterfall" } else { "" };
+                // click (and drag) to tune
+                let onmousedown = self.link.callback(move |e: MouseEvent| {
+                    match tuning::pointer_ratio(&e) {
+                        Some(ratio) => Msg::TuneWaterfall(receiver_id, ratio),
+                        None => Msg::None,
+                    }
+                });
+                let onmousemove = self.link.callback(move |e: MouseEvent| {
+                    match tuning::pointer_ratio(&e) {
+                        Some(ratio) if e.buttons() & 1 == 1 => Msg::TuneWaterfall(receiver_id, ratio),
+                        _ => Msg::None,
+                    }
+                });
+                let onwheel = self.link.callback(move |e: WheelEvent| {
+                    e.prevent_default();
+                    Msg::TuneStep(receiver_id, tuning::wheel_steps(&e))
+                });
+                html! {
+                    <div class="waterfall-container"
+                        onmouseup=self.link.callback(|_| Msg::EndFilterDrag)
+                        onmouseleave=self.link.callback(|_| Msg::EndFilterDrag)>
+                        <canvas id=id class="tunable" tabindex="0" ref=waterfall.canvas_node_ref.clone() width=waterfall.width().to_string() height=height style=style
+                            onmousedown=onmousedown onmousemove=onmousemove onwheel=onwheel />
+                        { self.filter_handles(receiver_id) }
+                    </div>
+                }
+            },
+            _ => html! {
+                <canvas id="waterfall" width="2048" height=height style=style />
+            },
+        }
+    }
+
+    // Drag handles on the low/high filter edges of a receiver
+    fn filter_handles(&self, receiver_id: u32) -> Html {
+        if !self.experimental {
+            return html! {};
+        }
+        let (start, stop) =
+            match self.spectrum.waterfall(receiver_id) {
+                Some(waterfall) if waterfall.freq_stop() > waterfall.freq_start() => (waterfall.freq_start(), waterfall.freq_stop()),
+                _ => return html! {},
+            };
+        let receiver =
+            match self.receivers.iter().find(|r| r.id == receiver_id) {
+                Some(receiver) => receiver,
+                None => return html! {},
+            };
+        let edges = [
+            (FilterEdge::Low, receiver.filter_low.min(receiver.filter_high)),
+            (FilterEdge::High, receiver.filter_low.max(receiver.filter_high)),
+        ];
+        html! {
+            {
+                for edges.iter().filter_map(|(edge, offset)| {
+                    let ratio = (receiver.frequency as f64 + *offset as f64 - start) / (stop - start);
+                    if ratio < 0.0 || ratio > 1.0 {
+                        return None;
+                    }
+                    let edge = *edge;
+                    let (class, title) =
+                        match edge {
+                            FilterEdge::Low => ("filter-handle filter-low", "Filter low edge"),
+                            FilterEdge::High => ("filter-handle filter-high", "Filter high edge"),
+                        };
+                    Some(html! {
+                        <div class=class title=title style=format!("left:{:.3}%", ratio * 100.0)
+                            onmousedown=self.link.callback(move |e: MouseEvent| {
+                                e.stop_propagation();
+                                e.prevent_default();
+                                Msg::StartFilterDrag(receiver_id, edge)
+                            })></div>
+                    })
+                })
+            }
+        }
+    }
+
+    // Spectrum trace shown above a waterfall
+    pub fn panadapter_canvas(&self, receiver_id: Option<u32>) -> Html {
+        match receiver_id.and_then(|id| self.spectrum.waterfall(id)) {
+            Some(waterfall) => html! {
+                <canvas class="panadapter" ref=waterfall.panadapter_node_ref.clone() width=waterfall.width().to_string() height=PANADAPTER_HEIGHT.to_string()
+                    style=format!("width:100%;height:{}px;background-color: black", PANADAPTER_HEIGHT) />
+            },
+            None => html! {},
+        }
+    }
+
+    // Hidden canvases used to scroll each waterfall
+    pub fn waterfall_tmp_canvases(&self) -> Html {
+        html! {
+            { for self.spectrum.subscribed().iter().filter_map(|id| self.spectrum.waterfall(*id)).map(|waterfall| {
+                html! {
+                    <canvas ref=waterfall.tmp_canvas_node_ref.clone() width=waterfall.width().to_string() height=WATERFALL_HEIGHT.to_string() style="display:none;background-color: black ;" />
+                }
+              })
+            }
+        }
+    }
+
+    // Frequency range shown below a waterfall
+    pub fn waterfall_range(&self, receiver_id: Option<u32>) -> Html {
+        let (start, stop) =
+            match receiver_id.and_then(|id| self.spectrum.waterfall(id)) {
+                Some(waterfall) => (waterfall.freq_start(), waterfall.freq_stop()),
+                None => (0.0, 0.0),
+            };
+        html! {
+            <table style="width:100%;border-left:2px solid #555;border-right:2px solid #555">
+                <tr>
+                    <th style="padding-left:10px">{ start }</th>
+                    <th style="text-align:right;padding-right:10px">{ stop }</th>
+                </tr>
+            </table>
+        }
+    }
+
+    // Panadapter trace and waterfall level controls
+    pub fn spectrum_controls_view(&self) -> Html {
+        let trace_mode = self.spectrum.trace_mode();
+        let settings = self.spectrum.settings();
+        html! {
+            <div class="spectrum-controls field is-grouped">
+                <p class="control">
+                    <span class="select is-small">
+                        <select title="Panadapter trace"
+                            onchange=self.link.callback(|e: ChangeData|
+                                match e {
+                                    ChangeData::Select(sel) => Msg::SetTraceMode(TraceMode::new(&sel.value())),
+                                    _ => Msg::None,
+                                })>
+                            {
+                                for TRACE_MODES.iter().map(|mode| {
+                                    html! { <option selected=(*mode == trace_mode)>{ mode.name() }</option> }
+                                })
+                            }
+                        </select>
+                    </span>
+                </p>
+                <p class="control">
+                    <span class="select is-small">
+                        <select title="Waterfall palette"
+                            onchange=self.link.callback(|e: ChangeData|
+                                match e {
+                                    ChangeData::Select(sel) => Msg::SetPalette(Palette::new(&sel.value())),
+                                    _ => Msg::None,
+                                })>
+                            {
+                                for PALETTES.iter().map(|palette| {
+                                    html! { <option selected=(*palette == settings.palette)>{ palette.name() }</option> }
+                                })
+                            }
+                        </select>
+                    </span>
+                </p>
+                <p class="control">
+                    <label>{ "Ref " }</label>
+                    <input class="input is-small" type="number" step="5" title="Reference level (dB)"
+                        value=format!("{:.0}", self.spectrum.reference_level())
+                        disabled=settings.auto
+                        onchange=self.link.callback(|e: ChangeData|
+                            match e {
+                                ChangeData::Value(value) => match value.parse::<f32>() {
+                                    Ok(reference) => Msg::SetReferenceLevel(reference),
+                                    Err(_) => Msg::None,
+                                },
+                                _ => Msg::None,
+                            }) />
+                </p>
+                <p class="control">
+                    <label>{ "Range " }</label>
+                    <input class="input is-small" type="number" step="5" min=MIN_WATERFALL_RANGE.to_string() title="Dynamic range (dB)"
+                        value=format!("{:.0}", settings.range)
+                        onchange=self.link.callback(|e: ChangeData|
+                            match e {
+                                ChangeData::Value(value) => match value.parse::<f32>() {
+                                    Ok(range) => Msg::SetWaterfallRange(range),
+                                    Err(_) => Msg::None,
+                                },
+                                _ => Msg::None,
+                            }) />
+                </p>
+                <p class="control">
+                    <label>{ "Auto" }</label>
+                    <label class="switch">
+                        <input type="checkbox" checked=settings.auto onclick=self.link.callback(|_| Msg::ToggleAutoLevels) />
+                        <span class="slider"></span>
+                    </label>
+                </p>
+            </div>
+        }
+    }
+
+    // Band scanner range, activity detection and status
+    pub fn scanner_view(&self) -> Html {
+        let settings = self.scanner.settings();
+        let number = |value: String| -> Option<f64> { value.parse::<f64>().ok() };
+        let status =
+            match self.scanner.state() {
+                ScanState::Idle => String::new(),
+                state => {
+                    let level =
+                        match self.scanner.level() {
+                            Some(level) => format!("{:.0} dB", level),
+                            None => "-".to_string(),
+                        };
+                    let state = if let ScanState::Dwelling { .. } = state { "Active" } else { "Scanning" };
+                    format!("{} {:.3} kHz ({})", state, self.scanner.frequency() / 1000.0, level)
+                }
+            };
+        html! {
+            <div class="scanner">
+                <div class="field is-grouped">
+                    <p class="control">
+                        <label>{ "Scan " }</label>
+                        <input class="input is-small" type="text" title="Scan start"
+                            value=format!("{:.3}k", settings.start / 1000.0)
+                            onchange=self.link.callback(|e: ChangeData| match e {
+                                ChangeData::Value(value) => Msg::SetScanStart(value),
+                                _ => Msg::None,
+                            }) />
+                    </p>
+                    <p class="control">
+                        <label>{ "to " }</label>
+                        <input class="input is-small" type="text" title="Scan stop"
+                            value=format!("{:.3}k", settings.stop / 1000.0)
+                            onchange=self.link.callback(|e: ChangeData| match e {
+                                ChangeData::Value(value) => Msg::SetScanStop(value),
+                                _ => Msg::None,
+                            }) />
+                    </p>
+                    <p class="control">
+                        <span class="select is-small">
+                            <select title="Scan step"
+                                onchange=self.link.callback(|e: ChangeData| match e {
+                                    ChangeData::Select(sel) => match sel.value().parse::<f64>() {
+                                        Ok(step) => Msg::SetScanStep(step),
+                                        Err(_) => Msg::None,
+                                    },
+                                    _ => Msg::None,
+                                })>
+                                {
+                                    for TUNING_STEPS.iter().map(|step| {
+                                        html! { <option value=step.to_string() selected=(*step == settings.step)>{ tuning::step_name(*step) }</option> }
+                                    })
+                                }
+                            </select>
+                        </span>
+                    </p>
+                    <p class="control">
+                        <label>{ "Squelch " }</label>
+                        <input class="input is-small" type="number" min="0" title="Activity threshold (dB above noise floor)"
+                            value=format!("{:.0}", settings.squelch)
+                            onchange=self.link.callback(move |e: ChangeData| match e {
+                                ChangeData::Value(value) => match number(value) {
+                                    Some(squelch) => Msg::SetSquelch(squelch as f32),
+                                    None => Msg::None,
+                                },
+                                _ => Msg::None,
+                            }) />
+                    </p>
+                    <p class="control">
+                        <label>{ "Dwell " }</label>
+                        <input class="input is-small" type="number" min="1" title="Longest time on an active channel (s)"
+                            value=settings.dwell.to_string()
+                            onchange=self.link.callback(move |e: ChangeData| match e {
+                                ChangeData::Value(value) => match number(value) {
+                                    Some(dwell) => Msg::SetScanDwell(dwell as u64),
+                                    None => Msg::None,
+                                },
+                                _ => Msg::None,
+                            }) />
+                    </p>
+                    <p class="control">
+                        <label>{ "Resume " }</label>
+                        <input class="input is-small" type="number" min="0" title="Quiet time before resuming (s)"
+                            value=settings.resume.to_string()
+                            onchange=self.link.callback(move |e: ChangeData| match e {
+                                ChangeData::Value(value) => match number(value) {
+                                    Some(resume) => Msg::SetScanResume(resume as u64),
+                                    None => Msg::None,
+                                },
+                                _ => Msg::None,
+                            }) />
+                    </p>
+                    <p class="control">
+                        <button class=if self.scanner.is_running() { "button is-small is-danger" } else { "button is-small" }
+                            disabled=self.default_receiver().is_none()
+                            onclick=self.link.callback(|_| Msg::ToggleScanner)>
+                            { if self.scanner.is_running() { "Stop" } else { "Scan" } }
+                        </button>
+                    </p>
+                    <p class="control">
+                        <button class="button is-small" disabled=(!self.scanner.is_running()) onclick=self.link.callback(|_| Msg::SkipScanChannel)>
+                            { "Skip" }
+                        </button>
+                    </p>
+                    <p class="control scanner-status">{ status }</p>
+                </div>
+                {
+                    match &self.scanner_error {
+                        Some(err) => html! { <p class="help is-danger">{ err }</p> },
+                        None => html! {},
+                    }
+                }
+            </div>
+        }
+    }
+
+    // Additional (pinned) waterfalls stacked below the primary one
+    pub fn pinned_waterfalls_view(&self) -> Html {
+        html! {
+            { for self.spectrum.subscribed().into_iter().filter(|id| Some(*id) != self.spectrum.primary()).map(|receiver_id| {
+                let label =
+                    match self.receivers.iter().find(|r| r.id == receiver_id) {
+                        Some(receiver) => format!("{} {}", self.decimal_mark(receiver.frequency.to_string()), receiver.mode.mode()),
+                        None => format!("Receiver {}", receiver_id),
+                    };
+                html! {
+                    <div class="pinned-waterfall">
+                        <p class="waterfall-label">
+                            <a onclick=self.link.callback(move |_| Msg::SetDefaultReceiver(receiver_id))>{ label }</a>
+                            <button class="delete is-small" onclick=self.link.callback(move |_| Msg::ToggleSpectrum(receiver_id))></button>
+                        </p>
+                        { self.panadapter_canvas(Some(receiver_id)) }
+                        { self.waterfall_canvas(Some(receiver_id)) }
+                        { self.waterfall_range(Some(receiver_id)) }
+                    </div>
+                }
+              })
+            }
+        }
+    }
+
     pub fn spots_view(&self) -> Html {
         let table_class =
             match self.default_receiver() {
@@ -570,10 +1801,23 @@ impl Model {
                 },
                 _ => "table is-narrow is-fullwidth",
             };
+        let receiver = self.default_receiver();
+        let ctx = FilterContext { logs: self.import.as_ref(), log_generation: self.import_generation, receiver: receiver.as_ref() };
+        let aggregate = self.spots.aggregate_enabled();
+        let (aggregate_class, aggregate_title) =
+            match aggregate {
+                true => ("button is-info", "Show every decode"),
+                false => ("button", "Group decodes by station"),
+            };
 
         html! {
             <>
                 <div style="text-align:right;margin-top:10px">
+                    <button class=aggregate_class title=aggregate_title onclick=self.link.callback(move |_| Msg::ToggleSpotAggregation)>
+                        <span class="icon is-small">
+                            <i class="fas fa-layer-group"></i>
+                        </span>
+                    </button>
                     <button class="button" onclick=self.link.callback(move |_| Msg::ClearSpots)>
                         <span class="icon is-small">
                             <i class="far fa-trash-alt"></i>
@@ -585,7 +1829,7 @@ impl Model {
                         <tr>
                             <th>{ "UTC" }</th>
                             <th>{ "dB" }</th>
-                            <th>{ "DT" }</th>
+                            <th>{ if aggregate { "Spots" } else { "DT" } }</th>
                             <th class="freqc">{ "Freq" }</th>
                             <th class="modec">{ "Mode" }</th>
                             <th>{ "Dist" }</th>
@@ -600,9 +1844,18 @@ impl Model {
                                 }
                             }
                         </tr>
-                        { for self.spots.spots().iter().rev().map(|s| {
-                            self.spot(&s)
-                          })
+                        {
+                            match aggregate {
+                                true => html! {
+                                    { for self.spots.stations(&ctx).iter().map(|s| self.station(s)) }
+                                },
+                                false => html! {
+                                    { for self.spots.filtered_spots(&ctx).into_iter().rev().map(|s| {
+                                        self.spot(s)
+                                      })
+                                    }
+                                },
+                            }
                         }
                     </table>
                 </div>
@@ -692,6 +1945,26 @@ impl Model {
                               html! {}
                         }}
                     </tbody>
+                    <thead>
+                        <tr>
+                            <th colspan="2">{ "Filter Presets" }</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        <tr>
+                            <td colspan="2">{ self.spot_filter_presets_view() }</td>
+                        </tr>
+                    </tbody>
+                    <thead>
+                        <tr>
+                            <th colspan="2">{ "Spot History" }</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        <tr>
+                            <td colspan="2">{ self.spot_history_view() }</td>
+                        </tr>
+                    </tbody>
                     <thead>
                         <tr>
                             <th colspan="2">{ "Log File" }</th>
@@ -706,6 +1979,148 @@ impl Model {
             </div>
         }
     }
+
+    // Pick, edit and save named filter expressions
+    fn spot_filter_presets_view(&self) -> Html {
+        let presets = &self.spots.filter_presets;
+        let active = presets.active_name().unwrap_or("").to_string();
+        let draft = &presets.draft;
+        let saved = presets.presets().iter().any(|p| p.name == draft.name.trim());
+        html! {
+            <div class="spot-filter-presets">
+                <div class="select is-small is-fullwidth">
+                    <select onchange=self.link.callback(|e: ChangeData| match e {
+                            ChangeData::Select(sel) => Msg::SelectSpotFilterPreset(sel.value()),
+                            _ => Msg::None,
+                        })>
+                        <option value="" selected=active.is_empty()>{ "No preset" }</option>
+                        { for presets.presets().iter().map(|preset| html! {
+                            <option value=&preset.name selected=(preset.name == active)>{ &preset.name }</option>
+                        }) }
+                    </select>
+                </div>
+                <input class="input is-small" type="text" placeholder="Name" value=&draft.name
+                    oninput=self.link.callback(|e: InputData| Msg::SetSpotFilterName(e.value)) />
+                <textarea class="textarea is-small" rows="2" placeholder="band:20m and cq and not worked" title=FILTER_SYNTAX
+                    value=&draft.expression
+                    oninput=self.link.callback(|e: InputData| Msg::SetSpotFilterExpression(e.value))></textarea>
+                {
+                    match &self.spot_filter_error {
+                        Some(err) => html! { <p class="help is-danger">{ err }</p> },
+                        None => html! {},
+                    }
+                }
+                <div class="buttons">
+                    <button class="button is-small" onclick=self.link.callback(|_| Msg::SaveSpotFilterPreset)>{ "Save" }</button>
+                    <button class="button is-small" disabled=(!saved) onclick=self.link.callback(|_| Msg::DeleteSpotFilterPreset)>{ "Delete" }</button>
+                </div>
+            </div>
+        }
+    }
+
+    // Retention settings and "when did we last hear ..." lookups
+    fn spot_history_view(&self) -> Html {
+        let number = |value: String| -> Option<f64> { value.parse::<f64>().ok() };
+        let retention = self.spots.retention();
+        let history = &self.spots.history;
+        let query = &history.query;
+        let heard: Vec<_> =
+            match (query.call.trim(), query.band.as_str()) {
+                ("", "") => history.heard_since(chrono::Utc::now() - chrono::Duration::hours(24)).into_iter().take(RECENTLY_HEARD).collect(),
+                ("", band) => history.heard_on_band_since(band, chrono::Utc::now() - chrono::Duration::hours(24)).into_iter().take(RECENTLY_HEARD).collect(),
+                (call, "") => history.heard(call),
+                (call, band) => history.last_heard(call, Some(band)).into_iter().collect(),
+            };
+        html! {
+            <div class="spot-history">
+                <div class="field is-grouped">
+                    <p class="control">
+                        <label>{ "Keep " }</label>
+                        <input class="input is-small" type="number" min="1" title="Most spots kept in the table and on the map"
+                            value=retention.max_spots.to_string()
+                            onchange=self.link.callback(move |e: ChangeData| match e {
+                                ChangeData::Value(value) => match number(value) {
+                                    Some(max_spots) => Msg::SetSpotRetention(Retention { max_spots: max_spots as usize, ..retention }),
+                                    None => Msg::None,
+                                },
+                                _ => Msg::None,
+                            }) />
+                    </p>
+                    <p class="control">
+                        <label>{ "Minutes " }</label>
+                        <input class="input is-small" type="number" min="1" title="Oldest spot kept (minutes)"
+                            value=retention.max_age_minutes.to_string()
+                            onchange=self.link.callback(move |e: ChangeData| match e {
+                                ChangeData::Value(value) => match number(value) {
+                                    Some(minutes) => Msg::SetSpotRetention(Retention { max_age_minutes: minutes as i64, ..retention }),
+                                    None => Msg::None,
+                                },
+                                _ => Msg::None,
+                            }) />
+                    </p>
+                </div>
+                <div class="field is-grouped">
+                    <p class="control is-expanded">
+                        <input class="input is-small" type="text" placeholder="Call" value=&query.call
+                            oninput=self.link.callback(|e: InputData| Msg::SetHistoryCall(e.value)) />
+                    </p>
+                    <p class="control">
+                        <span class="select is-small">
+                            <select onchange=self.link.callback(|e: ChangeData| match e {
+                                    ChangeData::Select(sel) => Msg::SetHistoryBand(sel.value()),
+                                    _ => Msg::None,
+                                })>
+                                <option value="" selected=query.band.is_empty()>{ "Any band" }</option>
+                                { for history.bands().into_iter().map(|band| html! {
+                                    <option value=&band selected=(band == query.band)>{ &band }</option>
+                                }) }
+                            </select>
+                        </span>
+                    </p>
+                </div>
+                {
+                    if heard.is_empty() {
+                        match query.call.trim() {
+                            "" => html! { <p class="help">{ "Nothing heard recently" }</p> },
+                            call => html! { <p class="help">{ format!("{} not heard", call.to_uppercase()) }</p> },
+                        }
+                    } else {
+                        html! {
+                            <table class="table is-narrow is-fullwidth">
+                                <tr>
+                                    <th>{ "Call" }</th>
+                                    <th>{ "Band" }</th>
+                                    <th>{ "Last" }</th>
+                                    <th>{ "dB" }</th>
+                                    <th>{ "Spots" }</th>
+                                </tr>
+                                { for heard.iter().map(|h| html! {
+                                    <tr title=format!("{:.3} kHz {}, first heard {}", h.frequency / 1000.0, h.mode, h.first.format("%Y-%m-%d %H:%MZ"))>
+                                        <td>{ &h.call }</td>
+                                        <td>{ &h.band }</td>
+                                        <td>{ h.last.format("%m-%d %H:%MZ").to_string() }</td>
+                                        <td>{ format!("{:.0}", h.snr) }</td>
+                                        <td>{ h.count }</td>
+                                    </tr>
+                                }) }
+                            </table>
+                        }
+                    }
+                }
+                {
+                    match history.save_error() {
+                        Some(err) => html! { <p class="help is-danger">{ err }</p> },
+                        None => html! {},
+                    }
+                }
+                <div class="buttons">
+                    <span class="help">{ format!("{} stations remembered", history.len()) }</span>
+                    <button class="button is-small" onclick=self.link.callback(|_| Msg::ClearSpotHistory)>{ "Clear" }</button>
+                </div>
+            </div>
+        }
+    }
+
     pub fn version_html(&self) -> Html {
         match &self.version {
             Some(version) => {
@@ -760,6 +2175,74 @@ impl Model {
     }
 
     fn spot(&self, spot: &Spot) -> Html {
+        html! {
+            <tr>
+                <td>{ spot.time.format("%H%M%S") }</td>
+                <td>{ spot.snr }</td>
+                <td>{ spot.dt }</td>
+                <td class="freqc"><span>{ format!("{} (+", spot.tuned_frequency) }</span>{ format!("{}", (spot.frequency - spot.tuned_frequency)) }<span>{ ")" }</span></td>
+                <th class="modec">{ spot.mode.mode() }</th>
+                <td>{ match spot.distance {
+                         Some(dist) => format!("{}", dist),
+                         None => format!(""),
+                      }
+                    }</td>
+                { self.spot_message(spot) }
+                { self.spot_call_columns(spot) }
+            </tr>
+        }
+    }
+
+    // One row per station (call, band and mode) in the aggregated spot table
+    fn station(&self, station: &Station) -> Html {
+        let spot = &station.spot;
+        let mut frequencies: Vec<String> = station.frequencies.iter()
+            .take(MAX_FREQUENCIES_SHOWN)
+            .map(|frequency| format!("+{}", *frequency as f32 - spot.tuned_frequency))
+            .collect();
+        if station.frequencies.len() > MAX_FREQUENCIES_SHOWN {
+            frequencies.push("\u{2026}".to_string());
+        }
+        html! {
+            <tr title=format!("first heard {}", station.heard.first.format("%H%M%S"))>
+                <td>{ station.last().format("%H%M%S") }</td>
+                <td title=format!("best {:.0} dB", station.best_snr)>{ format!("{:.0} {}", station.last_snr(), station.trend().symbol()) }</td>
+                <td>{ station.heard.count }</td>
+                <td class="freqc"><span>{ format!("{} (", spot.tuned_frequency) }</span>{ frequencies.join(", ") }<span>{ ")" }</span></td>
+                <th class="modec">{ spot.mode.mode() }</th>
+                <td>{ match spot.distance {
+                         Some(dist) => format!("{}", dist),
+                         None => format!(""),
+                      }
+                    }</td>
+                { self.spot_message(spot) }
+                { self.spot_call_columns(spot) }
+            </tr>
+        }
+    }
+
+    // Message of a spot, CQs tune to the receiver they were decoded on
+    fn spot_message(&self, spot: &Spot) -> Html {
+        let spot_receiver_id =
+            if let Some(index) = self.receivers.iter().position(|i| i.frequency == spot.tuned_frequency && i.mode == spot.mode ) {
+                Some(self.receivers[index].id)
+            } else {
+                None
+            };
+
+        if let Some(msg) = &spot.msg {
+            match (msg.contains("CQ"), spot_receiver_id) {
+                (true, Some(receiver_id)) => html! { <th><a onclick=self.link.callback(move |_| Msg::SetDefaultReceiver(receiver_id) )>{ msg.to_string() }</a></th> },
+                (true, None) => html! { <th>{ msg.to_string() }</th> },
+                (false, _) => html! { <td>{ msg.to_string() }</td> }
+            }
+        } else {
+            html! { <td>{ "--" }</td> }
+        }
+    }
+
+    // Country, state, operator and LoTW columns of a spot
+    fn spot_call_columns(&self, spot: &Spot) -> Html {
         let (country_icon, state_class) =
             match spot.call.country() {
                 Ok(country) => {
@@ -799,36 +2282,8 @@ impl Model {
                 LoTWStatus::Unknown => (false, html! {})
             };
 
-        let spot_receiver_id =
-            if let Some(index) = self.receivers.iter().position(|i| i.frequency == spot.tuned_frequency && i.mode == spot.mode ) {
-                Some(self.receivers[index].id)
-            } else {
-                None
-            };
-
         html! {
-            <tr>
-                <td>{ spot.time.format("%H%M%S") }</td>
-                <td>{ spot.snr }</td>
-                <td>{ spot.dt }</td>
-                <td class="freqc"><span>{ format!("{} (+", spot.tuned_frequency) }</span>{ format!("{}", (spot.frequency - spot.tuned_frequency)) }<span>{ ")" }</span></td>
-                <th class="modec">{ spot.mode.mode() }</th>
-                <td>{ match spot.distance {
-                         Some(dist) => format!("{}", dist),
-                         None => format!(""),
-                      }
-                    }</td>
-                {
-                    if let Some(msg) = &spot.msg {
-                        match (msg.contains("CQ"), spot_receiver_id) {
-                            (true, Some(receiver_id)) => html! { <th><a onclick=self.link.callback(move |_| Msg::SetDefaultReceiver(receiver_id) )>{ msg.to_string() }</a></th> },
-                            (true, None) => html! { <th>{ msg.to_string() }</th> },
-                            (false, _) => html! { <td>{ msg.to_string() }</td> }
-                        }
-                    } else {
-                        html! { <td>{ "--" }</td> }
-                    }
-                }
+            <>
                 <td>{ country_icon }</td>
                 <td class=state_class>{ match spot.call.state() {
                           Some(state) => format!("{}", state),
@@ -844,7 +2299,7 @@ impl Model {
                         false => html! {}
                     }
                 }
-            </tr>
+            </>
         }
     }
 
@@ -863,10 +2318,11 @@ impl Model {
             } else {
                 ("receiver-control", false)
             };
+        let is_recording = self.audio.channel(receiver_id).map(|c| c.is_recording()).unwrap_or(false);
         let mute_unmute_main_class =
-            match self.audio.receiving_audio() {
-                Some(_) => "icon is-small",
-                None => "icon is-small has-text-danger",
+            match self.audio.is_subscribed(receiver_id) {
+                true => "icon is-small",
+                false => "icon is-small has-text-danger",
             };
 
         if self.show_receiver_list || is_default {
@@ -879,7 +2335,10 @@ impl Model {
                         })
                     }
                 </div>
-                <div id="frequency" class="frequency">
+                <div id="frequency" class="frequency" tabindex="0" onwheel=self.link.callback(move |e: WheelEvent| {
+                        e.prevent_default();
+                        Msg::TuneStep(receiver_id, tuning::wheel_steps(&e))
+                    })>
                     {
                         for tmp.chars().map(|c| {
                             if ((c != '0' && c != ',' && inactive == true)) {
@@ -901,6 +2360,31 @@ impl Model {
                     }
                 </div>
                 <div class="mode control" style="margin-top:-0.5em;z-index:50">
+                    {
+                        if self.show_receiver_list && !is_default {
+                            let waterfall_class =
+                                match self.spectrum.is_pinned(receiver_id) {
+                                    true => "icon is-small has-text-success",
+                                    false => "icon is-small",
+                                };
+                            html! {
+                                <>
+                                <button style="float:right" class="button is-text" title="Waterfall" onclick=self.link.callback(move |e: MouseEvent| { e.stop_propagation(); Msg::ToggleSpectrum(receiver_id) })>
+                                    <span class=waterfall_class>
+                                    <i class="fas fa-chart-area"></i>
+                                    </span>
+                                </button>
+                                <button style="float:right" class="button is-text" title="Audio" onclick=self.link.callback(move |e: MouseEvent| { e.stop_propagation(); Msg::ToggleAudio(receiver_id) })>
+                                    <span class=mute_unmute_main_class>
+                                        <i class="fas fa-volume-up"></i>
+                                    </span>
+                                </button>
+                                </>
+                            }
+                        } else {
+                            html! {}
+                        }
+                    }
                     {
                         if self.show_receiver_list {
                             html! {
@@ -915,12 +2399,38 @@ impl Model {
                         }
                     }
                     { if is_default {
+                            let step = self.tuner.step();
                             html! {
-                                <button style="float:right" class="button is-text" onclick=self.link.callback(move |_| Msg::EnableAudio )>
+                                <>
+                                <div class="select is-small tuning-step" style="float:right">
+                                    <select title="Tuning step"
+                                        onclick=self.link.callback(|e: MouseEvent| { e.stop_propagation(); Msg::None })
+                                        onchange=self.link.callback(|e: ChangeData|
+                                            match e {
+                                                ChangeData::Select(sel) => match sel.value().parse::<f64>() {
+                                                    Ok(step) => Msg::SetTuningStep(step),
+                                                    Err(_) => Msg::None,
+                                                },
+                                                _ => Msg::None,
+                                            })>
+                                        {
+                                            for TUNING_STEPS.iter().map(|s| {
+                                                html! { <option value=s.to_string() selected=(*s == step)>{ tuning::step_name(*s) }</option> }
+                                            })
+                                        }
+                                    </select>
+                                </div>
+                                <button style="float:right" class="button is-text" title="Audio" onclick=self.link.callback(move |_| Msg::ToggleAudio(receiver_id))>
                                     <span class=mute_unmute_main_class>
                                         <i class="fas fa-volume-up"></i>
                                     </span>
                                 </button>
+                                <button style="float:right" class="button is-text" title="Record" onclick=self.link.callback(|_| Msg::ToggleRecording)>
+                                    <span class=if is_recording { "icon is-small has-text-danger" } else { "icon is-small" }>
+                                        <i class=if is_recording { "fas fa-stop-circle" } else { "fas fa-circle" }></i>
+                                    </span>
+                                </button>
+                                </>
                             }
                         } else {
                             html! { }
@@ -940,6 +2450,11 @@ impl Model {
                             })
                         }
                     </select>
+                    { self.filter_select(receiver) }
+                    { self.meters_view(receiver_id) }
+                    { self.audio_mixer_controls(receiver_id) }
+                    { if is_default { self.frequency_entry(receiver_id) } else { html! {} } }
+                    { if is_default { self.recording_view(receiver_id) } else { html! {} } }
                 </div>
             </div>
         }
@@ -948,6 +2463,345 @@ impl Model {
         }
     }
 
+    // S-meter from the passband power and the audio output level
+    fn meters_view(&self, receiver_id: u32) -> Html {
+        let signal = self.meters.signal(receiver_id);
+        let audio = self.audio.channel(receiver_id).map(|c| c.level());
+        if signal.is_none() && audio.is_none() {
+            return html! {};
+        }
+        html! {
+            <div class="meters">
+                {
+                    match signal {
+                        Some(signal) => html! {
+                            <div class="meter" title=format!("Passband {:.1} dBm, noise {:.1} dBm", signal.power, signal.noise_floor)>
+                                <span class="meter-label">{ meter::s_units(signal.power) }</span>
+                                <progress class="progress is-small is-success" max="1" value=meter::s_meter_fraction(signal.power).to_string()></progress>
+                                <span class="meter-value">{ format!("{:.0} dBm, {:.0} dB SNR", signal.power, signal.snr()) }</span>
+                            </div>
+                        },
+                        None => html! {},
+                    }
+                }
+                {
+                    match audio {
+                        Some(level) => html! {
+                            <div class="meter" title=format!("Audio peak {:.1} dBFS", level.peak)>
+                                <span class="meter-label">{ "AF" }</span>
+                                <progress class=if level.peak > -1.0 { "progress is-small is-danger" } else { "progress is-small is-info" } max="1"
+                                    value=meter::audio_fraction(level.rms).to_string()></progress>
+                                <span class="meter-value">{ format!("{:.0} dBFS", level.rms) }</span>
+                            </div>
+                        },
+                        None => html! {},
+                    }
+                }
+            </div>
+        }
+    }
+
+    // Volume, pan, mute and solo for a receiver we are receiving audio from
+    fn audio_mixer_controls(&self, receiver_id: u32) -> Html {
+        let channel =
+            match self.audio.channel(receiver_id) {
+                Some(channel) => channel,
+                None => return html! {},
+            };
+        let is_solo = self.audio.solo() == Some(receiver_id);
+        let stats = channel.stats();
+        html! {
+            <div class="audio-mixer" onclick=self.link.callback(|e: MouseEvent| { e.stop_propagation(); Msg::None })>
+                <input type="range" min="0" max="2" step="0.05" title="Volume" value=channel.volume().to_string()
+                    oninput=self.link.callback(move |e: InputData| match e.value.parse::<f32>() {
+                        Ok(volume) => Msg::SetReceiverVolume(receiver_id, volume),
+                        Err(_) => Msg::None,
+                    }) />
+                <input type="range" min="-1" max="1" step="0.1" title="Pan" value=channel.pan().to_string()
+                    oninput=self.link.callback(move |e: InputData| match e.value.parse::<f32>() {
+                        Ok(pan) => Msg::SetReceiverPan(receiver_id, pan),
+                        Err(_) => Msg::None,
+                    }) />
+                <div class="buttons has-addons">
+                    <button class=if channel.is_muted() { "button is-small is-danger" } else { "button is-small" } title="Mute"
+                        onclick=self.link.callback(move |_| Msg::ToggleReceiverMute(receiver_id))>{ "M" }</button>
+                    <button class=if is_solo { "button is-small is-warning" } else { "button is-small" } title="Solo"
+                        onclick=self.link.callback(move |_| Msg::ToggleReceiverSolo(receiver_id))>{ "S" }</button>
+                    <button class=if self.audio.raw_pcm() { "button is-small is-info" } else { "button is-small" }
+                        title="Play audio frames as raw 16 bit PCM (48 kHz), for all receivers"
+                        onclick=self.link.callback(|_| Msg::ToggleRawPcm)>{ "PCM" }</button>
+                </div>
+                { self.dsp_controls(receiver_id) }
+                <p class="audio-stats" title="Audio buffer: queued/target latency, packet length, underruns, dropped packets and playback rate">
+                    { format!("{:.0}/{:.0} ms, {:.1} ms pkts, {} under, {} drop, x{:.3}",
+                        stats.latency * 1000.0, stats.target * 1000.0, stats.packet_duration * 1000.0,
+                        stats.underruns, stats.overruns, stats.rate) }
+                </p>
+            </div>
+        }
+    }
+
+    fn dsp_controls(&self, receiver_id: u32) -> Html {
+        let settings = self.audio.dsp_settings(receiver_id);
+        html! {
+            <div class="buttons has-addons dsp-controls">
+                { for DSP_FEATURES.iter().map(|feature| {
+                    let feature = *feature;
+                    html! {
+                        <button class=if settings.enabled(feature) { "button is-small is-info" } else { "button is-small" } title=feature.title()
+                            onclick=self.link.callback(move |_| Msg::ToggleDsp(receiver_id, feature))>{ feature.name() }</button>
+                    }
+                }) }
+            </div>
+        }
+    }
+
+    // Recording length while recording, download link when finished
+    fn recording_view(&self, receiver_id: u32) -> Html {
+        match (self.audio.channel(receiver_id).and_then(|c| c.recording_status()), &self.recording_download) {
+            (Some((seconds, full)), _) => html! {
+                <p class="recording help is-danger">
+                    { format!("Recording {}:{:02}{}", seconds as u32 / 60, seconds as u32 % 60, if full { " (limit reached)" } else { "" }) }
+                </p>
+            },
+            (None, Some((file_name, url))) => html! {
+                <p class="recording help">
+                    <a download=file_name href=url onclick=self.link.callback(|e: MouseEvent| { e.stop_propagation(); Msg::None })>{ format!("Download {}", file_name) }</a>
+                </p>
+            },
+            (None, None) => html! {},
+        }
+    }
+
+    // Filter width presets for the receiver's mode
+    fn filter_select(&self, receiver: &Receiver) -> Html {
+        if !self.experimental {
+            return html! {};
+        }
+        let receiver_id = receiver.id;
+        let low = receiver.filter_low.min(receiver.filter_high);
+        let high = receiver.filter_low.max(receiver.filter_high);
+        let presets = filters::filter_presets(&receiver.mode.mode());
+        let current = presets.iter().position(|p| p.low == low && p.high == high);
+        html! {
+            <select class="select filter-select" title="Filter width"
+                onclick=self.link.callback(|e: MouseEvent| { e.stop_propagation(); Msg::None })
+                onchange=self.link.callback(move |e: ChangeData|
+                    match e {
+                        ChangeData::Select(sel) => match sel.value().parse::<usize>() {
+                            Ok(preset) => Msg::ApplyFilterPreset(receiver_id, preset),
+                            Err(_) => Msg::None,
+                        },
+                        _ => Msg::None,
+                    })>
+                {
+                    match current {
+                        Some(_) => html! {},
+                        None => html! { <option value="" selected=true>{ filters::width_name(low, high) }</option> },
+                    }
+                }
+                {
+                    for presets.iter().enumerate().map(|(i, preset)| {
+                        html! { <option value=i.to_string() selected=(current == Some(i))>{ preset.name }</option> }
+                    })
+                }
+            </select>
+        }
+    }
+
+    // Typed frequency and band preset controls for the default receiver
+    fn frequency_entry(&self, receiver_id: u32) -> Html {
+        html! {
+            <div class="frequency-entry">
+                <div class="field has-addons">
+                    <p class="control is-expanded">
+                        <input class="input is-small" type="text" placeholder="14.074 MHz" title="Frequency (Hz, kHz or MHz)"
+                            onchange=self.link.callback(move |e: ChangeData|
+                                match e {
+                                    ChangeData::Value(value) => Msg::EnterFrequency(receiver_id, value),
+                                    _ => Msg::None,
+                                }) />
+                    </p>
+                    <p class="control">
+                        <span class="select is-small">
+                            <select title="Band presets"
+                                onchange=self.link.callback(move |e: ChangeData|
+                                    match e {
+                                        ChangeData::Select(sel) => match sel.value().parse::<usize>() {
+                                            Ok(preset) => Msg::ApplyBandPreset(receiver_id, preset),
+                                            Err(_) => Msg::None,
+                                        },
+                                        _ => Msg::None,
+                                    })>
+                                <option value="" selected=true>{ "Band" }</option>
+                                {
+                                    for BAND_PRESETS.iter().enumerate().map(|(i, preset)| {
+                                        html! { <option value=i.to_string()>{ preset.label() }</option> }
+                                    })
+                                }
+                            </select>
+                        </span>
+                    </p>
+                </div>
+                {
+                    match &self.frequency_entry_error {
+                        Some(err) => html! { <p class="help is-danger">{ err }</p> },
+                        None => html! {},
+                    }
+                }
+            </div>
+        }
+    }
+
+    pub fn memories_view(&self) -> Html {
+        let target = self.memory_target();
+        let scanning = self.memories.scan().map(|scan| scan.index);
+        let data_uri = |mime: &str, content: String| format!("data:{};charset=utf-8,{}", mime, String::from(js_sys::encode_uri_component(&content)));
+        html! {
+            <div class="memories">
+                <div class="field is-grouped memory-save">
+                    <p class="control">
+                        <input class="input is-small" type="text" placeholder="Name" value=&self.memories.draft.name
+                            oninput=self.link.callback(|e: InputData| Msg::SetMemoryName(e.value)) />
+                    </p>
+                    <p class="control is-expanded">
+                        <input class="input is-small" type="text" placeholder="Notes" value=&self.memories.draft.notes
+                            oninput=self.link.callback(|e: InputData| Msg::SetMemoryNotes(e.value)) />
+                    </p>
+                    <p class="control">
+                        <input class="input is-small" type="text" placeholder="Tags (comma separated)" value=&self.memories.draft.tags
+                            oninput=self.link.callback(|e: InputData| Msg::SetMemoryTags(e.value)) />
+                    </p>
+                    <p class="control">
+                        <button class="button is-small is-link" disabled=self.default_receiver().is_none()
+                            onclick=self.link.callback(|_| Msg::SaveMemory)>
+                            { "Save current" }
+                        </button>
+                    </p>
+                </div>
+                <div class="field is-grouped memory-controls">
+                    <p class="control">
+                        <label>{ "Recall onto" }</label>
+                        <span class="select is-small">
+                            <select onchange=self.link.callback(|e: ChangeData| match e {
+                                    ChangeData::Select(sel) => match sel.value().parse::<u32>() {
+                                        Ok(receiver_id) => Msg::SetMemoryTarget(receiver_id),
+                                        Err(_) => Msg::None,
+                                    },
+                                    _ => Msg::None,
+                                })>
+                                {
+                                    for self.receivers.iter().map(|receiver| {
+                                        html! {
+                                            <option value=receiver.id.to_string() selected=(Some(receiver.id) == target)>
+                                                { format!("RX {} ({:.3} kHz)", receiver.id, receiver.frequency / 1000.0) }
+                                            </option>
+                                        }
+                                    })
+                                }
+                            </select>
+                        </span>
+                    </p>
+                    <p class="control">
+                        <label>{ "Dwell (s)" }</label>
+                        <input class="input is-small" type="number" min="1" value=self.memories.scan_interval().to_string()
+                            onchange=self.link.callback(|e: ChangeData| match e {
+                                ChangeData::Value(value) => match value.parse::<u64>() {
+                                    Ok(seconds) => Msg::SetMemoryScanInterval(seconds),
+                                    Err(_) => Msg::None,
+                                },
+                                _ => Msg::None,
+                            }) />
+                    </p>
+                    <p class="control">
+                        {
+                            match scanning {
+                                Some(_) => html! {
+                                    <button class="button is-small is-danger" onclick=self.link.callback(|_| Msg::StopMemoryScan)>{ "Stop scan" }</button>
+                                },
+                                None => html! {
+                                    <button class="button is-small" disabled=(self.memories.memories().is_empty() || target.is_none())
+                                        onclick=self.link.callback(|_| Msg::StartMemoryScan)>{ "Scan" }</button>
+                                },
+                            }
+                        }
+                    </p>
+                    <p class="control">
+                        <a class="button is-small" download="memories.csv" href=data_uri("text/csv", self.memories.to_csv())>{ "Export CSV" }</a>
+                    </p>
+                    <p class="control">
+                        <a class="button is-small" download="memories.json" href=data_uri("application/json", self.memories.to_json())>{ "Export JSON" }</a>
+                    </p>
+                    <p class="control">
+                        <input class="file-import" type="file" accept=".csv,.json" title="Import memories (csv or json)"
+                            onchange=self.link.callback(move |value| {
+                                let mut result = Vec::new();
+                                if let ChangeData::Files(files) = value {
+                                    let files = js_sys::try_iter(&files)
+                                        .unwrap()
+                                        .unwrap()
+                                        .into_iter()
+                                        .map(|v| File::from(v.unwrap()));
+                                    result.extend(files);
+                                }
+                                Msg::ImportMemories(result)
+                            }) />
+                    </p>
+                </div>
+                {
+                    match &self.memory_message {
+                        Some(message) => html! { <p class="help">{ message }</p> },
+                        None => html! {},
+                    }
+                }
+                <table class="table is-fullwidth is-narrow is-hoverable">
+                    <thead>
+                        <tr>
+                            <th>{ "Name" }</th>
+                            <th>{ "Frequency" }</th>
+                            <th>{ "Mode" }</th>
+                            <th>{ "Filter" }</th>
+                            <th>{ "Notes" }</th>
+                            <th>{ "Tags" }</th>
+                            <th></th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                    {
+                        for self.memories.memories().iter().enumerate().map(|(i, memory)| {
+                            let filter =
+                                match (memory.filter_low, memory.filter_high) {
+                                    (Some(low), Some(high)) => format!("{} / {}", low, high),
+                                    _ => String::new(),
+                                };
+                            html! {
+                                <tr class=if scanning == Some(i) { "is-selected" } else { "" }>
+                                    <td>{ &memory.name }</td>
+                                    <td>{ format!("{:.3} kHz", memory.frequency / 1000.0) }</td>
+                                    <td>{ &memory.mode }</td>
+                                    <td>{ filter }</td>
+                                    <td>{ &memory.notes }</td>
+                                    <td>
+                                        { for memory.tags.iter().map(|tag| html! { <span class="tag">{ tag }</span> }) }
+                                    </td>
+                                    <td>
+                                        <div class="buttons">
+                                            <button class="button is-small" disabled=target.is_none()
+                                                onclick=self.link.callback(move |_| Msg::RecallMemory(i))>{ "Recall" }</button>
+                                            <button class="delete" title="Delete memory"
+                                                onclick=self.link.callback(move |_| Msg::DeleteMemory(i))></button>
+                                        </div>
+                                    </td>
+                                </tr>
+                            }
+                        })
+                    }
+                    </tbody>
+                </table>
+            </div>
+        }
+    }
+
     fn decimal_mark(&self, s: String) -> String {
         let bytes: Vec<_> = s.bytes().rev().collect();
         let chunks: Vec<_> = bytes.chunks(3).map(|chunk| str::from_utf8(chunk).unwrap()).collect();
@@ -962,11 +2816,10 @@ impl Model {
                 true => "icon is-small has-text-success",
                 false => "icon is-small",
             };
-        let short_name = &radio.name[14..];
         html! {
             <>
-                <a class="navbar-item" disabled=true>
-                    { short_name }
+                <a class="navbar-item" title=&radio.name onclick=self.link.callback(move |_| Msg::ChangeRoute(AppRoute::Radio(radio_id)))>
+                    { radio_display_name(&radio.name) }
                 </a>
                 <div class="navbar-item">
                     <div class="field has-addons">
@@ -990,17 +2843,172 @@ impl Model {
         }
     }
 
+    // Radio details and management (/radio/{id})
+    pub fn radio_view(&self, radio_id: u32) -> Html {
+        let radio =
+            match self.radios.iter().find(|r| r.id == radio_id) {
+                Some(radio) => radio,
+                None => return html! {
+                    <div class="radio-details">
+                        <p>{ format!("Radio {} not found", radio_id) }</p>
+                    </div>
+                },
+            };
+        let receivers = self.radio_receivers(radio_id);
+        let unassigned = self.unassigned_receivers();
+        let info = self.radio_info.get(&radio_id);
+        html! {
+            <div class="radio-details">
+                <h2 class="subtitle">{ radio_display_name(&radio.name) }</h2>
+                <table class="table is-narrow">
+                    <tbody>
+                        <tr><th>{ "ID" }</th><td>{ radio.id }</td></tr>
+                        <tr><th>{ "Name" }</th><td>{ &radio.name }</td></tr>
+                        <tr><th>{ "State" }</th><td>{ if radio.running { "Running" } else { "Stopped" } }</td></tr>
+                        <tr><th>{ "Receivers" }</th><td>{ receivers.len() }</td></tr>
+                        <tr><th>{ "Transmit" }</th><td>{ if self.transmitter.is_capable(radio_id) { "Yes" } else { "Not reported" } }</td></tr>
+                        {
+                            for info.iter().flat_map(|info| info.properties.iter())
+                                .filter(|(key, _)| key.as_str() != "Name" && key.as_str() != "Running")
+                                .map(|(key, value)| html! {
+                                    <tr><th>{ key }</th><td>{ property_value(value) }</td></tr>
+                                })
+                        }
+                    </tbody>
+                </table>
+                <div class="buttons">
+                    <button class=if radio.running { "button is-danger" } else { "button is-success" }
+                        onclick=self.link.callback(move |_| Msg::TogglePower(radio_id))>
+                        { if radio.running { "Stop" } else { "Start" } }
+                    </button>
+                    <button class="button" onclick=self.link.callback(move |_| Msg::AddReceiver(radio_id))>
+                        { "Add Receiver" }
+                    </button>
+                    <button class="button" disabled=receivers.is_empty()
+                        title="Removes the receivers known to belong to this radio"
+                        onclick=self.link.callback(move |_| Msg::RemoveRadioReceivers(radio_id))>
+                        { "Remove All Receivers" }
+                    </button>
+                </div>
+                { self.receiver_table(&receivers) }
+                {
+                    match unassigned.is_empty() {
+                        true => html! {},
+                        false => html! {
+                            <>
+                                <h3 class="subtitle is-6" title="SparkSDR did not report which radio these receivers belong to">
+                                    { "Unassigned receivers" }
+                                </h3>
+                                { self.receiver_table(&unassigned) }
+                            </>
+                        },
+                    }
+                }
+                { self.signal_log_view(&receivers.iter().map(|r| r.id).collect::<Vec<u32>>()) }
+            </div>
+        }
+    }
+
+    fn receiver_table(&self, receivers: &[&Receiver]) -> Html {
+        html! {
+            <table class="table is-fullwidth is-narrow is-hoverable">
+                <thead>
+                    <tr>
+                        <th>{ "Receiver" }</th>
+                        <th>{ "Frequency" }</th>
+                        <th>{ "Mode" }</th>
+                        <th>{ "Filter" }</th>
+                        <th></th>
+                    </tr>
+                </thead>
+                <tbody>
+                {
+                    for receivers.iter().map(|receiver| {
+                        let receiver_id = receiver.id;
+                        let is_default = self.default_receiver == Some(receiver_id);
+                        html! {
+                            <tr class=if is_default { "is-selected" } else { "" }>
+                                <td>{ receiver_id }</td>
+                                <td>{ format!("{:.3} kHz", receiver.frequency / 1000.0) }</td>
+                                <td>{ receiver.mode.mode() }</td>
+                                <td>{ format!("{} / {}", receiver.filter_low, receiver.filter_high) }</td>
+                                <td>
+                                    <div class="buttons">
+                                        <button class="button is-small" disabled=is_default
+                                            onclick=self.link.callback(move |_| Msg::SetDefaultReceiver(receiver_id))>{ "Select" }</button>
+                                        <button class="delete" title="Remove receiver"
+                                            onclick=self.link.callback(move |_| Msg::RemoveReceiver(receiver_id))></button>
+                                    </div>
+                                </td>
+                            </tr>
+                        }
+                    })
+                }
+                </tbody>
+            </table>
+        }
+    }
+
+    // Our meter readings next to the SNR reported by spots decoded on
+    // the given receivers, newest first
+    fn signal_log_view(&self, receiver_ids: &[u32]) -> Html {
+        let reports: Vec<&SignalReport> = self.meters.log().iter().rev().filter(|r| receiver_ids.contains(&r.receiver_id)).collect();
+        if reports.is_empty() {
+            return html! {};
+        }
+        html! {
+            <div class="signal-log">
+                <h3 class="subtitle is-6">
+                    { "Signal reports" }
+                    <button class="delete is-small" title="Clear" onclick=self.link.callback(|_| Msg::ClearSignalLog)></button>
+                </h3>
+                <table class="table is-fullwidth is-narrow">
+                    <thead>
+                        <tr>
+                            <th>{ "UTC" }</th>
+                            <th>{ "Receiver" }</th>
+                            <th>{ "Call" }</th>
+                            <th>{ "Freq" }</th>
+                            <th>{ "Spot dB" }</th>
+                            <th>{ "S-meter" }</th>
+                            <th>{ "SNR" }</th>
+                            <th>{ "Audio" }</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                    {
+                        for reports.iter().map(|report| html! {
+                            <tr>
+                                <td>{ report.time.format("%H:%M:%S").to_string() }</td>
+                                <td>{ report.receiver_id }</td>
+                                <td>{ &report.call }</td>
+                                <td>{ format!("{:.0}", report.frequency) }</td>
+                                <td>{ format!("{:.0}", report.spot_snr) }</td>
+                                <td>{ format!("{} ({:.0} dBm)", meter::s_units(report.power), report.power) }</td>
+                                <td>{ format!("{:.0} dB", report.snr) }</td>
+                                <td>{ report.audio.map(|a| format!("{:.0} dBFS", a)).unwrap_or_default() }</td>
+                            </tr>
+                        })
+                    }
+                    </tbody>
+                </table>
+            </div>
+        }
+    }
+
     pub fn navbar_view(&self) -> Html {
         let cls = if self.show_receiver_list == true {
             "fa-chevron-up"
         } else {
             "fa-chevron-down"
         };
-        let (spot_class, map_class) =
+        let (spot_class, map_class, memories_class) =
             match AppRoute::switch(self.route.clone()) {
-                Some(AppRoute::Index) => ("navbar-item is-active", "navbar-item"),
-                Some(AppRoute::Map) => ("navbar-item", "navbar-item is-active"),
-                None => ("navbar-item is-active","navbar-item"),
+                Some(AppRoute::Index) => ("navbar-item is-active", "navbar-item", "navbar-item"),
+                Some(AppRoute::Map) => ("navbar-item", "navbar-item is-active", "navbar-item"),
+                Some(AppRoute::Memories) => ("navbar-item", "navbar-item", "navbar-item is-active"),
+                Some(AppRoute::Radio(_)) => ("navbar-item", "navbar-item", "navbar-item"),
+                None => ("navbar-item is-active","navbar-item", "navbar-item"),
             };
             
         html! {
@@ -1035,12 +3043,88 @@ impl Model {
                             { "Map" }
                         </a>
 
+                        <a class=memories_class onclick=self.link.callback(|_| Msg::ChangeRoute(AppRoute::Memories))>
+                            { "Memories" }
+                        </a>
+
+                    </div>
+                    <div class="navbar-end">
+                        <div class="navbar-item">
+                            <button class=if self.experimental { "button is-small is-warning" } else { "button is-small" }
+                                title="Send commands SparkSDR does not document (receiver filter, transmit)"
+                                onclick=self.link.callback(|_| Msg::ToggleExperimental)>{ "Experimental" }</button>
+                        </div>
+                        { self.transmit_view() }
                     </div>
                 </div>
             </nav>
         }
     }
 
+    // Push to talk button, doubles as the TX indicator
+    fn transmit_view(&self) -> Html {
+        if !self.experimental {
+            return html! {};
+        }
+        let (class, label) =
+            match (self.transmitter.state(), self.transmitter.remaining()) {
+                (TxState::Transmitting { .. }, Some(remaining)) => ("button is-danger tx-active", format!("TX {}:{:02}", remaining.max(0) / 60, remaining.max(0) % 60)),
+                (TxState::Starting { .. }, _) => ("button is-warning", "TX...".to_string()),
+                _ => ("button", "PTT".to_string()),
+            };
+        html! {
+            <div class="navbar-item">
+                <button class=class title="Hold to transmit from the microphone"
+                    onmousedown=self.link.callback(|_| Msg::PttDown)
+                    onmouseup=self.link.callback(|_| Msg::PttUp)
+                    onmouseleave=self.link.callback(|_| Msg::PttUp)
+                    ontouchstart=self.link.callback(|e: TouchEvent| {
+                        // no emulated mouse events after the touch
+                        e.prevent_default();
+                        Msg::PttDown
+                    })
+                    ontouchend=self.link.callback(|e: TouchEvent| {
+                        e.prevent_default();
+                        Msg::PttUp
+                    })
+                    ontouchcancel=self.link.callback(|_| Msg::PttUp)>
+                    <span class="icon is-small">
+                        <i class="fas fa-microphone"></i>
+                    </span>
+                    <span>{ label }</span>
+                </button>
+            </div>
+        }
+    }
+
+    pub fn transmit_error_view(&self) -> Html {
+        match self.transmitter.error() {
+            Some(err) => html! {
+                <div class="notification is-danger transmit-error">
+                    <button class="delete" onclick=self.link.callback(|_| Msg::ClearTransmitError)></button>
+                    { format!("Transmit: {}", err) }
+                </div>
+            },
+            None => html! {},
+        }
+    }
+
+    pub fn reconnect_view(&self) -> Html {
+        match (self.is_reconnecting(), self.reconnect.delay()) {
+            (true, Some(delay)) => {
+                html! {
+                    <div class="notification is-warning reconnect">
+                        <button class="button is-small" onclick=self.link.callback(|_| Msg::Disconnect)>
+                            { "Cancel" }
+                        </button>
+                        { format!("Connection to {} lost, reconnecting (attempt {}, retry after {:.1}s)", self.ws_location, self.reconnect.attempts(), delay.as_secs_f32()) }
+                    </div>
+                }
+            },
+            _ => html! {},
+        }
+    }
+
     pub fn footer_view(&self) -> Html {
         html! {
             <div class="copy">
@@ -1074,4 +3158,32 @@ impl Model {
             </>
         }
     }
-}
\ No newline at end of file
+}
+
+// SparkSDR radio names start with a 14 character prefix, fall back to
+// the full name when it is shorter than that
+fn radio_display_name(name: &str) -> &str {
+    match name.get(14..) {
+        Some(short_name) if !short_name.trim().is_empty() => short_name,
+        _ => name,
+    }
+}
+
+// Reported radio property for the details table, strings unquoted
+fn property_value(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(value) => value.to_string(),
+        serde_json::Value::Null => "--".to_string(),
+        value => value.to_string(),
+    }
+}
+
+// WAV file of a recording as a blob url for downloading
+fn recording_url(recording: &Recording) -> Result<String, JsValue> {
+    let wav = recording.to_wav();
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(&wav[..]));
+    let mut options = web_sys::BlobPropertyBag::new();
+    options.type_("audio/wav");
+    let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &options)?;
+    web_sys::Url::create_object_url_with_blob(&blob)
+}
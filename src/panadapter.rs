@@ -0,0 +1,211 @@
+use web_sys::{HtmlCanvasElement,CanvasRenderingContext2d};
+use wasm_bindgen::{JsCast,JsValue};
+
+pub const PANADAPTER_HEIGHT: u32 = 100;
+// Spacing of the dB scale grid lines
+const GRID_DB: f32 = 10.0;
+// Peak hold decay per spectrum frame (dB)
+const PEAK_DECAY_DB: f32 = 0.1;
+// Weight of a new frame in the averaged trace
+const AVERAGE_WEIGHT: f32 = 0.2;
+// Percentile of the spectrum used as the noise floor estimate
+const NOISE_FLOOR_PERCENTILE: f32 = 0.2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TraceMode {
+    Latest,
+    PeakHold,
+    Average,
+}
+
+pub const TRACE_MODES: [TraceMode; 3] = [TraceMode::Latest, TraceMode::PeakHold, TraceMode::Average];
+
+impl TraceMode {
+    pub fn new(name: &str) -> TraceMode {
+        match name {
+            "Peak Hold" => TraceMode::PeakHold,
+            "Average" => TraceMode::Average,
+            _ => TraceMode::Latest,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TraceMode::Latest => "Latest",
+            TraceMode::PeakHold => "Peak Hold",
+            TraceMode::Average => "Average",
+        }
+    }
+}
+
+// Latest, peak hold and averaged versions of the incoming spectrum
+pub struct Trace {
+    latest: Vec<f32>,
+    peak: Vec<f32>,
+    average: Vec<f32>,
+}
+
+impl Trace {
+    pub fn new() -> Trace {
+        Trace {
+            latest: Vec::new(),
+            peak: Vec::new(),
+            average: Vec::new(),
+        }
+    }
+
+    pub fn update(&mut self, bins: &[f32]) {
+        if self.latest.len() != bins.len() {
+            self.reset();
+            self.peak = bins.to_vec();
+            self.average = bins.to_vec();
+        }
+        self.latest = bins.to_vec();
+        for (peak, value) in self.peak.iter_mut().zip(bins.iter()) {
+            *peak = (*peak - PEAK_DECAY_DB).max(*value);
+        }
+        for (average, value) in self.average.iter_mut().zip(bins.iter()) {
+            *average += (*value - *average) * AVERAGE_WEIGHT;
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.latest.clear();
+        self.peak.clear();
+        self.average.clear();
+    }
+
+    pub fn values(&self, mode: TraceMode) -> &[f32] {
+        match mode {
+            TraceMode::Latest => &self.latest,
+            TraceMode::PeakHold => &self.peak,
+            TraceMode::Average => &self.average,
+        }
+    }
+}
+
+// Estimate the noise floor as a low percentile of the spectrum
+pub fn noise_floor(bins: &[f32]) -> Option<f32> {
+    let mut sorted: Vec<f32> = bins.iter().cloned().filter(|v| v.is_finite()).collect();
+    if sorted.is_empty() {
+        return None;
+    }
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let index = ((sorted.len() - 1) as f32 * NOISE_FLOOR_PERCENTILE).round() as usize;
+    Some(sorted[index])
+}
+
+// dB range shown on the panadapter: from below the noise floor to
+// above the strongest signal, rounded out to the grid spacing
+pub fn db_range(bins: &[f32], floor: f32) -> (f32, f32) {
+    let max = bins.iter().cloned().filter(|v| v.is_finite()).fold(floor, f32::max);
+    let bottom = ((floor - GRID_DB) / GRID_DB).floor() * GRID_DB;
+    let top = ((max + GRID_DB) / GRID_DB).ceil() * GRID_DB;
+    (bottom, top.max(bottom + GRID_DB))
+}
+
+// Draw a spectrum trace (already resampled to the canvas width) with a
+// dB scale, noise floor marker and the receiver passband (absolute
+// frequencies in Hz) shaded
+pub fn draw(canvas: &HtmlCanvasElement, trace: &[f32], freq_start: f64, freq_stop: f64, passband: Option<(f64, f64)>) {
+    let ctx = canvas.get_context("2d").unwrap().unwrap().dyn_into::<CanvasRenderingContext2d>().unwrap();
+    let width = canvas.width() as f64;
+    let height = canvas.height() as f64;
+
+    ctx.set_fill_style(&JsValue::from_str("#000"));
+    ctx.fill_rect(0.0, 0.0, width, height);
+
+    let floor =
+        match noise_floor(trace) {
+            Some(floor) => floor,
+            None => return,
+        };
+    let (bottom, top) = db_range(trace, floor);
+    let y = |db: f32| height - ((db - bottom) / (top - bottom)) as f64 * height;
+
+    // receiver passband
+    if let Some((low, high)) = passband {
+        if freq_stop > freq_start {
+            let hz_per_pixel = (freq_stop - freq_start) / width;
+            let x1 = (low - freq_start) / hz_per_pixel;
+            let x2 = (high - freq_start) / hz_per_pixel;
+            ctx.set_fill_style(&JsValue::from_str("rgba(255, 255, 255, 0.15)"));
+            ctx.fill_rect(x1, 0.0, (x2 - x1).max(1.0), height);
+        }
+    }
+
+    // dB scale
+    ctx.set_stroke_style(&JsValue::from_str("rgba(255, 255, 255, 0.2)"));
+    ctx.set_fill_style(&JsValue::from_str("rgba(255, 255, 255, 0.6)"));
+    ctx.set_font("10px monospace");
+    ctx.set_line_width(1.0);
+    let mut db = bottom + GRID_DB;
+    while db < top {
+        ctx.begin_path();
+        ctx.move_to(0.0, y(db));
+        ctx.line_to(width, y(db));
+        ctx.stroke();
+        ctx.fill_text(&format!("{} dB", db), 4.0, y(db) - 2.0).unwrap();
+        db += GRID_DB;
+    }
+
+    // spectrum trace
+    ctx.begin_path();
+    ctx.move_to(0.0, height);
+    for (x, value) in trace.iter().enumerate() {
+        ctx.line_to(x as f64, y(*value));
+    }
+    ctx.line_to(trace.len() as f64, height);
+    ctx.close_path();
+    ctx.set_fill_style(&JsValue::from_str("rgba(0, 209, 178, 0.3)"));
+    ctx.fill();
+    ctx.set_stroke_style(&JsValue::from_str("#00d1b2"));
+    ctx.stroke();
+
+    // noise floor
+    let dash = js_sys::Array::of2(&JsValue::from(4.0), &JsValue::from(4.0));
+    ctx.set_line_dash(&dash).unwrap();
+    ctx.set_stroke_style(&JsValue::from_str("rgba(255, 221, 87, 0.8)"));
+    ctx.begin_path();
+    ctx.move_to(0.0, y(floor));
+    ctx.line_to(width, y(floor));
+    ctx.stroke();
+    ctx.set_line_dash(&js_sys::Array::new()).unwrap();
+    ctx.set_fill_style(&JsValue::from_str("rgba(255, 221, 87, 0.8)"));
+    ctx.fill_text(&format!("NF {:.0} dB", floor), width - 80.0, y(floor) - 2.0).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn noise_floor_is_a_low_percentile() {
+        let bins: Vec<f32> = (0..=10).rev().map(|v| v as f32 * -10.0).collect();
+        // 11 values, index (10 * 0.2).round() of the sorted bins
+        assert_eq!(noise_floor(&bins), Some(-80.0));
+        assert_eq!(noise_floor(&[-95.0]), Some(-95.0));
+    }
+
+    #[test]
+    fn noise_floor_ignores_non_finite_bins() {
+        let bins = [f32::NEG_INFINITY, f32::NAN, -100.0, -90.0, -80.0, -70.0, -60.0, f32::INFINITY];
+        assert_eq!(noise_floor(&bins), Some(-90.0));
+        assert_eq!(noise_floor(&[]), None);
+        assert_eq!(noise_floor(&[f32::NAN, f32::NEG_INFINITY]), None);
+    }
+
+    #[test]
+    fn db_range_rounds_out_to_the_grid() {
+        assert_eq!(db_range(&[-121.0, -73.0], -121.0), (-140.0, -60.0));
+        // on a grid line there is still a line of margin
+        assert_eq!(db_range(&[-120.0, -80.0], -120.0), (-130.0, -70.0));
+        assert_eq!(db_range(&[-73.0, f32::INFINITY, f32::NAN], -121.0), (-140.0, -60.0));
+    }
+
+    #[test]
+    fn db_range_of_a_flat_spectrum() {
+        assert_eq!(db_range(&[-100.0, -100.0], -100.0), (-110.0, -90.0));
+        assert_eq!(db_range(&[], -100.0), (-110.0, -90.0));
+    }
+}
@@ -0,0 +1,232 @@
+use std::convert::TryInto;
+
+// Audio frames SparkSDR sends without a container
+pub const RAW_SAMPLE_RATE: f32 = 48000.0;
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+// Decoded audio, one Vec of samples (-1.0..1.0) per channel
+pub struct Pcm {
+    pub sample_rate: f32,
+    pub channels: Vec<Vec<f32>>,
+}
+
+impl Pcm {
+    pub fn frames(&self) -> usize {
+        self.channels.first().map(|c| c.len()).unwrap_or(0)
+    }
+}
+
+// Decode an audio frame payload ourselves when it is PCM: a WAV file
+// with integer or float samples, or, when the connection is known to
+// carry raw PCM (`raw_pcm`), headerless 16 bit little endian mono at
+// RAW_SAMPLE_RATE.  Returns None for anything that needs the browser's
+// decoder (compressed formats, unsupported sample types and payloads we
+// cannot positively identify).
+pub fn decode(data: &[u8], raw_pcm: bool) -> Option<Pcm> {
+    if data.is_empty() {
+        return None;
+    }
+    if data.starts_with(b"RIFF") {
+        return decode_wav(data);
+    }
+    if !raw_pcm || is_compressed(data) || data.len() % 2 != 0 {
+        return None;
+    }
+    Some(Pcm {
+        sample_rate: RAW_SAMPLE_RATE,
+        channels: vec![decode_raw(data)],
+    })
+}
+
+// 16 bit little endian samples
+fn decode_raw(data: &[u8]) -> Vec<f32> {
+    data.chunks_exact(2).map(|s| i16::from_le_bytes([s[0], s[1]]) as f32 / 32768.0).collect()
+}
+
+// Containers/streams decodeAudioData understands
+fn is_compressed(data: &[u8]) -> bool {
+    data.starts_with(b"OggS") || data.starts_with(b"fLaC") || data.starts_with(b"ID3") ||
+        // WebM/Matroska (EBML header), e.g. Opus
+        data.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) ||
+        // MP4/M4A
+        data.get(4..8) == Some(b"ftyp") ||
+        // ADTS AAC
+        (data.len() >= 7 && data[0] == 0xFF && data[1] & 0xF6 == 0xF0) ||
+        // MPEG audio frame sync with a valid bitrate and sample rate index
+        (data.len() >= 4 && data[0] == 0xFF && data[1] & 0xE0 == 0xE0 && data[2] >> 4 != 0xF && (data[2] >> 2) & 0x3 != 0x3)
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2).map(|b| u16::from_le_bytes(b.try_into().unwrap()))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+}
+
+fn decode_wav(data: &[u8]) -> Option<Pcm> {
+    if data.get(8..12)? != b"WAVE" {
+        return None;
+    }
+
+    // (format, channels, sample rate, bits per sample)
+    let mut format: Option<(u16, u16, u32, u16)> = None;
+    let mut offset = 12;
+    while offset + 8 <= data.len() {
+        let id = &data[offset..offset + 4];
+        let size = u32_at(data, offset + 4)? as usize;
+        let body = offset + 8;
+        match id {
+            b"fmt " => {
+                let mut tag = u16_at(data, body)?;
+                if tag == WAVE_FORMAT_EXTENSIBLE {
+                    // first two bytes of the sub format GUID
+                    tag = u16_at(data, body + 24)?;
+                }
+                format = Some((tag, u16_at(data, body + 2)?, u32_at(data, body + 4)?, u16_at(data, body + 14)?));
+            },
+            b"data" => {
+                let (tag, channels, sample_rate, bits) = format?;
+                // streamed WAV headers may not know the data length
+                let end = body.saturating_add(size).min(data.len());
+                return decode_samples(&data[body..end], tag, channels, sample_rate, bits);
+            },
+            _ => (),
+        }
+        // chunks are padded to an even length
+        offset = body.checked_add(size)?.checked_add(size & 1)?;
+    }
+    None
+}
+
+fn decode_samples(data: &[u8], tag: u16, channels: u16, sample_rate: u32, bits: u16) -> Option<Pcm> {
+    if channels == 0 || sample_rate == 0 {
+        return None;
+    }
+    let sample: fn(&[u8]) -> f32 =
+        match (tag, bits) {
+            (WAVE_FORMAT_PCM, 8) => |b| (b[0] as f32 - 128.0) / 128.0,
+            (WAVE_FORMAT_PCM, 16) => |b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0,
+            (WAVE_FORMAT_PCM, 24) => |b| (i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as f32 / 8388608.0,
+            (WAVE_FORMAT_PCM, 32) => |b| i32::from_le_bytes(b.try_into().unwrap()) as f32 / 2147483648.0,
+            (WAVE_FORMAT_IEEE_FLOAT, 32) => |b| f32::from_le_bytes(b.try_into().unwrap()),
+            _ => return None,
+        };
+
+    let width = bits as usize / 8;
+    let channels = channels as usize;
+    let mut output = vec![Vec::with_capacity(data.len() / (width * channels)); channels];
+    for frame in data.chunks_exact(width * channels) {
+        for (channel, bytes) in output.iter_mut().zip(frame.chunks_exact(width)) {
+            channel.push(sample(bytes));
+        }
+    }
+    Some(Pcm {
+        sample_rate: sample_rate as f32,
+        channels: output,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wav(tag: u16, channels: u16, sample_rate: u32, bits: u16, samples: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(b"RIFF");
+        data.extend_from_slice(&(36 + samples.len() as u32).to_le_bytes());
+        data.extend_from_slice(b"WAVEfmt ");
+        data.extend_from_slice(&16u32.to_le_bytes());
+        data.extend_from_slice(&tag.to_le_bytes());
+        data.extend_from_slice(&channels.to_le_bytes());
+        data.extend_from_slice(&sample_rate.to_le_bytes());
+        let block_align = channels * bits / 8;
+        data.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+        data.extend_from_slice(&block_align.to_le_bytes());
+        data.extend_from_slice(&bits.to_le_bytes());
+        data.extend_from_slice(b"data");
+        data.extend_from_slice(&(samples.len() as u32).to_le_bytes());
+        data.extend_from_slice(samples);
+        data
+    }
+
+    #[test]
+    fn raw_samples_are_little_endian() {
+        assert_eq!(decode_raw(&[0x00, 0x00, 0x00, 0x40, 0x00, 0xC0, 0xFF, 0x7F, 0x00, 0x80]),
+            vec![0.0, 0.5, -0.5, 32767.0 / 32768.0, -1.0]);
+    }
+
+    #[test]
+    fn headerless_payloads_need_raw_pcm() {
+        let data = [0x00, 0x40, 0x00, 0xC0];
+        assert!(decode(&data, false).is_none());
+        let pcm = decode(&data, true).unwrap();
+        assert_eq!(pcm.sample_rate, RAW_SAMPLE_RATE);
+        assert_eq!(pcm.channels, vec![vec![0.5, -0.5]]);
+        // a partial sample is not raw PCM
+        assert!(decode(&[0, 0, 0], true).is_none());
+    }
+
+    #[test]
+    fn compressed_payloads_go_to_the_browser() {
+        let payloads: [&[u8]; 7] = [
+            b"OggS\x00\x02\x00\x00",
+            b"fLaC\x00\x00\x00\x22",
+            b"ID3\x04\x00\x00\x00\x00",
+            &[0x1A, 0x45, 0xDF, 0xA3, 0x9F, 0x42, 0x86, 0x81],
+            b"\x00\x00\x00\x20ftypM4A ",
+            &[0xFF, 0xF1, 0x50, 0x80, 0x02, 0x1F, 0xFC, 0x00],
+            &[0xFF, 0xFB, 0x90, 0x64, 0x00, 0x00, 0x00, 0x00],
+        ];
+        for payload in payloads.iter() {
+            assert!(is_compressed(payload), "{:?}", payload);
+            assert!(decode(payload, true).is_none(), "{:?}", payload);
+        }
+        assert!(!is_compressed(&[0x00, 0x40, 0x00, 0xC0]));
+    }
+
+    #[test]
+    fn decodes_wav_without_raw_pcm() {
+        let data = wav(WAVE_FORMAT_PCM, 2, 8000, 16, &[0x00, 0x40, 0x00, 0xC0]);
+        let pcm = decode(&data, false).unwrap();
+        assert_eq!(pcm.sample_rate, 8000.0);
+        assert_eq!(pcm.channels, vec![vec![0.5], vec![-0.5]]);
+        assert_eq!(pcm.frames(), 1);
+    }
+
+    #[test]
+    fn decodes_float_wav() {
+        let mut samples = Vec::new();
+        samples.extend_from_slice(&0.25f32.to_le_bytes());
+        samples.extend_from_slice(&(-1.0f32).to_le_bytes());
+        let pcm = decode(&wav(WAVE_FORMAT_IEEE_FLOAT, 1, 48000, 32, &samples), false).unwrap();
+        assert_eq!(pcm.channels, vec![vec![0.25, -1.0]]);
+    }
+
+    #[test]
+    fn rejects_unsupported_wav() {
+        assert!(decode(&wav(WAVE_FORMAT_IEEE_FLOAT, 1, 48000, 64, &[0; 8]), false).is_none());
+        assert!(decode(b"RIFF\x00\x00\x00\x00AVI ", true).is_none());
+    }
+
+    #[test]
+    fn skips_padded_chunks() {
+        let data = wav(WAVE_FORMAT_PCM, 1, 8000, 16, &[0x00, 0x40]);
+        // odd length chunk and its pad byte ahead of "fmt "
+        let mut padded = data[..12].to_vec();
+        padded.extend_from_slice(b"junk\x03\x00\x00\x00abc\x00");
+        padded.extend_from_slice(&data[12..]);
+        assert_eq!(decode(&padded, false).unwrap().channels, vec![vec![0.5]]);
+    }
+
+    #[test]
+    fn rejects_oversized_chunks() {
+        let mut data = b"RIFF\x00\x00\x00\x00WAVEjunk".to_vec();
+        data.extend_from_slice(&u32::MAX.to_le_bytes());
+        data.extend_from_slice(&[0; 8]);
+        assert!(decode(&data, false).is_none());
+    }
+}
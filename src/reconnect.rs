@@ -0,0 +1,95 @@
+use std::time::Duration;
+use yew::{ComponentLink};
+use yew::services::{ConsoleService};
+use yew::services::timeout::{TimeoutService, TimeoutTask};
+
+use crate::model::{Model,Msg};
+
+// Delay before the first reconnect attempt, doubled on every
+// failed attempt up to RECONNECT_MAX_DELAY_MS
+const RECONNECT_BASE_DELAY_MS: u64 = 500;
+const RECONNECT_MAX_DELAY_MS: u64 = 30000;
+
+// Keeps track of an interrupted SparkSDR connection and schedules
+// reconnect attempts with exponential backoff.  The supervisor is only
+// armed once a connection has been established so a bad address on the
+// connect form still falls back to the disconnected view.
+pub struct ReconnectSupervisor {
+    armed: bool,
+    attempts: u32,
+    delay: Option<Duration>,
+    task: Option<TimeoutTask>,
+}
+
+impl ReconnectSupervisor {
+    pub fn new() -> ReconnectSupervisor {
+        ReconnectSupervisor {
+            armed: false,
+            attempts: 0,
+            delay: None,
+            task: None,
+        }
+    }
+
+    // Called when a connection is opened, returns true if this
+    // connection replaces one that was lost
+    pub fn connected(&mut self) -> bool {
+        let resumed = self.attempts > 0;
+        self.armed = true;
+        self.attempts = 0;
+        self.delay = None;
+        self.task = None;
+        resumed
+    }
+
+    // Stop supervising (user requested disconnect)
+    pub fn disarm(&mut self) {
+        self.armed = false;
+        self.attempts = 0;
+        self.delay = None;
+        self.task = None;
+    }
+
+    pub fn is_armed(&self) -> bool {
+        self.armed
+    }
+
+    pub fn is_reconnecting(&self) -> bool {
+        self.armed && self.attempts > 0
+    }
+
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    pub fn delay(&self) -> Option<Duration> {
+        self.delay
+    }
+
+    // Schedule the next reconnect attempt, returns false if one is
+    // already pending (onerror and onclose both fire for a single failure)
+    pub fn schedule(&mut self, link: &ComponentLink<Model>) -> bool {
+        match self.task {
+            Some(_) => false,
+            None => {
+                let delay = Duration::from_millis(backoff_delay_ms(self.attempts));
+                self.attempts += 1;
+                self.delay = Some(delay);
+                ConsoleService::log(&format!("reconnecting in {}ms (attempt {})", delay.as_millis(), self.attempts));
+                let callback = link.callback(|_| Msg::Reconnect);
+                self.task = Some(TimeoutService::spawn(delay, callback));
+                true
+            }
+        }
+    }
+
+    // The pending timer has fired
+    pub fn fired(&mut self) {
+        self.task = None;
+    }
+}
+
+fn backoff_delay_ms(attempts: u32) -> u64 {
+    let factor = 1u64.checked_shl(attempts.min(16)).unwrap_or(u64::MAX);
+    RECONNECT_BASE_DELAY_MS.saturating_mul(factor).min(RECONNECT_MAX_DELAY_MS)
+}
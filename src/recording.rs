@@ -0,0 +1,251 @@
+use chrono::{DateTime,Utc};
+use web_sys::{AudioBuffer};
+use yew::services::{ConsoleService};
+
+// Longest recording kept in memory (seconds), about 29 MB of 48 kHz
+// mono samples which are copied once more when the WAV file is built
+pub const MAX_RECORDING_SECS: f32 = 300.0;
+
+// Receiver state at the start of a recording, written to the WAV file
+#[derive(Clone, Debug)]
+pub struct RecordingInfo {
+    pub receiver_id: u32,
+    pub frequency: f32,
+    pub mode: String,
+    pub started: DateTime<Utc>,
+}
+
+impl RecordingInfo {
+    pub fn file_name(&self) -> String {
+        format!("{}_{:.0}Hz_{}.wav", self.started.format("%Y%m%dT%H%M%SZ"), self.frequency, self.mode)
+    }
+
+    fn title(&self) -> String {
+        format!("{:.6} MHz {}", self.frequency / 1000000.0, self.mode)
+    }
+
+    fn comment(&self) -> String {
+        format!("receiver {}, {:.0} Hz, {}, started {}", self.receiver_id, self.frequency, self.mode, self.started.format("%Y-%m-%dT%H:%M:%SZ"))
+    }
+}
+
+// Decoded audio of a receiver as 16 bit interleaved samples
+pub struct Recording {
+    info: RecordingInfo,
+    sample_rate: f32,
+    channels: usize,
+    samples: Vec<i16>,
+}
+
+impl Recording {
+    pub fn new(info: RecordingInfo) -> Recording {
+        Recording {
+            info,
+            sample_rate: 0.0,
+            channels: 0,
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn info(&self) -> &RecordingInfo {
+        &self.info
+    }
+
+    pub fn seconds(&self) -> f32 {
+        match self.channels {
+            0 => 0.0,
+            channels => self.samples.len() as f32 / channels as f32 / self.sample_rate,
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.seconds() >= MAX_RECORDING_SECS
+    }
+
+    // Append a played buffer
+    pub fn push(&mut self, buffer: &AudioBuffer) {
+        let channels: Vec<Vec<f32>> = (0..buffer.number_of_channels()).filter_map(|i| buffer.get_channel_data(i).ok()).collect();
+        if channels.len() != buffer.number_of_channels() as usize {
+            return;
+        }
+        self.push_channels(buffer.sample_rate(), &channels);
+    }
+
+    // Append one Vec of samples per channel, the first call sets the format
+    fn push_channels(&mut self, sample_rate: f32, channels: &[Vec<f32>]) {
+        if self.channels == 0 {
+            self.channels = channels.len();
+            self.sample_rate = sample_rate;
+        }
+        if channels.len() != self.channels || sample_rate != self.sample_rate {
+            ConsoleService::error("recording: audio format changed, buffer skipped");
+            return;
+        }
+        if self.is_full() {
+            return;
+        }
+
+        let frames = channels.iter().map(|c| c.len()).max().unwrap_or(0);
+        for frame in 0..frames {
+            for channel in channels.iter() {
+                // same scale pcm::decode uses
+                let sample = channel.get(frame).cloned().unwrap_or(0.0) * 32768.0;
+                self.samples.push(sample.max(-32768.0).min(32767.0) as i16);
+            }
+        }
+    }
+
+    // 16 bit PCM WAV with the receiver info in a LIST/INFO chunk
+    pub fn to_wav(&self) -> Vec<u8> {
+        let channels = self.channels.max(1) as u16;
+        let sample_rate = if self.sample_rate > 0.0 { self.sample_rate as u32 } else { 48000 };
+        let block_align = channels * 2;
+
+        let mut info = b"INFO".to_vec();
+        for (id, value) in [(b"INAM", self.info.title()), (b"ICMT", self.info.comment()), (b"ICRD", self.info.started.format("%Y-%m-%d").to_string())].iter() {
+            let mut value = value.as_bytes().to_vec();
+            value.push(0);
+            info.extend_from_slice(*id);
+            info.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            info.extend_from_slice(&value);
+            // the size excludes the pad byte of an odd length chunk
+            if value.len() % 2 == 1 {
+                info.push(0);
+            }
+        }
+
+        let data_len = (self.samples.len() * 2) as u32;
+        let mut wav = Vec::with_capacity(44 + info.len() + data_len as usize);
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(4 + (8 + 16) + (8 + info.len() as u32) + (8 + data_len)).to_le_bytes());
+        wav.extend_from_slice(b"WAVE");
+
+        wav.extend_from_slice(b"fmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&channels.to_le_bytes());
+        wav.extend_from_slice(&sample_rate.to_le_bytes());
+        wav.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+        wav.extend_from_slice(&block_align.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+
+        wav.extend_from_slice(b"LIST");
+        wav.extend_from_slice(&(info.len() as u32).to_le_bytes());
+        wav.extend_from_slice(&info);
+
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_len.to_le_bytes());
+        for sample in self.samples.iter() {
+            wav.extend_from_slice(&sample.to_le_bytes());
+        }
+        wav
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use crate::pcm;
+
+    fn recording() -> Recording {
+        Recording::new(RecordingInfo {
+            receiver_id: 1,
+            frequency: 14074000.0,
+            mode: "USB".to_string(),
+            started: Utc.ymd(2026, 10, 18).and_hms(12, 0, 0),
+        })
+    }
+
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+    }
+
+    // (id, offset of the body, size) of the chunks from `offset` on
+    fn chunks(data: &[u8], mut offset: usize) -> Vec<(Vec<u8>, usize, usize)> {
+        let mut chunks = Vec::new();
+        while offset + 8 <= data.len() {
+            let size = u32_at(data, offset + 4) as usize;
+            chunks.push((data[offset..offset + 4].to_vec(), offset + 8, size));
+            offset += 8 + size + (size & 1);
+        }
+        assert_eq!(offset, data.len());
+        chunks
+    }
+
+    #[test]
+    fn file_name() {
+        assert_eq!(recording().info().file_name(), "20261018T120000Z_14074000Hz_USB.wav");
+    }
+
+    #[test]
+    fn chunk_sizes() {
+        let mut recording = recording();
+        recording.push_channels(8000.0, &[vec![0.0, 0.5, -0.5]]);
+        let wav = recording.to_wav();
+
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(u32_at(&wav, 4) as usize, wav.len() - 8);
+        assert_eq!(&wav[8..12], b"WAVE");
+        let chunks = chunks(&wav, 12);
+        let ids: Vec<&[u8]> = chunks.iter().map(|(id, _, _)| id.as_slice()).collect();
+        assert_eq!(ids, vec![&b"fmt "[..], &b"LIST"[..], &b"data"[..]]);
+        assert_eq!(chunks[0].2, 16);
+        assert_eq!(chunks[2].2, 6);
+
+        // LIST/INFO sub chunks, values are NUL terminated
+        let (_, list, list_size) = chunks[1];
+        assert_eq!(&wav[list..list + 4], b"INFO");
+        let info = chunks(&wav[..list + list_size], list + 4);
+        let values: Vec<(&[u8], &[u8])> = info.iter().map(|(id, body, size)| (id.as_slice(), &wav[*body..*body + *size])).collect();
+        assert_eq!(values, vec![
+            (&b"INAM"[..], &b"14.074000 MHz USB\0"[..]),
+            (&b"ICMT"[..], &b"receiver 1, 14074000 Hz, USB, started 2026-10-18T12:00:00Z\0"[..]),
+            (&b"ICRD"[..], &b"2026-10-18\0"[..]),
+        ]);
+    }
+
+    #[test]
+    fn odd_info_values_are_padded() {
+        let wav = recording().to_wav();
+        let chunks = chunks(&wav, 12);
+        let (_, list, list_size) = chunks[1];
+        let info = chunks(&wav[..list + list_size], list + 4);
+        // "2026-10-18\0" is 11 bytes, followed by a pad byte
+        let (_, body, size) = info[2];
+        assert_eq!(size, 11);
+        assert_eq!(wav[body + size], 0);
+        assert_eq!(list_size % 2, 0);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut recording = recording();
+        let left = vec![0.0, 0.5, -0.5, -1.0, 32767.0 / 32768.0];
+        let right = vec![0.25, -0.25, 0.0, 0.125, -0.125];
+        recording.push_channels(48000.0, &[left.clone(), right.clone()]);
+        recording.push_channels(48000.0, &[vec![1.5], vec![-1.5]]);
+        assert_eq!(recording.seconds(), 6.0 / 48000.0);
+
+        let pcm = pcm::decode(&recording.to_wav(), false).unwrap();
+        assert_eq!(pcm.sample_rate, 48000.0);
+        let mut left = left;
+        let mut right = right;
+        // clipped
+        left.push(32767.0 / 32768.0);
+        right.push(-1.0);
+        assert_eq!(pcm.channels, vec![left, right]);
+    }
+
+    #[test]
+    fn stops_when_full() {
+        let mut recording = recording();
+        let second = vec![0.0; 100];
+        for _ in 0..MAX_RECORDING_SECS as usize {
+            recording.push_channels(100.0, &[second.clone()]);
+        }
+        assert!(recording.is_full());
+        recording.push_channels(100.0, &[second]);
+        assert_eq!(recording.seconds(), MAX_RECORDING_SECS);
+    }
+}
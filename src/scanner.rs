@@ -0,0 +1,325 @@
+use std::time::Duration;
+use yew::{ComponentLink};
+use yew::format::{Json};
+use yew::services::interval::{IntervalService, IntervalTask};
+use yew::services::storage::{Area, StorageService};
+
+use crate::model::{Model,Msg};
+
+const SETTINGS_KEY: &str = "radio.scanner.settings";
+// Time spent on a quiet channel before stepping to the next one,
+// long enough for a few spectrum frames at the new frequency
+const HOP_MS: u64 = 300;
+
+// Scan range and activity detection, persisted between sessions
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct ScannerSettings {
+    // scan range and step (Hz)
+    pub start: f64,
+    pub stop: f64,
+    pub step: f64,
+    // passband level above the noise floor (dB) that counts as activity
+    pub squelch: f32,
+    // longest time to stay on an active channel (seconds)
+    pub dwell: u64,
+    // time a channel has to be quiet before resuming the scan (seconds)
+    pub resume: u64,
+}
+
+impl ScannerSettings {
+    pub fn new() -> ScannerSettings {
+        ScannerSettings {
+            start: 14000000.0,
+            stop: 14350000.0,
+            step: 5000.0,
+            squelch: 10.0,
+            dwell: 15,
+            resume: 3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScanState {
+    Idle,
+    // moving through the range looking for activity
+    Stepping,
+    // stopped on an active channel (times in ms since the epoch)
+    Dwelling { since: f64, last_active: f64 },
+}
+
+// Steps a receiver across a frequency range, pausing while the signal
+// level in the receiver passband is above the squelch threshold
+pub struct Scanner {
+    // None in tests
+    storage: Option<StorageService>,
+    settings: ScannerSettings,
+    state: ScanState,
+    receiver_id: Option<u32>,
+    frequency: f64,
+    // passband level above the noise floor measured at `frequency`
+    level: Option<f32>,
+    task: Option<IntervalTask>,
+}
+
+impl Scanner {
+    pub fn new() -> Scanner {
+        let storage = StorageService::new(Area::Local).expect("storage was disabled by the user");
+        let settings = {
+            if let Json(Ok(settings)) = storage.restore(SETTINGS_KEY) {
+                settings
+            } else {
+                ScannerSettings::new()
+            }
+        };
+
+        Scanner::with_settings(Some(storage), settings)
+    }
+
+    fn with_settings(storage: Option<StorageService>, settings: ScannerSettings) -> Scanner {
+        Scanner {
+            storage,
+            settings,
+            state: ScanState::Idle,
+            receiver_id: None,
+            frequency: 0.0,
+            level: None,
+            task: None,
+        }
+    }
+
+    pub fn settings(&self) -> &ScannerSettings {
+        &self.settings
+    }
+
+    // Returns false if the range is empty
+    pub fn set_range(&mut self, start: f64, stop: f64) -> bool {
+        if stop <= start {
+            return false;
+        }
+        self.settings.start = start;
+        self.settings.stop = stop;
+        self.store();
+        true
+    }
+
+    pub fn set_step(&mut self, step: f64) {
+        self.settings.step = step.max(1.0);
+        self.store();
+    }
+
+    pub fn set_squelch(&mut self, squelch: f32) {
+        self.settings.squelch = squelch.max(0.0);
+        self.store();
+    }
+
+    pub fn set_dwell(&mut self, seconds: u64) {
+        self.settings.dwell = seconds.max(1);
+        self.store();
+    }
+
+    pub fn set_resume(&mut self, seconds: u64) {
+        self.settings.resume = seconds;
+        self.store();
+    }
+
+    fn store(&mut self) {
+        if let Some(storage) = &mut self.storage {
+            storage.store(SETTINGS_KEY, Json(&self.settings));
+        }
+    }
+
+    pub fn state(&self) -> ScanState {
+        self.state
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.state != ScanState::Idle
+    }
+
+    pub fn receiver_id(&self) -> Option<u32> {
+        self.receiver_id
+    }
+
+    pub fn frequency(&self) -> f64 {
+        self.frequency
+    }
+
+    pub fn level(&self) -> Option<f32> {
+        self.level
+    }
+
+    // Start scanning a receiver from its current frequency (or the start
+    // of the range if outside of it), returns the frequency to tune to
+    pub fn start(&mut self, link: &ComponentLink<Model>, receiver_id: u32, frequency: f64) -> f64 {
+        let frequency = self.begin(receiver_id, frequency);
+        let callback = link.callback(|_| Msg::ScannerTick);
+        self.task = Some(IntervalService::spawn(Duration::from_millis(HOP_MS), callback));
+        frequency
+    }
+
+    fn begin(&mut self, receiver_id: u32, frequency: f64) -> f64 {
+        let settings = &self.settings;
+        self.frequency =
+            if frequency >= settings.start && frequency <= settings.stop {
+                settings.start + ((frequency - settings.start) / settings.step).round() * settings.step
+            } else {
+                settings.start
+            };
+        self.receiver_id = Some(receiver_id);
+        self.state = ScanState::Stepping;
+        self.level = None;
+        self.frequency
+    }
+
+    pub fn stop(&mut self) {
+        self.state = ScanState::Idle;
+        self.receiver_id = None;
+        self.level = None;
+        self.task = None;
+    }
+
+    // Passband level (dB above the noise floor) from a spectrum frame
+    pub fn measure(&mut self, level: f32) {
+        if self.is_running() {
+            self.level = Some(level);
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.level.map(|level| level >= self.settings.squelch).unwrap_or(false)
+    }
+
+    // Called every HOP_MS with the current time (ms), returns the next
+    // frequency if the scan moves on
+    pub fn tick(&mut self, now: f64) -> Option<f64> {
+        let active = self.is_active();
+        match self.state {
+            ScanState::Idle => None,
+            ScanState::Stepping if active => {
+                self.state = ScanState::Dwelling { since: now, last_active: now };
+                None
+            },
+            ScanState::Stepping => Some(self.next()),
+            ScanState::Dwelling { since, last_active } => {
+                let last_active = if active { now } else { last_active };
+                if now - since >= (self.settings.dwell * 1000) as f64 ||
+                    now - last_active >= (self.settings.resume * 1000) as f64 {
+                    Some(self.next())
+                } else {
+                    self.state = ScanState::Dwelling { since, last_active };
+                    None
+                }
+            },
+        }
+    }
+
+    // Skip the current channel (e.g. while dwelling)
+    pub fn skip(&mut self) -> Option<f64> {
+        match self.state {
+            ScanState::Idle => None,
+            _ => Some(self.next()),
+        }
+    }
+
+    fn next(&mut self) -> f64 {
+        self.frequency += self.settings.step;
+        if self.frequency > self.settings.stop {
+            self.frequency = self.settings.start;
+        }
+        self.level = None;
+        self.state = ScanState::Stepping;
+        self.frequency
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 14.000 - 14.015 MHz in 5 kHz steps, dwell 10 s, resume after 2 s
+    fn scanner() -> Scanner {
+        let settings = ScannerSettings {
+            start: 14000000.0,
+            stop: 14015000.0,
+            step: 5000.0,
+            squelch: 10.0,
+            dwell: 10,
+            resume: 2,
+        };
+        let mut scanner = Scanner::with_settings(None, settings);
+        scanner.begin(1, 14000000.0);
+        scanner
+    }
+
+    #[test]
+    fn starts_on_the_grid() {
+        let mut scanner = scanner();
+        assert_eq!(scanner.begin(1, 14006000.0), 14005000.0);
+        assert_eq!(scanner.begin(1, 7074000.0), 14000000.0);
+        assert_eq!(scanner.state(), ScanState::Stepping);
+        assert_eq!(scanner.receiver_id(), Some(1));
+    }
+
+    #[test]
+    fn steps_while_quiet_and_wraps() {
+        let mut scanner = scanner();
+        scanner.measure(3.0);
+        assert_eq!(scanner.tick(0.0), Some(14005000.0));
+        assert_eq!(scanner.level(), None);
+        // no measurement at the new frequency yet is quiet
+        assert_eq!(scanner.tick(300.0), Some(14010000.0));
+        assert_eq!(scanner.tick(600.0), Some(14015000.0));
+        assert_eq!(scanner.tick(900.0), Some(14000000.0));
+    }
+
+    #[test]
+    fn squelch_threshold() {
+        let mut scanner = scanner();
+        scanner.measure(9.9);
+        assert_eq!(scanner.tick(0.0), Some(14005000.0));
+        scanner.measure(10.0);
+        assert_eq!(scanner.tick(300.0), None);
+        assert_eq!(scanner.state(), ScanState::Dwelling { since: 300.0, last_active: 300.0 });
+    }
+
+    #[test]
+    fn resumes_after_quiet_period() {
+        let mut scanner = scanner();
+        scanner.measure(20.0);
+        assert_eq!(scanner.tick(0.0), None);
+        assert_eq!(scanner.tick(1000.0), None);
+        scanner.measure(0.0);
+        assert_eq!(scanner.tick(2000.0), None);
+        assert_eq!(scanner.state(), ScanState::Dwelling { since: 0.0, last_active: 1000.0 });
+        assert_eq!(scanner.tick(2999.0), None);
+        assert_eq!(scanner.tick(3000.0), Some(14005000.0));
+        assert_eq!(scanner.state(), ScanState::Stepping);
+    }
+
+    #[test]
+    fn leaves_after_the_dwell_time() {
+        let mut scanner = scanner();
+        scanner.measure(20.0);
+        assert_eq!(scanner.tick(0.0), None);
+        for now in (1..10).map(|s| s as f64 * 1000.0) {
+            assert_eq!(scanner.tick(now), None);
+        }
+        assert_eq!(scanner.tick(10000.0), Some(14005000.0));
+    }
+
+    #[test]
+    fn idle_and_skip() {
+        let mut scanner = scanner();
+        scanner.measure(20.0);
+        scanner.tick(0.0);
+        assert_eq!(scanner.skip(), Some(14005000.0));
+        scanner.stop();
+        // measurements are ignored while stopped
+        scanner.measure(20.0);
+        assert_eq!(scanner.level(), None);
+        assert_eq!(scanner.tick(1000.0), None);
+        assert_eq!(scanner.skip(), None);
+        assert!(!scanner.is_running());
+    }
+}
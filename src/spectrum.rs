@@ -1,39 +1,94 @@
+use std::collections::{BTreeMap,BTreeSet};
 use web_sys::{HtmlCanvasElement};
 use yew::prelude::*;
 use yew::services::{ConsoleService};
+use yew::format::{Json};
+use yew::services::storage::{Area, StorageService};
 use wasm_bindgen::{JsCast,Clamped};
 use web_sys::{ImageData};
 
-use crate::color::{ColourGradient};
+use crate::color::{ColourGradient,Palette};
+use crate::panadapter::{self,Trace,TraceMode};
 
-pub struct SpectrumProvider {
+const SETTINGS_KEY: &str = "radio.spectrum.settings";
+
+pub const WATERFALL_HEIGHT: u32 = 200;
+// Canvas width used until the first spectrum frame arrives
+const DEFAULT_WATERFALL_WIDTH: u32 = 2048;
+// Limits of the canvas width, spectrum frames are resampled to the
+// displayed width of the canvas
+const MIN_WATERFALL_WIDTH: u32 = 512;
+const MAX_WATERFALL_WIDTH: u32 = 4096;
+// In automatic mode the bottom of the colour range sits this far
+// below the tracked noise floor (dB)
+const AUTO_FLOOR_MARGIN: f32 = 10.0;
+// Weight of a new noise floor measurement in the tracked noise floor
+const AUTO_FLOOR_WEIGHT: f32 = 0.1;
+pub const MIN_WATERFALL_RANGE: f32 = 10.0;
+
+// Waterfall colour mapping, values between `reference - range` and
+// `reference` (dB) are spread over the colour gradient.  The defaults
+// match the original fixed `+ 180.0` offset.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct SpectrumSettings {
+    pub reference: f32,
+    pub range: f32,
+    // track the noise floor instead of using `reference`
+    pub auto: bool,
+    #[serde(default)]
+    pub palette: Palette,
+}
+
+impl SpectrumSettings {
+    pub fn new() -> SpectrumSettings {
+        SpectrumSettings {
+            reference: 75.0,
+            range: 255.0,
+            auto: false,
+            palette: Palette::Classic,
+        }
+    }
+}
+
+// Waterfall display for a single receiver's spectrum subscription
+pub struct Waterfall {
     pub canvas_node_ref: NodeRef,
     pub tmp_canvas_node_ref: NodeRef,
+    pub panadapter_node_ref: NodeRef,
     pub canvas: Option<HtmlCanvasElement>,
     pub tmp_canvas: Option<HtmlCanvasElement>,
-    subscribed_spectrum: Option<u32>,
+    pub panadapter: Option<HtmlCanvasElement>,
     freq_start: f64,
     freq_stop: f64,
-    spectrum_buffer: Vec<[f32;2048]>,
-    gradient: ColourGradient,
+    // number of bins in the spectrum frames we are receiving
+    bins: usize,
+    // canvas width in pixels
+    width: u32,
+    spectrum_buffer: Vec<Vec<f32>>,
+    // panadapter trace and receiver passband (Hz)
+    trace: Trace,
+    passband: Option<(f64, f64)>,
+    // noise floor tracked for automatic levels
+    auto_floor: Option<f32>,
 }
 
-impl SpectrumProvider {
-    pub fn new() -> SpectrumProvider {
-        let mut gradient = ColourGradient::new();
-        gradient.set_min(0.0);
-        gradient.set_max(255.0);
-
-        SpectrumProvider {
+impl Waterfall {
+    pub fn new() -> Waterfall {
+        Waterfall {
             canvas_node_ref: NodeRef::default(),
             tmp_canvas_node_ref: NodeRef::default(),
+            panadapter_node_ref: NodeRef::default(),
             canvas: None,
             tmp_canvas: None,
-            subscribed_spectrum: None,
+            panadapter: None,
             freq_start: 0.0,
             freq_stop: 0.0,
+            bins: 0,
+            width: DEFAULT_WATERFALL_WIDTH,
             spectrum_buffer: Vec::new(),
-            gradient: gradient,
+            trace: Trace::new(),
+            passband: None,
+            auto_floor: None,
         }
     }
 
@@ -45,74 +100,505 @@ impl SpectrumProvider {
         self.freq_stop
     }
 
-    pub fn receiving_spectrum(&self) -> Option<u32> {
-        self.subscribed_spectrum
+    // Bottom of the colour range (dB) for this waterfall
+    pub fn floor(&self, settings: &SpectrumSettings) -> f32 {
+        match (settings.auto, self.auto_floor) {
+            (true, Some(floor)) => floor - AUTO_FLOOR_MARGIN,
+            _ => settings.reference - settings.range,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn bins(&self) -> usize {
+        self.bins
+    }
+
+    // Adjust to the bin count of incoming frames and the displayed
+    // width of the canvas, returns true if the canvas width changed
+    fn set_bins(&mut self, bins: usize) -> bool {
+        if self.bins != bins {
+            self.bins = bins;
+            self.spectrum_buffer.clear();
+        }
+
+        let displayed = self.canvas.as_ref().map(|canvas| canvas.client_width()).unwrap_or(0);
+        let width = canvas_width(displayed, bins);
+        if self.width == width {
+            return false;
+        }
+        self.width = width;
+        // resizing clears the canvas
+        for canvas in self.canvas.iter().chain(self.tmp_canvas.iter()).chain(self.panadapter.iter()) {
+            canvas.set_width(width);
+        }
+        true
+    }
+
+    // look up our canvas elements after the view has been rendered
+    pub fn bind_canvas(&mut self) {
+        self.canvas = self.canvas_node_ref.cast::<HtmlCanvasElement>();
+        self.tmp_canvas = self.tmp_canvas_node_ref.cast::<HtmlCanvasElement>();
+        self.panadapter = self.panadapter_node_ref.cast::<HtmlCanvasElement>();
+    }
+}
+
+// Spectrum subscriptions keyed by receiver id.  The primary receiver
+// (the default receiver) always has a waterfall, additional receivers
+// can be pinned to show their waterfalls below it.
+pub struct SpectrumProvider {
+    primary: Option<u32>,
+    pinned: BTreeSet<u32>,
+    waterfalls: BTreeMap<u32, Waterfall>,
+    storage: StorageService,
+    settings: SpectrumSettings,
+    gradient: ColourGradient,
+    trace_mode: TraceMode,
+}
+
+impl SpectrumProvider {
+    pub fn new() -> SpectrumProvider {
+        let storage = StorageService::new(Area::Local).expect("storage was disabled by the user");
+        let settings = {
+            if let Json(Ok(settings)) = storage.restore(SETTINGS_KEY) {
+                settings
+            } else {
+                SpectrumSettings::new()
+            }
+        };
+        let gradient = waterfall_gradient(settings.palette);
+
+        SpectrumProvider {
+            primary: None,
+            pinned: BTreeSet::new(),
+            waterfalls: BTreeMap::new(),
+            storage,
+            settings,
+            gradient: gradient,
+            trace_mode: TraceMode::Latest,
+        }
+    }
+
+    pub fn settings(&self) -> &SpectrumSettings {
+        &self.settings
+    }
+
+    // Reference level currently in use by the primary waterfall
+    // (follows the noise floor in automatic mode)
+    pub fn reference_level(&self) -> f32 {
+        match self.primary.and_then(|id| self.waterfall(id)) {
+            Some(waterfall) => waterfall.floor(&self.settings) + self.settings.range,
+            None => self.settings.reference,
+        }
+    }
+
+    pub fn set_reference_level(&mut self, reference: f32) {
+        self.settings.reference = reference;
+        self.store_settings();
+    }
+
+    pub fn set_range(&mut self, range: f32) {
+        self.settings.range = range.max(MIN_WATERFALL_RANGE);
+        self.store_settings();
+    }
+
+    pub fn toggle_auto_levels(&mut self) {
+        // keep the levels we were showing when leaving automatic mode
+        if self.settings.auto {
+            self.settings.reference = self.reference_level();
+        }
+        self.settings.auto = !self.settings.auto;
+        self.store_settings();
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.settings.palette = palette;
+        self.gradient = waterfall_gradient(palette);
+        self.store_settings();
+    }
+
+    fn store_settings(&mut self) {
+        self.storage.store(SETTINGS_KEY, Json(&self.settings));
+    }
+
+    pub fn trace_mode(&self) -> TraceMode {
+        self.trace_mode
+    }
+
+    pub fn set_trace_mode(&mut self, mode: TraceMode) {
+        self.trace_mode = mode;
+    }
+
+    // Receiver passband (absolute frequencies in Hz) shaded on the panadapter
+    pub fn set_passband(&mut self, receiver_id: u32, passband: Option<(f64, f64)>) {
+        if let Some(waterfall) = self.waterfalls.get_mut(&receiver_id) {
+            waterfall.passband = passband;
+        }
+    }
+
+    pub fn primary(&self) -> Option<u32> {
+        self.primary
     }
 
-    pub fn set_subscribed(&mut self, receiver: Option<u32>) {
-        self.subscribed_spectrum = receiver;
+    pub fn waterfall(&self, receiver_id: u32) -> Option<&Waterfall> {
+        self.waterfalls.get(&receiver_id)
     }
 
-    pub fn import_spectrum_data(&mut self, data: js_sys::ArrayBuffer, start: f64, stop: f64) {
-        let data = js_sys::Float32Array::new(&data.slice(1+4+8+8));
-        let mut tmp = [0.0; 2048];
-        data.copy_to(&mut tmp);
-        self.spectrum_buffer.push(tmp);
+    pub fn freq_start(&self) -> f64 {
+        self.primary.and_then(|id| self.waterfall(id)).map(|w| w.freq_start()).unwrap_or(0.0)
+    }
 
-        match (self.spectrum_buffer.len(), &self.canvas, &self.tmp_canvas) {
+    pub fn freq_stop(&self) -> f64 {
+        self.primary.and_then(|id| self.waterfall(id)).map(|w| w.freq_stop()).unwrap_or(0.0)
+    }
+
+    pub fn receiving_spectrum(&self, receiver_id: u32) -> bool {
+        self.waterfalls.contains_key(&receiver_id)
+    }
+
+    pub fn is_pinned(&self, receiver_id: u32) -> bool {
+        self.pinned.contains(&receiver_id)
+    }
+
+    // Subscribed receivers, primary first followed by pinned receivers
+    pub fn subscribed(&self) -> Vec<u32> {
+        let mut subscribed: Vec<u32> = self.primary.into_iter().collect();
+        subscribed.extend(self.pinned.iter().filter(|id| Some(**id) != self.primary));
+        subscribed
+    }
+
+    // Change the primary receiver, returns the previous primary receiver
+    // if it no longer needs a spectrum subscription
+    pub fn set_primary(&mut self, receiver: Option<u32>) -> Option<u32> {
+        let previous = self.primary;
+        self.primary = receiver;
+        if let Some(receiver_id) = receiver {
+            self.waterfalls.entry(receiver_id).or_insert_with(Waterfall::new);
+        }
+        // the receiver marker follows the primary waterfall
+        if previous != receiver {
+            match (self.freq_start(), self.freq_stop()) {
+                (start, stop) if stop > start => update_waterfall_nav(start, stop),
+                _ => js_sys::eval("frequencyStart = null;frequencyStop = null;updateWaterfallNav();").map(|_| ()).unwrap(),
+            }
+        }
+        match previous {
+            Some(previous_id) if Some(previous_id) != receiver && !self.is_pinned(previous_id) => {
+                self.waterfalls.remove(&previous_id);
+                Some(previous_id)
+            },
+            _ => None,
+        }
+    }
+
+    // Pin/unpin an additional waterfall, returns true if the receiver
+    // needs to be subscribed and false if it should be unsubscribed
+    pub fn toggle_pinned(&mut self, receiver_id: u32) -> bool {
+        if self.pinned.remove(&receiver_id) {
+            if Some(receiver_id) != self.primary {
+                self.waterfalls.remove(&receiver_id);
+            }
+            false
+        } else {
+            self.pinned.insert(receiver_id);
+            self.waterfalls.entry(receiver_id).or_insert_with(Waterfall::new);
+            true
+        }
+    }
+
+    // Drop a receiver that no longer exists
+    pub fn remove(&mut self, receiver_id: u32) {
+        self.pinned.remove(&receiver_id);
+        self.waterfalls.remove(&receiver_id);
+        if self.primary == Some(receiver_id) {
+            self.primary = None;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.primary = None;
+        self.pinned.clear();
+        self.waterfalls.clear();
+    }
+
+    pub fn bind_canvases(&mut self) {
+        for waterfall in self.waterfalls.values_mut() {
+            waterfall.bind_canvas();
+        }
+    }
+
+    // Returns true if the waterfall needs to be re-rendered (canvas width
+    // or frequency span changed)
+    pub fn import_spectrum_data(&mut self, receiver_id: u32, bins: &[f32], start: f64, stop: f64) -> bool {
+        let is_primary = self.primary == Some(receiver_id);
+        let gradient = &self.gradient;
+        let settings = &self.settings;
+        let trace_mode = self.trace_mode;
+        let waterfall =
+            match self.waterfalls.get_mut(&receiver_id) {
+                Some(waterfall) if !bins.is_empty() => waterfall,
+                _ => return false,
+            };
+
+        let mut changed = waterfall.set_bins(bins.len());
+        waterfall.spectrum_buffer.push(bins.to_vec());
+        waterfall.trace.update(bins);
+
+        match (waterfall.spectrum_buffer.len(), &waterfall.canvas, &waterfall.tmp_canvas) {
             (buffer_len, Some(canvas), Some(tmp_canvas)) if buffer_len >= 10 => {
                 // TODO: move this somewhere
-                if self.freq_start != start || self.freq_stop != stop {
-                    let js = format!("frequencyStart = {};frequencyStop = {};updateWaterfallNav();", start, stop);
-                    ConsoleService::log(&format!("js: {}", js));
-                    js_sys::eval(&js).unwrap();
-                    self.freq_stop = stop;
-                    self.freq_start = start;
+                if waterfall.freq_start != start || waterfall.freq_stop != stop {
+                    if is_primary {
+                        update_waterfall_nav(start, stop);
+                    }
+                    waterfall.freq_stop = stop;
+                    waterfall.freq_start = start;
+                    changed = true;
+                    waterfall.trace.reset();
+                    waterfall.trace.update(bins);
                 }
 
+                let width = waterfall.width as f64;
+                let height = WATERFALL_HEIGHT as f64;
+
                 // canvas ctx
                 let ctx = canvas.get_context("2d").unwrap().unwrap().dyn_into::<web_sys::CanvasRenderingContext2d>().unwrap();
                 let tmp_ctx = tmp_canvas.get_context("2d").unwrap().unwrap().dyn_into::<web_sys::CanvasRenderingContext2d>().unwrap();
 
                 // make copy of current canvas
-                tmp_ctx.draw_image_with_html_canvas_element_and_dw_and_dh(&canvas, 0.0, 0.0, 2048.0, 200.0).unwrap();
+                tmp_ctx.draw_image_with_html_canvas_element_and_dw_and_dh(&canvas, 0.0, 0.0, width, height).unwrap();
+
+                // peak value of each bin over our buffer array
+                let mut peak = vec![f32::MIN; waterfall.bins];
+                for buffer in waterfall.spectrum_buffer.iter() {
+                    for (p, value) in peak.iter_mut().zip(buffer.iter()) {
+                        *p = p.max(*value);
+                    }
+                }
+                let pixels = resample(&peak, waterfall.width as usize);
 
-                let mut line = vec![0; 8192];
-                let mut iter = line.chunks_exact_mut(4);
-                for i in 0..2047 {
-                    // average pixel value over our buffer array
-                    let mut max = self.spectrum_buffer.iter().max_by_key(|b| b[i] as u32 ).unwrap()[i] + 180.0;
-                    if max > 255.0 { max = 255.0; }
-                    if max < 0.0 { max = 0.0; }
-                    let color = self.gradient.get_colour(max);
+                waterfall.auto_floor = track_noise_floor(waterfall.auto_floor, &peak);
+                let floor = waterfall.floor(settings);
+
+                let mut line = vec![0; pixels.len() * 4];
+                for (pixel, value) in line.chunks_exact_mut(4).zip(pixels.iter()) {
+                    let color = gradient.get_colour(colour_level(*value, floor, settings.range));
 
                     // Color to ImageData pixel
-                    for pixel in iter.next() {
-                        if let [r,g,b,a] = pixel {
-                            *a = u8::max_value();
-                            *r = color.r;
-                            *g = color.g;
-                            *b = color.b;
-                        }
-                    };
+                    if let [r,g,b,a] = pixel {
+                        *a = u8::max_value();
+                        *r = color.r;
+                        *g = color.g;
+                        *b = color.b;
+                    }
                 }
 
                 // add our new line to the canvas
-                let line = ImageData::new_with_u8_clamped_array(Clamped(&mut line), 2048 ).unwrap();
+                let line = ImageData::new_with_u8_clamped_array(Clamped(&mut line), waterfall.width).unwrap();
                 ctx.put_image_data(&line, 0.0, 0.0).unwrap();
 
                 // waterfall scroll
                 ctx.translate(0 as f64,1 as f64).unwrap();
-                ctx.draw_image_with_html_canvas_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(&tmp_canvas, 0.0, 0.0, 2048.0, 200.0, 0.0, 0.0, 2048.0, 200.0).unwrap();
+                ctx.draw_image_with_html_canvas_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(&tmp_canvas, 0.0, 0.0, width, height, 0.0, 0.0, width, height).unwrap();
                 ctx.set_transform(1.0, 0.0, 0.0, 1.0, 0.0, 0.0).unwrap();
 
-                self.spectrum_buffer = Vec::new();
+                waterfall.spectrum_buffer = Vec::new();
+
+                // panadapter trace
+                if let Some(panadapter) = &waterfall.panadapter {
+                    let trace = resample(waterfall.trace.values(trace_mode), waterfall.width as usize);
+                    panadapter::draw(panadapter, &trace, start, stop, waterfall.passband);
+                }
             },
             (_, None, _) |
             (_, _, None) => {
-                ConsoleService::error("unable to find canvas");
+                // pinned waterfalls are not rendered on every route
+                if is_primary {
+                    ConsoleService::error("unable to find canvas");
+                }
+                waterfall.spectrum_buffer.clear();
             },
             _ => ()
         }
+        changed
+    }
+}
+
+// Gradient for the 0..255 output of `colour_level`
+fn waterfall_gradient(palette: Palette) -> ColourGradient {
+    let mut gradient = ColourGradient::from_palette(palette);
+    gradient.set_min(0.0);
+    gradient.set_max(255.0);
+    gradient
+}
+
+// Smooth the noise floor over recent frames
+fn track_noise_floor(current: Option<f32>, bins: &[f32]) -> Option<f32> {
+    match (current, panadapter::noise_floor(bins)) {
+        (Some(current), Some(floor)) => Some(current + (floor - current) * AUTO_FLOOR_WEIGHT),
+        (None, floor) => floor,
+        (current, None) => current,
     }
-}
\ No newline at end of file
+}
+
+// Map a spectrum value (dB) to the 0..255 gradient input
+pub fn colour_level(value: f32, floor: f32, range: f32) -> f32 {
+    let mut level = (value - floor) / range * 255.0;
+    if level > 255.0 { level = 255.0; }
+    if level < 0.0 || level.is_nan() { level = 0.0; }
+    level
+}
+
+// Canvas width (pixels) for a canvas shown `displayed` CSS pixels wide,
+// the bin count is only used before the canvas has been laid out
+fn canvas_width(displayed: i32, bins: usize) -> u32 {
+    let width = if displayed > 0 { displayed as u32 } else { bins as u32 };
+    width.max(MIN_WATERFALL_WIDTH).min(MAX_WATERFALL_WIDTH)
+}
+
+// Resample spectrum bins to `width` pixels.  When there are more bins
+// than pixels each pixel shows the strongest bin it covers so narrow
+// signals are not lost, otherwise bins are linearly interpolated.
+pub fn resample(bins: &[f32], width: usize) -> Vec<f32> {
+    match (bins.len(), width) {
+        (_, 0) | (0, _) => Vec::new(),
+        (len, width) if len == width => bins.to_vec(),
+        (len, width) if len > width => {
+            (0..width).map(|x| {
+                let from = x * len / width;
+                let to = ((x + 1) * len / width).max(from + 1);
+                bins[from..to].iter().cloned().fold(f32::MIN, f32::max)
+            }).collect()
+        },
+        (1, width) => vec![bins[0]; width],
+        (len, width) => {
+            let scale = (len - 1) as f32 / (width - 1) as f32;
+            (0..width).map(|x| {
+                let pos = x as f32 * scale;
+                let i = (pos.floor() as usize).min(len - 2);
+                let frac = pos - i as f32;
+                bins[i] + (bins[i + 1] - bins[i]) * frac
+            }).collect()
+        },
+    }
+}
+
+// Power of the bins covering a passband (dB)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PassbandPower {
+    // everything in the passband, what an S-meter reads
+    pub total: f32,
+    // per bin, comparable with the noise floor of a bin
+    pub mean: f32,
+    pub bins: usize,
+}
+
+// Power of the bins covering `low`..`high` (Hz), None if that range is
+// not inside the frame.  Summed in linear power, not dB.
+pub fn passband_power(bins: &[f32], freq_start: f64, freq_stop: f64, low: f64, high: f64) -> Option<PassbandPower> {
+    if bins.is_empty() || freq_stop <= freq_start || low < freq_start || high > freq_stop || high < low {
+        return None;
+    }
+    let hz_per_bin = (freq_stop - freq_start) / bins.len() as f64;
+    let first = (((low - freq_start) / hz_per_bin).floor() as usize).min(bins.len() - 1);
+    let last = (((high - freq_start) / hz_per_bin).ceil() as usize).max(first + 1).min(bins.len());
+    let passband = &bins[first..last];
+    let total = passband.iter().map(|db| 10f32.powf(db / 10.0)).sum::<f32>();
+    Some(PassbandPower {
+        total: 10.0 * total.log10(),
+        mean: 10.0 * (total / passband.len() as f32).log10(),
+        bins: passband.len(),
+    })
+}
+
+// Frequency range of the primary waterfall used to position the
+// receiver marker (static/js/app.js)
+fn update_waterfall_nav(start: f64, stop: f64) {
+    let js = format!("frequencyStart = {};frequencyStop = {};updateWaterfallNav();", start, stop);
+    ConsoleService::log(&format!("js: {}", js));
+    js_sys::eval(&js).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 1 kHz wide bins from 14.0 MHz, a -73 dBm carrier at 14.005 MHz
+    fn carrier_bins() -> Vec<f32> {
+        let mut bins = vec![-130.0; 20];
+        bins[5] = -73.0;
+        bins
+    }
+
+    #[test]
+    fn total_power_does_not_depend_on_the_passband_width() {
+        let bins = carrier_bins();
+        let narrow = passband_power(&bins, 14000000.0, 14020000.0, 14005000.0, 14006000.0).unwrap();
+        let wide = passband_power(&bins, 14000000.0, 14020000.0, 14003000.0, 14009000.0).unwrap();
+        assert_eq!(narrow.bins, 1);
+        assert_eq!(wide.bins, 6);
+        assert!((narrow.total - -73.0).abs() < 0.01);
+        assert!((wide.total - -73.0).abs() < 0.01);
+        // the mean is spread over the bins
+        assert!((wide.mean - (-73.0 - 10.0 * 6f32.log10())).abs() < 0.01);
+    }
+
+    #[test]
+    fn colour_level_spans_floor_to_floor_plus_range() {
+        assert_eq!(colour_level(-130.0, -130.0, 60.0), 0.0);
+        assert_eq!(colour_level(-100.0, -130.0, 60.0), 127.5);
+        assert_eq!(colour_level(-70.0, -130.0, 60.0), 255.0);
+    }
+
+    #[test]
+    fn colour_level_is_clamped() {
+        assert_eq!(colour_level(-150.0, -130.0, 60.0), 0.0);
+        assert_eq!(colour_level(0.0, -130.0, 60.0), 255.0);
+        assert_eq!(colour_level(f32::NAN, -130.0, 60.0), 0.0);
+        assert_eq!(colour_level(f32::INFINITY, -130.0, 60.0), 255.0);
+        assert_eq!(colour_level(f32::NEG_INFINITY, -130.0, 60.0), 0.0);
+    }
+
+    #[test]
+    fn resample_empty() {
+        assert_eq!(resample(&[], 10), Vec::<f32>::new());
+        assert_eq!(resample(&[1.0, 2.0], 0), Vec::<f32>::new());
+        assert_eq!(resample(&[1.0, 2.0], 2), vec![1.0, 2.0]);
+    }
+
+    #[test]
+    fn resample_down_keeps_peaks() {
+        let bins = [-120.0, -60.0, -120.0, -120.0, -120.0, -120.0, -120.0, -80.0];
+        assert_eq!(resample(&bins, 4), vec![-60.0, -120.0, -120.0, -80.0]);
+        // uneven ratios still cover every bin
+        assert_eq!(resample(&bins, 3), vec![-60.0, -120.0, -80.0]);
+        assert_eq!(resample(&bins, 1), vec![-60.0]);
+    }
+
+    #[test]
+    fn resample_up_interpolates() {
+        assert_eq!(resample(&[0.0, 10.0], 5), vec![0.0, 2.5, 5.0, 7.5, 10.0]);
+        assert_eq!(resample(&[0.0, 10.0, 0.0], 5), vec![0.0, 5.0, 10.0, 5.0, 0.0]);
+        assert_eq!(resample(&[-90.0], 3), vec![-90.0, -90.0, -90.0]);
+    }
+
+    #[test]
+    fn canvas_follows_displayed_width() {
+        assert_eq!(canvas_width(1200, 8192), 1200);
+        assert_eq!(canvas_width(100, 8192), MIN_WATERFALL_WIDTH);
+        assert_eq!(canvas_width(10000, 256), MAX_WATERFALL_WIDTH);
+        // not laid out yet
+        assert_eq!(canvas_width(0, 1024), 1024);
+        assert_eq!(canvas_width(0, 8192), MAX_WATERFALL_WIDTH);
+    }
+
+    #[test]
+    fn passband_outside_the_frame() {
+        let bins = carrier_bins();
+        assert_eq!(passband_power(&bins, 14000000.0, 14020000.0, 13999000.0, 14001000.0), None);
+        assert_eq!(passband_power(&bins, 14000000.0, 14020000.0, 14019000.0, 14021000.0), None);
+        assert_eq!(passband_power(&[], 14000000.0, 14020000.0, 14005000.0, 14006000.0), None);
+    }
+}
@@ -6,17 +6,38 @@ use yew::format::{Json,Text,Nothing};
 use yew::services::fetch::{FetchService, Request, Response};
 use yew::services::storage::{Area, StorageService};
 use yew::services::{ConsoleService};
+use std::cell::RefCell;
 use std::collections::HashMap;
+use chrono::{DateTime,Duration,Utc};
 
-use ham_rs::{Call,CountryInfo,Country,LogEntry,Band};
+use ham_rs::{Call,CountryInfo,Country,Band};
 use ham_rs::lotw::LoTWStatus;
-use sparkplug::Spot;
+use sparkplug::{Spot};
 
 use crate::model::{Model,Msg};
+use crate::spot_filter::{FilterContext,FilterKey,FilterPresets,Predicate};
+use crate::spot_history::{SpotHistory};
+use crate::station::{Station,Stations};
 
 const FILTERS_KEY: &str = "radio.spots.filters";
 const LOTW_USERS_KEY: &str = "radio.spots.lotwUsers";
 const STATES_OVERLAY_KEY: &str = "radio.spots.statesOverlay";
+const RETENTION_KEY: &str = "radio.spots.retention";
+const AGGREGATE_KEY: &str = "radio.spots.aggregate";
+
+// How many spots (or stations when grouped), and for how long, the spot
+// table and map keep
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Retention {
+    pub max_spots: usize,
+    pub max_age_minutes: i64,
+}
+
+impl Default for Retention {
+    fn default() -> Retention {
+        Retention { max_spots: 100, max_age_minutes: 30 }
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 enum LoTWUsers {
@@ -32,10 +53,26 @@ enum StatesOverlay {
 
 pub struct SpotDB {
     storage: StorageService,
-    // Spots from enabling SubscribeToSpots
+    // Spots from enabling SubscribeToSpots, filters only decide which of
+    // them are shown
     spots: Vec<Spot>,
+    // Map marker id of each spot, in step with `spots`
+    spot_ids: Vec<u64>,
+    next_spot_id: u64,
+    // Indices of the spots passing the filters and the context they were
+    // filtered with, None until recomputed
+    filtered: RefCell<Option<(FilterKey, Vec<usize>)>>,
     pending_spots: HashMap<String,Vec<Spot>>,
+    retention: Retention,
+    // Spot table shows one row per station instead of every decode
+    aggregate: bool,
+    // Decodes grouped by station, outlive the retained spots
+    stations: Stations,
+    // Every station heard, outlives the retained spots
+    pub history: SpotHistory,
     spot_filters: Vec<SpotFilter>,
+    // Named filter expressions, applied on top of the toggles
+    pub filter_presets: FilterPresets,
     // Local callsign cache
     callsigns: HashMap<String,CallsignInfo>,
     lotw_ft: Option<FetchTask>,
@@ -54,6 +91,20 @@ impl SpotDB {
                 Vec::new()
             }
         };
+        let retention = {
+            if let Json(Ok(retention)) = storage.restore(RETENTION_KEY) {
+                retention
+            } else {
+                Retention::default()
+            }
+        };
+        let aggregate = {
+            if let Json(Ok(aggregate)) = storage.restore(AGGREGATE_KEY) {
+                aggregate
+            } else {
+                false
+            }
+        };
         let lotw_users = {
             if let Json(Ok(entries)) = storage.restore(LOTW_USERS_KEY) {
                 ConsoleService::log("Restoring LoTW users file");
@@ -74,8 +125,16 @@ impl SpotDB {
         SpotDB {
             storage,
             spots: Vec::new(),
+            spot_ids: Vec::new(),
+            next_spot_id: 0,
+            filtered: RefCell::new(None),
             pending_spots: HashMap::new(),
+            retention,
+            aggregate,
+            stations: Stations::default(),
+            history: SpotHistory::new(),
             spot_filters: spot_filters,
+            filter_presets: FilterPresets::new(),
             callsigns: HashMap::new(),
             lotw_ft: None,
             lotw_users: lotw_users,
@@ -86,13 +145,73 @@ impl SpotDB {
 
     pub fn clear_spots(&mut self) {
         self.spots = Vec::new();
+        self.spot_ids = Vec::new();
+        self.stations.clear();
+        self.invalidate_filter();
         self.pending_spots = HashMap::new();
+        js_sys::eval("clearMarkers();").unwrap();
     }
 
     pub fn spots(&self) -> &Vec<Spot> {
         &self.spots
     }
 
+    // Spots passing the current filters, oldest first.  The cached result
+    // is reused while `ctx` matches the context it was filtered with
+    pub fn filtered_spots(&self, ctx: &FilterContext) -> Vec<&Spot> {
+        self.filtered_indices(ctx).into_iter().map(|index| &self.spots[index]).collect()
+    }
+
+    fn filtered_indices(&self, ctx: &FilterContext) -> Vec<usize> {
+        let key = ctx.key();
+        let mut filtered = self.filtered.borrow_mut();
+        if filtered.as_ref().map_or(true, |(cached, _)| *cached != key) {
+            let indices = self.spots.iter().enumerate()
+                .filter(|(_, spot)| self.matches(spot, ctx))
+                .map(|(index, _)| index)
+                .collect();
+            *filtered = Some((key, indices));
+        }
+        filtered.iter().flat_map(|(_, indices)| indices.iter().cloned()).collect()
+    }
+
+    // Filters changed
+    pub fn invalidate_filter(&self) {
+        *self.filtered.borrow_mut() = None;
+    }
+
+    // The current receiver filter is enabled, as a toggle or in the
+    // active preset
+    pub fn filters_use_receiver(&self) -> bool {
+        self.current_receiver_spot_filter_enabled() ||
+            self.filter_presets.active().map(|expr| expr.uses(&Predicate::CurrentReceiver)).unwrap_or(false)
+    }
+
+    // Stations (callsign, band and mode) whose latest decode passes the
+    // filters, most recently heard first
+    pub fn stations(&self, ctx: &FilterContext) -> Vec<&Station> {
+        self.stations.sorted(|station| self.matches(&station.spot, ctx))
+    }
+
+    pub fn aggregate_enabled(&self) -> bool {
+        self.aggregate
+    }
+
+    pub fn toggle_aggregate(&mut self) {
+        self.aggregate = !self.aggregate;
+        self.storage.store(AGGREGATE_KEY, Json(&self.aggregate));
+    }
+
+    // Redraw the map markers after the filters (or what they depend on)
+    // changed
+    pub fn refresh_markers(&self, ctx: &FilterContext) {
+        self.invalidate_filter();
+        js_sys::eval("clearMarkers();").unwrap();
+        for index in self.filtered_indices(ctx) {
+            add_marker(self.spot_ids[index], &self.spots[index]);
+        }
+    }
+
     pub fn has_lotw_users(&self) -> bool {
         match self.lotw_users {
             LoTWUsers::Users(_) => true,
@@ -202,7 +321,7 @@ impl SpotDB {
     }
 
     // CommandResponse: spotResponse
-    pub fn add_spot(&mut self, link: &ComponentLink<Model>, spot: Spot, logs: &Option<Vec<LogEntry>>) {
+    pub fn add_spot(&mut self, link: &ComponentLink<Model>, spot: Spot, ctx: &FilterContext) {
         // FIXME: temp fix
         let mut spot = spot;
 
@@ -229,59 +348,77 @@ impl SpotDB {
             true => {
                 self.pending_spots.entry(spot.call.call()).or_insert(Vec::new()).push(spot);
             },
-            false => self.internal_spot_push(spot, logs),
+            false => self.internal_spot_push(spot, ctx),
         }
     }
 
-    fn internal_spot_push(&mut self, spot: Spot, logs: &Option<Vec<LogEntry>>) {
-        match (logs, self.state_spot_filter_enabled(), self.country_spot_filter_enabled()) {
-            (Some(logs), true, false) if !spot.new_state(logs) => (),
-            (Some(logs), false, true) if !spot.new_country(logs) => (),
-            (Some(logs), true, true) if !spot.new_country(logs) || !spot.new_state(logs) => (),
-            _ => {
-                match self.lotw_spot_filter_enabled() {
-                    true if !spot.uses_lotw() => (),
-                    _ => {
-                        match &spot.locator {
-                            Some(locator) => {
-                                match locator.coord() {
-                                    Ok((lat,lon)) => {
-                                        let spot_on = spot.time.format("%H%M%S").to_string();
-                                        let band = Band::new(spot.tuned_frequency as i32);
-                                        let uses_lotw =
-                                            match spot.call.lotw() {
-                                                LoTWStatus::Registered | LoTWStatus::LastUpload(_) => true,
-                                                _ => false,
-                                            };
-                                        let is_cq = spot.is_cq();
-                                        match band.band() {
-                                            Some(band_name) => {
-                                                js_sys::eval(&format!("addMarker(\"{}\", {}, {}, \"{}\", {}, \"{}\", {}, {}, \"{}\");", spot.call.call(), lat, lon, spot_on, spot.tuned_frequency, band_name, uses_lotw, is_cq, spot.mode.mode())).unwrap();
-                                            },
-                                            _ => (),
-                                        }
-                                    },
-                                    Err(_) => (),
-                                }
-                            },
-                            None => (),
-                        }
-                        self.spots.push(spot)
-                    }
-                }
+    // Spot passes the enabled toggles and the active filter preset
+    pub fn matches(&self, spot: &Spot, ctx: &FilterContext) -> bool {
+        self.spot_filters.iter().all(|f| f.predicate().matches(spot, ctx)) &&
+            self.filter_presets.active().map(|expr| expr.matches(spot, ctx)).unwrap_or(true)
+    }
+
+    fn internal_spot_push(&mut self, spot: Spot, ctx: &FilterContext) {
+        let id = self.next_spot_id;
+        self.next_spot_id += 1;
+        let matches = self.matches(&spot, ctx);
+        if matches {
+            add_marker(id, &spot);
+        }
+        // extend the cached result if it was filtered with the same context
+        let key = ctx.key();
+        let filtered = self.filtered.get_mut();
+        if filtered.as_ref().map_or(false, |(cached, _)| *cached != key) {
+            *filtered = None;
+        }
+        if let Some((_, indices)) = filtered {
+            if matches {
+                indices.push(self.spots.len());
             }
         }
+        self.history.record(&spot);
+        self.stations.add(&spot);
+        self.spots.push(spot);
+        self.spot_ids.push(id);
+    }
+
+    pub fn retention(&self) -> Retention {
+        self.retention
     }
 
-    // helper function to remove all except `limit` recent spots
-    pub fn trim_spots(&mut self, limit: usize) {
-        if self.spots.len() > limit {
-            let drain = self.spots.len() - limit;
-            self.spots.drain(0..drain);
+    pub fn set_retention(&mut self, retention: Retention) {
+        self.retention = Retention {
+            max_spots: retention.max_spots.max(1),
+            max_age_minutes: retention.max_age_minutes.max(1),
+        };
+        self.storage.store(RETENTION_KEY, Json(&self.retention));
+    }
+
+    // Drop spots and stations past the retention policy along with the
+    // markers of the dropped spots, returns true if any were removed
+    pub fn prune(&mut self, now: DateTime<Utc>) -> bool {
+        let oldest = now - Duration::minutes(self.retention.max_age_minutes);
+        let spots = std::mem::take(&mut self.spots);
+        let spot_ids = std::mem::take(&mut self.spot_ids);
+        let (mut kept, mut pruned): (Vec<(u64, Spot)>, Vec<(u64, Spot)>) =
+            spot_ids.into_iter().zip(spots).partition(|(_, spot)| spot.time >= oldest);
+        if kept.len() > self.retention.max_spots {
+            let drain = kept.len() - self.retention.max_spots;
+            pruned.extend(kept.drain(0..drain));
+        }
+        let (spot_ids, spots) = kept.into_iter().unzip();
+        self.spot_ids = spot_ids;
+        self.spots = spots;
+
+        let stations_pruned = self.stations.prune(oldest, self.retention.max_spots);
+        if !pruned.is_empty() {
+            self.invalidate_filter();
+            remove_markers(&pruned.iter().map(|(id, _)| *id).collect::<Vec<u64>>());
         }
+        !pruned.is_empty() || stations_pruned
     }
 
-    pub fn cache_callsign_info(&mut self, call: Call, logs: &Option<Vec<LogEntry>>) {
+    pub fn cache_callsign_info(&mut self, call: Call, ctx: &FilterContext) {
         self.callsigns.insert(call.call(), CallsignInfo::Found(call.clone()));
 
         // remove spots from pending queue and publish them
@@ -290,7 +427,7 @@ impl SpotDB {
             Some(mut spots) => {
                 for mut spot in spots.drain(..) {
                     spot.set_call(call.clone());
-                    self.internal_spot_push(spot, logs);
+                    self.internal_spot_push(spot, ctx);
                 }
             },
             None => ()
@@ -301,6 +438,7 @@ impl SpotDB {
         self.spot_filters.push(filter);
         self.spot_filters.sort();
         self.spot_filters.dedup();
+        self.invalidate_filter();
         self.storage.store(FILTERS_KEY, Json(&self.spot_filters));
     }
 
@@ -308,6 +446,7 @@ impl SpotDB {
         match self.spot_filters.iter().position(|x| *x == filter) {
             Some(index) => {
                 self.spot_filters.remove(index);
+                self.invalidate_filter();
                 self.storage.store(FILTERS_KEY, Json(&self.spot_filters));
                 Ok(())
             },
@@ -351,6 +490,41 @@ impl SpotDB {
     }
 }
 
+// Add a spot to the map (static/js/app.js)
+fn add_marker(id: u64, spot: &Spot) {
+    match &spot.locator {
+        Some(locator) => {
+            match locator.coord() {
+                Ok((lat,lon)) => {
+                    let spot_on = spot.time.format("%H%M%S").to_string();
+                    let band = Band::new(spot.tuned_frequency as i32);
+                    let uses_lotw =
+                        match spot.call.lotw() {
+                            LoTWStatus::Registered | LoTWStatus::LastUpload(_) => true,
+                            _ => false,
+                        };
+                    let is_cq = spot.is_cq();
+                    match band.band() {
+                        Some(band_name) => {
+                            js_sys::eval(&format!("addMarker({}, \"{}\", {}, {}, \"{}\", {}, \"{}\", {}, {}, \"{}\");", id, spot.call.call(), lat, lon, spot_on, spot.tuned_frequency, band_name, uses_lotw, is_cq, spot.mode.mode())).unwrap();
+                        },
+                        _ => (),
+                    }
+                },
+                Err(_) => (),
+            }
+        },
+        None => (),
+    }
+}
+
+// Remove the markers of spots that were dropped, ids without a marker
+// (filtered out spots) are ignored
+fn remove_markers(ids: &[u64]) {
+    js_sys::eval(&format!("removeMarkers({:?});", ids)).unwrap();
+}
+
+// Quick filter toggles in the sidebar, each is a filter engine predicate
 #[derive(Ord, PartialOrd, Eq, PartialEq, Deserialize, Serialize)]
 pub enum SpotFilter {
     CQOnly,
@@ -360,6 +534,18 @@ pub enum SpotFilter {
     LoTW,
 }
 
+impl SpotFilter {
+    pub fn predicate(&self) -> Predicate {
+        match self {
+            SpotFilter::CQOnly => Predicate::Cq,
+            SpotFilter::NewState => Predicate::NewState,
+            SpotFilter::NewCountry => Predicate::NewCountry,
+            SpotFilter::CurrentReceiver => Predicate::CurrentReceiver,
+            SpotFilter::LoTW => Predicate::LoTW,
+        }
+    }
+}
+
 
 
 // Used with the local callsign cache for our requests
@@ -0,0 +1,678 @@
+use std::fmt;
+use regex::{Regex,RegexBuilder};
+use yew::format::{Json};
+use yew::services::storage::{Area, StorageService};
+
+use ham_rs::{Band,CountryInfo,LogEntry};
+use sparkplug::{Receiver,Spot};
+
+const PRESETS_KEY: &str = "radio.spots.filterPresets";
+
+// Continents by ISO 3166 country code (as used for the flag icons)
+const CONTINENTS: [(&str, &str); 7] = [
+    ("NA", "ag ai aw bb bl bm bq bs bz ca cr cu cw dm do gd gl gp gt hn ht jm kn ky lc mf mq ms mx ni pa pm pr sv sx tc tt um us vc vg vi"),
+    ("SA", "ar bo br cl co ec fk gf gs gy pe py sr uy ve"),
+    ("EU", "ad al at ax ba be bg by ch cy cz de dk ee es fi fo fr gb gg gi gr hr hu ie im is it je li lt lu lv mc md me mk mt nl no pl pt ro rs ru se si sj sk sm ua va xk"),
+    ("AF", "ao bf bi bj bw cd cf cg ci cm cv dj dz eg eh er et ga gh gm gn gq gw ke km lr ls ly ma mg ml mr mu mw mz na ne ng re rw sc sd sh sl sn so ss st sz td tg tn tz ug yt za zm zw"),
+    ("AS", "ae af am az bd bh bn bt cn ge hk id il in io iq ir jo jp kg kh kp kr kw kz la lb lk mm mn mo mv my np om ph pk ps qa sa sg sy th tj tl tm tr tw uz vn ye"),
+    ("OC", "as au ck fj fm gu ki mh mp nc nf nr nu nz pf pg pn pw sb tk to tv vu wf ws"),
+    ("AN", "aq bv hm tf"),
+];
+
+pub fn continent(country_code: &str) -> Option<&'static str> {
+    let code = country_code.to_lowercase();
+    CONTINENTS.iter().find(|(_, codes)| codes.split(' ').any(|c| c == code)).map(|(continent, _)| *continent)
+}
+
+// Inclusive numeric range, either end open
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Range {
+    pub min: Option<f32>,
+    pub max: Option<f32>,
+}
+
+impl Range {
+    // ">-10", "<0", "-20..-5" or "-10" (at least)
+    fn parse(value: &str) -> Option<Range> {
+        let value = value.trim_start_matches('=');
+        if let Some(max) = value.strip_prefix("<") {
+            return max.trim_start_matches('=').parse().ok().map(|max| Range { min: None, max: Some(max) });
+        }
+        if let Some(min) = value.strip_prefix(">") {
+            return min.trim_start_matches('=').parse().ok().map(|min| Range { min: Some(min), max: None });
+        }
+        match value.find("..") {
+            Some(index) => {
+                let min = &value[..index];
+                let max = &value[index + 2..];
+                Some(Range {
+                    min: if min.is_empty() { None } else { Some(min.parse().ok()?) },
+                    max: if max.is_empty() { None } else { Some(max.parse().ok()?) },
+                })
+            },
+            None => value.parse().ok().map(|min| Range { min: Some(min), max: None }),
+        }
+    }
+
+    fn contains(&self, value: f32) -> bool {
+        self.min.map(|min| value >= min).unwrap_or(true) && self.max.map(|max| value <= max).unwrap_or(true)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Predicate {
+    Band(String),
+    Mode(String),
+    Snr(Range),
+    // km, spots without a locator never match
+    Distance(Range),
+    // part of the country name
+    Country(String),
+    Continent(String),
+    // callsign glob, * and ?
+    Call(String),
+    Message(Regex),
+    Cq,
+    LoTW,
+    // callsign is in the imported log
+    Worked,
+    NewCountry,
+    NewState,
+    // spots heard on the default receiver
+    CurrentReceiver,
+}
+
+#[derive(Clone, Debug)]
+pub enum FilterExpr {
+    Predicate(Predicate),
+    And(Vec<FilterExpr>),
+    Or(Vec<FilterExpr>),
+    Not(Box<FilterExpr>),
+}
+
+// What a spot is compared against besides its own fields
+pub struct FilterContext<'a> {
+    pub logs: Option<&'a Vec<LogEntry>>,
+    // changes whenever `logs` does
+    pub log_generation: u64,
+    pub receiver: Option<&'a Receiver>,
+}
+
+// The parts of a FilterContext predicates look at, results filtered with
+// an equal key are still valid
+#[derive(Clone, Debug, PartialEq)]
+pub struct FilterKey {
+    log_generation: u64,
+    // id, frequency, mode and filter edges
+    receiver: Option<(u32, f32, String, f32, f32)>,
+}
+
+impl<'a> FilterContext<'a> {
+    pub fn key(&self) -> FilterKey {
+        FilterKey {
+            log_generation: self.log_generation,
+            receiver: self.receiver.map(|r| (r.id, r.frequency, r.mode.mode().to_string(), r.filter_low, r.filter_high)),
+        }
+    }
+}
+
+impl FilterExpr {
+    pub fn matches(&self, spot: &Spot, ctx: &FilterContext) -> bool {
+        match self {
+            FilterExpr::Predicate(predicate) => predicate.matches(spot, ctx),
+            FilterExpr::And(exprs) => exprs.iter().all(|e| e.matches(spot, ctx)),
+            FilterExpr::Or(exprs) => exprs.iter().any(|e| e.matches(spot, ctx)),
+            FilterExpr::Not(expr) => !expr.matches(spot, ctx),
+        }
+    }
+
+    // Does the expression contain the (value-less) `predicate`
+    pub fn uses(&self, predicate: &Predicate) -> bool {
+        match self {
+            FilterExpr::Predicate(p) => std::mem::discriminant(p) == std::mem::discriminant(predicate),
+            FilterExpr::And(exprs) | FilterExpr::Or(exprs) => exprs.iter().any(|e| e.uses(predicate)),
+            FilterExpr::Not(expr) => expr.uses(predicate),
+        }
+    }
+}
+
+impl Predicate {
+    pub fn matches(&self, spot: &Spot, ctx: &FilterContext) -> bool {
+        match self {
+            Predicate::Band(band) => {
+                match Band::new(spot.tuned_frequency as i32).band() {
+                    Some(name) => name.to_string().eq_ignore_ascii_case(band),
+                    None => false,
+                }
+            },
+            Predicate::Mode(mode) => spot.mode.mode().eq_ignore_ascii_case(mode),
+            Predicate::Snr(range) => range.contains(spot.snr as f32),
+            Predicate::Distance(range) => spot.distance.map(|d| range.contains(d as f32)).unwrap_or(false),
+            Predicate::Country(name) => {
+                match spot.call.country() {
+                    Ok(country) => country.name().to_string().to_lowercase().contains(&name.to_lowercase()),
+                    Err(_) => false,
+                }
+            },
+            Predicate::Continent(name) => {
+                match spot.call.country() {
+                    Ok(country) => continent(&country.code().to_string()).map(|c| c.eq_ignore_ascii_case(name)).unwrap_or(false),
+                    Err(_) => false,
+                }
+            },
+            Predicate::Call(pattern) => glob_match(&pattern.to_uppercase(), &spot.call.call().to_uppercase()),
+            Predicate::Message(regex) => spot.msg.as_ref().map(|msg| regex.is_match(msg)).unwrap_or(false),
+            Predicate::Cq => spot.is_cq(),
+            Predicate::LoTW => spot.uses_lotw(),
+            // without a log everything is new and nothing worked
+            Predicate::Worked => ctx.logs.map(|logs| logs.iter().any(|l| same_call(&l.call.call(), &spot.call.call()))).unwrap_or(false),
+            Predicate::NewCountry => ctx.logs.map(|logs| spot.new_country(logs)).unwrap_or(true),
+            Predicate::NewState => ctx.logs.map(|logs| spot.new_state(logs)).unwrap_or(true),
+            Predicate::CurrentReceiver => {
+                match ctx.receiver {
+                    Some(receiver) if receiver.has_spots() => spot.current_rx(receiver),
+                    _ => true,
+                }
+            },
+        }
+    }
+}
+
+// Logged and spotted calls differ in case depending on the source
+fn same_call(a: &str, b: &str) -> bool {
+    a.to_uppercase() == b.to_uppercase()
+}
+
+// * matches any run of characters, ? a single one
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // position of the last * and the text position it was tried at
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterError {
+    Empty,
+    UnexpectedEnd,
+    Unexpected(String),
+    UnknownTerm(String),
+    InvalidValue { key: String, value: String },
+    InvalidRegex(String),
+}
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FilterError::Empty => write!(f, "empty filter"),
+            FilterError::UnexpectedEnd => write!(f, "unexpected end of filter"),
+            FilterError::Unexpected(token) => write!(f, "unexpected \"{}\"", token),
+            FilterError::UnknownTerm(term) => write!(f, "unknown filter term \"{}\"", term),
+            FilterError::InvalidValue { key, value } => write!(f, "invalid value \"{}\" for {}", value, key),
+            FilterError::InvalidRegex(err) => write!(f, "invalid message pattern: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+// Help text for the expression syntax
+pub const FILTER_SYNTAX: &str = "Terms: band:20m mode:FT8 snr:>-10 snr:-20..-5 dist:>5000 country:\"united states\" continent:EU call:K* msg:\"^CQ DX\" cq lotw worked new-country new-state current-rx. Combine with and, or, not and parentheses, terms next to each other are and-ed.";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Term(String),
+}
+
+fn tokenize(input: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.peek().cloned() {
+        match c {
+            _ if c.is_whitespace() => { chars.next(); },
+            '(' => { chars.next(); tokens.push(Token::Open); },
+            ')' => { chars.next(); tokens.push(Token::Close); },
+            '!' => { chars.next(); tokens.push(Token::Not); },
+            _ => {
+                // a word, double quotes keep spaces and parentheses
+                let mut word = String::new();
+                let mut quoted = false;
+                while let Some(c) = chars.peek().cloned() {
+                    if c == '"' {
+                        quoted = !quoted;
+                    } else if !quoted && (c.is_whitespace() || c == '(' || c == ')') {
+                        break;
+                    } else {
+                        word.push(c);
+                    }
+                    chars.next();
+                }
+                tokens.push(
+                    match word.to_lowercase().as_str() {
+                        "and" | "&&" => Token::And,
+                        "or" | "||" => Token::Or,
+                        "not" => Token::Not,
+                        _ => Token::Term(word),
+                    });
+            }
+        }
+    }
+    tokens
+}
+
+fn term(word: &str) -> Result<Predicate, FilterError> {
+    let (key, value) =
+        match word.find(':') {
+            Some(index) => (word[..index].to_lowercase(), Some(word[index + 1..].to_string())),
+            None => (word.to_lowercase(), None),
+        };
+    let invalid = |value: &str| FilterError::InvalidValue { key: key.clone(), value: value.to_string() };
+    match (key.as_str(), value) {
+        ("cq", None) => Ok(Predicate::Cq),
+        ("lotw", None) => Ok(Predicate::LoTW),
+        ("worked", None) => Ok(Predicate::Worked),
+        ("new-country", None) => Ok(Predicate::NewCountry),
+        ("new-state", None) => Ok(Predicate::NewState),
+        ("current-rx", None) => Ok(Predicate::CurrentReceiver),
+        (_, Some(value)) if value.is_empty() => Err(invalid(&value)),
+        ("band", Some(value)) => Ok(Predicate::Band(value)),
+        ("mode", Some(value)) => Ok(Predicate::Mode(value)),
+        ("snr", Some(value)) => Range::parse(&value).map(Predicate::Snr).ok_or_else(|| invalid(&value)),
+        ("dist", Some(value)) | ("distance", Some(value)) => Range::parse(&value).map(Predicate::Distance).ok_or_else(|| invalid(&value)),
+        ("country", Some(value)) => Ok(Predicate::Country(value)),
+        ("continent", Some(value)) => {
+            match CONTINENTS.iter().any(|(c, _)| c.eq_ignore_ascii_case(&value)) {
+                true => Ok(Predicate::Continent(value)),
+                false => Err(invalid(&value)),
+            }
+        },
+        ("call", Some(value)) => Ok(Predicate::Call(value)),
+        ("msg", Some(value)) => {
+            RegexBuilder::new(&value).case_insensitive(true).build()
+                .map(Predicate::Message)
+                .map_err(|err| FilterError::InvalidRegex(err.to_string()))
+        },
+        _ => Err(FilterError::UnknownTerm(word.to_string())),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<FilterExpr, FilterError> {
+        let mut exprs = vec![self.and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            exprs.push(self.and()?);
+        }
+        Ok(if exprs.len() == 1 { exprs.remove(0) } else { FilterExpr::Or(exprs) })
+    }
+
+    fn and(&mut self) -> Result<FilterExpr, FilterError> {
+        let mut exprs = vec![self.unary()?];
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.next();
+                    exprs.push(self.unary()?);
+                },
+                Some(Token::Not) | Some(Token::Open) | Some(Token::Term(_)) => exprs.push(self.unary()?),
+                _ => break,
+            }
+        }
+        Ok(if exprs.len() == 1 { exprs.remove(0) } else { FilterExpr::And(exprs) })
+    }
+
+    fn unary(&mut self) -> Result<FilterExpr, FilterError> {
+        match self.next() {
+            Some(Token::Not) => Ok(FilterExpr::Not(Box::new(self.unary()?))),
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    Some(token) => Err(FilterError::Unexpected(token_text(&token))),
+                    None => Err(FilterError::UnexpectedEnd),
+                }
+            },
+            Some(Token::Term(word)) => term(&word).map(FilterExpr::Predicate),
+            Some(token) => Err(FilterError::Unexpected(token_text(&token))),
+            None => Err(FilterError::UnexpectedEnd),
+        }
+    }
+}
+
+fn token_text(token: &Token) -> String {
+    match token {
+        Token::Open => "(".to_string(),
+        Token::Close => ")".to_string(),
+        Token::And => "and".to_string(),
+        Token::Or => "or".to_string(),
+        Token::Not => "not".to_string(),
+        Token::Term(word) => word.to_string(),
+    }
+}
+
+// Parse a filter expression such as `band:20m and (cq or lotw) and not worked`
+pub fn parse(input: &str) -> Result<FilterExpr, FilterError> {
+    let mut parser = Parser { tokens: tokenize(input), pos: 0 };
+    if parser.tokens.is_empty() {
+        return Err(FilterError::Empty);
+    }
+    let expr = parser.or()?;
+    match parser.next() {
+        Some(token) => Err(FilterError::Unexpected(token_text(&token))),
+        None => Ok(expr),
+    }
+}
+
+// A saved filter, kept as its source text
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FilterPreset {
+    pub name: String,
+    pub expression: String,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct StoredPresets {
+    presets: Vec<FilterPreset>,
+    active: Option<String>,
+}
+
+// Preset being edited in the sidebar
+#[derive(Clone, Debug, Default)]
+pub struct FilterDraft {
+    pub name: String,
+    pub expression: String,
+}
+
+// Named filter presets, at most one of them applied to the spots
+pub struct FilterPresets {
+    storage: StorageService,
+    presets: Vec<FilterPreset>,
+    active: Option<(String, FilterExpr)>,
+    pub draft: FilterDraft,
+}
+
+impl FilterPresets {
+    pub fn new() -> FilterPresets {
+        let storage = StorageService::new(Area::Local).expect("storage was disabled by the user");
+        let stored = {
+            if let Json(Ok(stored)) = storage.restore(PRESETS_KEY) {
+                stored
+            } else {
+                StoredPresets::default()
+            }
+        };
+        let mut presets = FilterPresets {
+            storage,
+            presets: stored.presets,
+            active: None,
+            draft: FilterDraft::default(),
+        };
+        if let Some(name) = stored.active {
+            // a preset that no longer parses is dropped silently
+            let _ = presets.activate(Some(&name));
+        }
+        presets
+    }
+
+    pub fn presets(&self) -> &Vec<FilterPreset> {
+        &self.presets
+    }
+
+    pub fn active_name(&self) -> Option<&str> {
+        self.active.as_ref().map(|(name, _)| name.as_str())
+    }
+
+    pub fn active(&self) -> Option<&FilterExpr> {
+        self.active.as_ref().map(|(_, expr)| expr)
+    }
+
+    // Apply a preset (None for no preset) and load it into the draft
+    pub fn activate(&mut self, name: Option<&str>) -> Result<(), FilterError> {
+        self.active =
+            match name.and_then(|name| self.presets.iter().find(|p| p.name == name)) {
+                Some(preset) => {
+                    self.draft = FilterDraft { name: preset.name.clone(), expression: preset.expression.clone() };
+                    Some((preset.name.clone(), parse(&preset.expression)?))
+                },
+                None => None,
+            };
+        self.store();
+        Ok(())
+    }
+
+    // Save the draft (replacing a preset of the same name) and apply it
+    pub fn save(&mut self) -> Result<(), FilterError> {
+        let name = self.draft.name.trim().to_string();
+        let expression = self.draft.expression.trim().to_string();
+        let expr = parse(&expression)?;
+        if name.is_empty() {
+            return Err(FilterError::InvalidValue { key: "name".to_string(), value: name });
+        }
+        match self.presets.iter_mut().find(|p| p.name == name) {
+            Some(preset) => preset.expression = expression,
+            None => self.presets.push(FilterPreset { name: name.clone(), expression }),
+        }
+        self.active = Some((name, expr));
+        self.store();
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.presets.retain(|p| p.name != name);
+        if self.active_name() == Some(name) {
+            self.active = None;
+        }
+        self.store();
+    }
+
+    fn store(&mut self) {
+        let stored = StoredPresets {
+            presets: self.presets.clone(),
+            active: self.active_name().map(|name| name.to_string()),
+        };
+        self.storage.store(PRESETS_KEY, Json(&stored));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(min: Option<f32>, max: Option<f32>) -> Range {
+        Range { min, max }
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        match parse("cq or lotw and worked").unwrap() {
+            FilterExpr::Or(exprs) => {
+                assert_eq!(exprs.len(), 2);
+                assert!(matches!(exprs[0], FilterExpr::Predicate(Predicate::Cq)));
+                match &exprs[1] {
+                    FilterExpr::And(exprs) => {
+                        assert!(matches!(exprs[0], FilterExpr::Predicate(Predicate::LoTW)));
+                        assert!(matches!(exprs[1], FilterExpr::Predicate(Predicate::Worked)));
+                    },
+                    expr => panic!("expected and, got {:?}", expr),
+                }
+            },
+            expr => panic!("expected or, got {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn parentheses_override_precedence() {
+        match parse("(cq or lotw) and worked").unwrap() {
+            FilterExpr::And(exprs) => {
+                assert!(matches!(exprs[0], FilterExpr::Or(_)));
+                assert!(matches!(exprs[1], FilterExpr::Predicate(Predicate::Worked)));
+            },
+            expr => panic!("expected and, got {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn adjacent_terms_are_anded() {
+        match parse("band:20m mode:FT8 cq").unwrap() {
+            FilterExpr::And(exprs) => {
+                assert_eq!(exprs.len(), 3);
+                assert!(matches!(&exprs[0], FilterExpr::Predicate(Predicate::Band(band)) if band == "20m"));
+                assert!(matches!(&exprs[1], FilterExpr::Predicate(Predicate::Mode(mode)) if mode == "FT8"));
+                assert!(matches!(exprs[2], FilterExpr::Predicate(Predicate::Cq)));
+            },
+            expr => panic!("expected and, got {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn negation() {
+        for input in &["!worked", "not worked", "NOT worked"] {
+            match parse(input).unwrap() {
+                FilterExpr::Not(expr) => assert!(matches!(*expr, FilterExpr::Predicate(Predicate::Worked))),
+                expr => panic!("expected not for {}, got {:?}", input, expr),
+            }
+        }
+        match parse("cq !worked").unwrap() {
+            FilterExpr::And(exprs) => assert!(matches!(exprs[1], FilterExpr::Not(_))),
+            expr => panic!("expected and, got {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn quoted_values_keep_spaces_and_parentheses() {
+        match parse("country:\"united states\"").unwrap() {
+            FilterExpr::Predicate(Predicate::Country(name)) => assert_eq!(name, "united states"),
+            expr => panic!("expected country, got {:?}", expr),
+        }
+        match parse("msg:\"^CQ (DX|NA)\" cq").unwrap() {
+            FilterExpr::And(exprs) => {
+                match &exprs[0] {
+                    FilterExpr::Predicate(Predicate::Message(regex)) => {
+                        assert!(regex.is_match("cq dx K1ABC FN42"));
+                        assert!(!regex.is_match("K1ABC W1AW -10"));
+                    },
+                    expr => panic!("expected message, got {:?}", expr),
+                }
+            },
+            expr => panic!("expected and, got {:?}", expr),
+        }
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(Range::parse(">-10"), Some(range(Some(-10.0), None)));
+        assert_eq!(Range::parse(">=-10"), Some(range(Some(-10.0), None)));
+        assert_eq!(Range::parse("<0"), Some(range(None, Some(0.0))));
+        assert_eq!(Range::parse("-20..-5"), Some(range(Some(-20.0), Some(-5.0))));
+        assert_eq!(Range::parse("..5"), Some(range(None, Some(5.0))));
+        assert_eq!(Range::parse("5.."), Some(range(Some(5.0), None)));
+        assert_eq!(Range::parse("-10"), Some(range(Some(-10.0), None)));
+        assert_eq!(Range::parse("loud"), None);
+        assert_eq!(Range::parse("-20..x"), None);
+
+        let r = range(Some(-20.0), Some(-5.0));
+        assert!(r.contains(-20.0) && r.contains(-5.0) && r.contains(-12.0));
+        assert!(!r.contains(-21.0) && !r.contains(0.0));
+
+        assert!(matches!(parse("snr:-20..-5").unwrap(), FilterExpr::Predicate(Predicate::Snr(r)) if r == range(Some(-20.0), Some(-5.0))));
+        assert!(matches!(parse("dist:>5000").unwrap(), FilterExpr::Predicate(Predicate::Distance(r)) if r == range(Some(5000.0), None)));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(parse("").unwrap_err(), FilterError::Empty);
+        assert_eq!(parse("   ").unwrap_err(), FilterError::Empty);
+        assert!(matches!(parse("msg:\"(unclosed\"").unwrap_err(), FilterError::InvalidRegex(_)));
+        assert_eq!(parse("loud").unwrap_err(), FilterError::UnknownTerm("loud".to_string()));
+        assert_eq!(parse("colour:red").unwrap_err(), FilterError::UnknownTerm("colour:red".to_string()));
+        assert_eq!(parse("snr:loud").unwrap_err(), FilterError::InvalidValue { key: "snr".to_string(), value: "loud".to_string() });
+        assert_eq!(parse("band:").unwrap_err(), FilterError::InvalidValue { key: "band".to_string(), value: String::new() });
+        assert_eq!(parse("continent:XX").unwrap_err(), FilterError::InvalidValue { key: "continent".to_string(), value: "XX".to_string() });
+        assert_eq!(parse("(cq or lotw").unwrap_err(), FilterError::UnexpectedEnd);
+        assert_eq!(parse("cq and").unwrap_err(), FilterError::UnexpectedEnd);
+        assert_eq!(parse("cq)").unwrap_err(), FilterError::Unexpected(")".to_string()));
+        assert_eq!(parse("or cq").unwrap_err(), FilterError::Unexpected("or".to_string()));
+    }
+
+    #[test]
+    fn uses_predicate() {
+        assert!(parse("band:20m and (cq or not current-rx)").unwrap().uses(&Predicate::CurrentReceiver));
+        assert!(!parse("band:20m cq").unwrap().uses(&Predicate::CurrentReceiver));
+    }
+
+    #[test]
+    fn glob() {
+        assert!(glob_match("K*", "K1ABC"));
+        assert!(glob_match("K*1?", "K1AB1C"));
+        assert!(glob_match("K*1?", "KA1B"));
+        assert!(glob_match("*1*", "W1AW"));
+        assert!(glob_match("W1AW", "W1AW"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("K*1?", "K1ABC"));
+        assert!(!glob_match("K*", "W1AW"));
+        assert!(!glob_match("W1A?", "W1A"));
+        assert!(!glob_match("", "W1AW"));
+    }
+
+    #[test]
+    fn context_key_follows_the_logs() {
+        let logs = Vec::new();
+        let ctx = FilterContext { logs: Some(&logs), log_generation: 1, receiver: None };
+        assert_eq!(ctx.key(), FilterContext { logs: Some(&logs), log_generation: 1, receiver: None }.key());
+        assert_ne!(ctx.key(), FilterContext { logs: None, log_generation: 2, receiver: None }.key());
+    }
+
+    #[test]
+    fn calls_compare_case_insensitively() {
+        assert!(same_call("W1AW", "W1AW"));
+        assert!(same_call("w1aw", "W1AW"));
+        assert!(same_call("DL1abc/P", "dl1ABC/p"));
+        assert!(!same_call("W1AW", "W1AW/P"));
+        assert!(!same_call("K1ABC", "K1ABD"));
+    }
+
+    #[test]
+    fn continents() {
+        assert_eq!(continent("US"), Some("NA"));
+        assert_eq!(continent("de"), Some("EU"));
+        assert_eq!(continent("jp"), Some("AS"));
+        assert_eq!(continent("zz"), None);
+    }
+}
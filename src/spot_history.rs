@@ -0,0 +1,444 @@
+use chrono::{DateTime,Utc};
+use std::collections::{BTreeMap,BTreeSet,HashMap};
+use web_sys::{Storage};
+use yew::services::{ConsoleService};
+
+use ham_rs::{Band};
+use sparkplug::{Spot};
+
+const HISTORY_KEY: &str = "radio.spots.history";
+// Stations (call and band) remembered, the least recently heard are
+// forgotten first
+const MAX_HISTORY_ENTRIES: usize = 5000;
+
+// When and how a station was heard on a band
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Heard {
+    pub call: String,
+    pub band: String,
+    pub first: DateTime<Utc>,
+    pub last: DateTime<Utc>,
+    pub frequency: f32,
+    pub mode: String,
+    pub snr: f32,
+    pub count: u32,
+}
+
+impl Heard {
+    // A single decode of `spot`, heard on `band`
+    pub fn from_spot(spot: &Spot, band: String) -> Heard {
+        Heard {
+            call: spot.call.call(),
+            band,
+            first: spot.time,
+            last: spot.time,
+            frequency: spot.frequency as f32,
+            mode: spot.mode.mode().to_string(),
+            snr: spot.snr as f32,
+            count: 1,
+        }
+    }
+
+    // Fold in more decodes of the same station.  The frequency, mode and
+    // SNR are those of the latest decode, decodes arriving out of order
+    // only add to the count and first heard time
+    pub fn merge(&mut self, decode: Heard) {
+        let first = self.first.min(decode.first);
+        let count = self.count + decode.count;
+        if decode.last >= self.last {
+            *self = decode;
+        }
+        self.first = first;
+        self.count = count;
+    }
+}
+
+// Call/band being looked up in the sidebar
+#[derive(Default)]
+pub struct HistoryQuery {
+    pub call: String,
+    pub band: String,
+}
+
+// Every station spotted, kept independently of the spot retention
+pub struct SpotHistory {
+    // local storage, written directly so a full storage can be handled
+    storage: Option<Storage>,
+    // call -> band -> heard
+    by_call: HashMap<String, BTreeMap<String, Heard>>,
+    // (last heard, call, band), oldest first
+    by_time: BTreeSet<(DateTime<Utc>, String, String)>,
+    // band -> (last heard, call), oldest first
+    by_band: HashMap<String, BTreeSet<(DateTime<Utc>, String)>>,
+    max_entries: usize,
+    // changed since the last save
+    dirty: bool,
+    save_error: Option<String>,
+    pub query: HistoryQuery,
+}
+
+impl SpotHistory {
+    pub fn new() -> SpotHistory {
+        let storage = web_sys::window().and_then(|window| window.local_storage().ok().flatten());
+        let entries: Vec<Heard> =
+            match storage.as_ref().and_then(|storage| storage.get_item(HISTORY_KEY).ok().flatten()) {
+                Some(data) => serde_json::from_str(&data).unwrap_or_default(),
+                None => Vec::new(),
+            };
+        SpotHistory::with_entries(storage, entries, MAX_HISTORY_ENTRIES)
+    }
+
+    fn with_entries(storage: Option<Storage>, entries: Vec<Heard>, max_entries: usize) -> SpotHistory {
+        let mut history = SpotHistory {
+            storage,
+            by_call: HashMap::new(),
+            by_time: BTreeSet::new(),
+            by_band: HashMap::new(),
+            max_entries,
+            dirty: false,
+            save_error: None,
+            query: HistoryQuery::default(),
+        };
+        for heard in entries {
+            history.insert(heard);
+        }
+        history.evict(max_entries);
+        history
+    }
+
+    fn insert(&mut self, heard: Heard) {
+        self.by_time.insert((heard.last, heard.call.clone(), heard.band.clone()));
+        self.by_band.entry(heard.band.clone()).or_insert_with(BTreeSet::new).insert((heard.last, heard.call.clone()));
+        self.by_call.entry(heard.call.clone()).or_insert_with(BTreeMap::new).insert(heard.band.clone(), heard);
+    }
+
+    // Drop a station from the time indices, by_call is left to the caller
+    fn unindex(&mut self, last: DateTime<Utc>, call: &str, band: &str) {
+        self.by_time.remove(&(last, call.to_string(), band.to_string()));
+        if let Some(calls) = self.by_band.get_mut(band) {
+            calls.remove(&(last, call.to_string()));
+            if calls.is_empty() {
+                self.by_band.remove(band);
+            }
+        }
+    }
+
+    pub fn record(&mut self, spot: &Spot) {
+        let band =
+            match Band::new(spot.tuned_frequency as i32).band() {
+                Some(band) => band.to_string(),
+                None => return,
+            };
+        self.update(Heard::from_spot(spot, band));
+    }
+
+    // Merge a single decode into what we know about the station
+    fn update(&mut self, decode: Heard) {
+        let previous = self.by_call.get_mut(&decode.call).and_then(|bands| bands.remove(&decode.band));
+        let heard =
+            match previous {
+                Some(mut heard) => {
+                    self.unindex(heard.last, &heard.call, &heard.band);
+                    heard.merge(decode);
+                    heard
+                },
+                None => decode,
+            };
+        self.insert(heard);
+        self.evict(self.max_entries);
+        self.dirty = true;
+    }
+
+    // Forget the least recently heard stations until at most `max` remain
+    fn evict(&mut self, max: usize) {
+        while self.by_time.len() > max {
+            let oldest = self.by_time.iter().next().cloned();
+            if let Some(oldest) = oldest {
+                let (last, call, band) = oldest;
+                self.unindex(last, &call, &band);
+                if let Some(bands) = self.by_call.get_mut(&call) {
+                    bands.remove(&band);
+                    if bands.is_empty() {
+                        self.by_call.remove(&call);
+                    }
+                }
+            }
+        }
+    }
+
+    // Persist if anything changed, called on the spot prune interval
+    // rather than for every batch of spots. When local storage is full
+    // the least recently heard half is forgotten until the rest fits
+    pub fn save(&mut self) -> Result<(), String> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let storage =
+            match &self.storage {
+                Some(storage) => storage.clone(),
+                None => {
+                    self.dirty = false;
+                    return Ok(());
+                },
+            };
+        loop {
+            let entries: Vec<&Heard> = self.by_call.values().flat_map(|bands| bands.values()).collect();
+            let data = serde_json::to_string(&entries).map_err(|err| err.to_string())?;
+            match storage.set_item(HISTORY_KEY, &data) {
+                Ok(()) => {
+                    self.dirty = false;
+                    self.save_error = None;
+                    return Ok(());
+                },
+                Err(_) if !self.by_time.is_empty() => {
+                    let keep = self.by_time.len() / 2;
+                    ConsoleService::warn(&format!("spot history does not fit in local storage, keeping {} stations", keep));
+                    self.evict(keep);
+                },
+                Err(err) => {
+                    let err = format!("unable to store the spot history: {:?}", err);
+                    self.save_error = Some(err.clone());
+                    return Err(err);
+                },
+            }
+        }
+    }
+
+    pub fn save_error(&self) -> Option<&str> {
+        self.save_error.as_deref()
+    }
+
+    pub fn clear(&mut self) {
+        self.by_call.clear();
+        self.by_time.clear();
+        self.by_band.clear();
+        self.dirty = true;
+        let _ = self.save();
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_time.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_time.is_empty()
+    }
+
+    // When did we last hear `call`, on `band` or on any band
+    pub fn last_heard(&self, call: &str, band: Option<&str>) -> Option<&Heard> {
+        let bands = self.by_call.get(&call.trim().to_uppercase())?;
+        match band {
+            Some(band) => bands.get(band),
+            None => bands.values().max_by_key(|heard| heard.last),
+        }
+    }
+
+    // Bands `call` was heard on, most recent first
+    pub fn heard(&self, call: &str) -> Vec<&Heard> {
+        let mut heard: Vec<&Heard> =
+            match self.by_call.get(&call.trim().to_uppercase()) {
+                Some(bands) => bands.values().collect(),
+                None => Vec::new(),
+            };
+        heard.sort_by(|a, b| b.last.cmp(&a.last));
+        heard
+    }
+
+    // Stations heard since `since`, most recent first
+    pub fn heard_since(&self, since: DateTime<Utc>) -> Vec<&Heard> {
+        self.by_time
+            .range((since, String::new(), String::new())..)
+            .rev()
+            .filter_map(|(_, call, band)| self.by_call.get(call).and_then(|bands| bands.get(band)))
+            .collect()
+    }
+
+    // Stations heard on `band` since `since`, most recent first
+    pub fn heard_on_band_since(&self, band: &str, since: DateTime<Utc>) -> Vec<&Heard> {
+        match self.by_band.get(band) {
+            Some(calls) => calls
+                .range((since, String::new())..)
+                .rev()
+                .filter_map(|(_, call)| self.by_call.get(call).and_then(|bands| bands.get(band)))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // Bands anything was heard on, for the lookup band select
+    pub fn bands(&self) -> Vec<String> {
+        let mut bands: Vec<String> = self.by_band.keys().cloned().collect();
+        bands.sort_by_key(|band| band_order(band));
+        bands
+    }
+}
+
+// "160m" before "20m" before "70cm"
+fn band_order(band: &str) -> i64 {
+    let digits: String = band.chars().take_while(|c| c.is_ascii_digit()).collect();
+    let length = digits.parse::<i64>().unwrap_or(0);
+    if band.ends_with("cm") { -length } else { -length * 100 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone};
+
+    fn decode(call: &str, band: &str, minute: u32, snr: f32) -> Heard {
+        let time = Utc.ymd(2020, 12, 1).and_hms(12, minute, 0);
+        Heard {
+            call: call.to_string(),
+            band: band.to_string(),
+            first: time,
+            last: time,
+            frequency: 14074000.0,
+            mode: "FT8".to_string(),
+            snr,
+            count: 1,
+        }
+    }
+
+    fn history(max_entries: usize) -> SpotHistory {
+        SpotHistory::with_entries(None, Vec::new(), max_entries)
+    }
+
+    // by_time and by_band hold exactly one entry per (call, band) in by_call
+    fn assert_consistent(history: &SpotHistory) {
+        let entries: usize = history.by_call.values().map(|bands| bands.len()).sum();
+        assert_eq!(entries, history.by_time.len());
+        for (last, call, band) in &history.by_time {
+            assert_eq!(history.by_call[call][band].last, *last);
+            assert!(history.by_band[band].contains(&(*last, call.clone())));
+        }
+        assert_eq!(history.by_band.values().map(|calls| calls.len()).sum::<usize>(), entries);
+        assert!(history.by_band.values().all(|calls| !calls.is_empty()));
+    }
+
+    #[test]
+    fn update_merges_decodes() {
+        let mut history = history(10);
+        history.update(decode("K1ABC", "20m", 5, -10.0));
+        history.update(decode("K1ABC", "20m", 1, -12.0));
+        history.update(decode("K1ABC", "20m", 9, -3.0));
+        history.update(decode("K1ABC", "40m", 2, -20.0));
+        assert_consistent(&history);
+        assert_eq!(history.len(), 2);
+
+        let heard = history.last_heard("k1abc", Some("20m")).unwrap();
+        assert_eq!(heard.count, 3);
+        assert_eq!(heard.first, decode("", "", 1, 0.0).first);
+        assert_eq!(heard.last, decode("", "", 9, 0.0).last);
+        assert_eq!(heard.snr, -3.0);
+        assert_eq!(history.last_heard("K1ABC", Some("40m")).unwrap().snr, -20.0);
+        assert_eq!(history.last_heard("K1ABC", None).unwrap().band, "20m");
+        assert_eq!(history.heard("K1ABC").iter().map(|h| h.band.as_str()).collect::<Vec<_>>(), vec!["20m", "40m"]);
+    }
+
+    #[test]
+    fn older_decodes_keep_the_latest_details() {
+        let mut history = history(10);
+        history.update(decode("K1ABC", "20m", 5, -10.0));
+        let mut older = decode("K1ABC", "20m", 1, -2.0);
+        older.frequency = 14075000.0;
+        older.mode = "FT4".to_string();
+        history.update(older);
+
+        let heard = history.last_heard("K1ABC", Some("20m")).unwrap();
+        assert_eq!(heard.count, 2);
+        assert_eq!(heard.first, decode("", "", 1, 0.0).first);
+        assert_eq!(heard.last, decode("", "", 5, 0.0).last);
+        assert_eq!(heard.snr, -10.0);
+        assert_eq!(heard.frequency, 14074000.0);
+        assert_eq!(heard.mode, "FT8");
+
+        // a decode at the same time counts as the latest
+        history.update(decode("K1ABC", "20m", 5, -7.0));
+        assert_eq!(history.last_heard("K1ABC", Some("20m")).unwrap().snr, -7.0);
+        assert_consistent(&history);
+    }
+
+    #[test]
+    fn heard_on_band() {
+        let mut history = history(10);
+        history.update(decode("K1ABC", "20m", 1, 0.0));
+        history.update(decode("W1AW", "40m", 2, 0.0));
+        history.update(decode("N0CALL", "20m", 3, 0.0));
+        history.update(decode("W1AW", "20m", 4, 0.0));
+        // moves K1ABC to the front of 20m
+        history.update(decode("K1ABC", "20m", 5, 0.0));
+        assert_consistent(&history);
+
+        let calls = |band: &str, minute: u32| -> Vec<String> {
+            history.heard_on_band_since(band, decode("", "", minute, 0.0).first).iter().map(|h| h.call.clone()).collect()
+        };
+        assert_eq!(calls("20m", 0), vec!["K1ABC", "W1AW", "N0CALL"]);
+        assert_eq!(calls("20m", 4), vec!["K1ABC", "W1AW"]);
+        assert_eq!(calls("40m", 0), vec!["W1AW"]);
+        assert!(calls("40m", 3).is_empty());
+        assert!(calls("80m", 0).is_empty());
+    }
+
+    #[test]
+    fn clear_empties_every_index() {
+        let mut history = history(10);
+        assert!(history.is_empty());
+        history.update(decode("K1ABC", "20m", 1, 0.0));
+        assert!(!history.is_empty());
+        history.clear();
+        assert!(history.is_empty());
+        assert!(history.bands().is_empty());
+        assert_consistent(&history);
+    }
+
+    #[test]
+    fn evicts_least_recently_heard() {
+        let mut history = history(2);
+        history.update(decode("K1ABC", "20m", 1, -10.0));
+        history.update(decode("W1AW", "20m", 2, -10.0));
+        // heard again, so W1AW is now the oldest
+        history.update(decode("K1ABC", "20m", 3, -10.0));
+        history.update(decode("N0CALL", "20m", 4, -10.0));
+        assert_consistent(&history);
+        assert_eq!(history.len(), 2);
+        assert!(history.last_heard("W1AW", None).is_none());
+        assert!(!history.by_call.contains_key("W1AW"));
+        assert_eq!(history.last_heard("K1ABC", Some("20m")).unwrap().count, 2);
+
+        let since = decode("", "", 0, 0.0).first;
+        let calls: Vec<&str> = history.heard_since(since).iter().map(|h| h.call.as_str()).collect();
+        assert_eq!(calls, vec!["N0CALL", "K1ABC"]);
+    }
+
+    #[test]
+    fn restored_entries_are_limited() {
+        let entries = vec![decode("K1ABC", "20m", 1, 0.0), decode("W1AW", "20m", 2, 0.0), decode("N0CALL", "40m", 3, 0.0)];
+        let history = SpotHistory::with_entries(None, entries, 2);
+        assert_consistent(&history);
+        assert_eq!(history.len(), 2);
+        assert!(history.last_heard("K1ABC", None).is_none());
+    }
+
+    #[test]
+    fn saves_only_when_changed() {
+        let mut history = history(10);
+        assert!(!history.dirty);
+        history.update(decode("K1ABC", "20m", 1, 0.0));
+        assert!(history.dirty);
+        assert_eq!(history.save(), Ok(()));
+        assert!(!history.dirty);
+        assert_eq!(history.save_error(), None);
+    }
+
+    #[test]
+    fn bands_sorted_by_wavelength() {
+        let mut history = history(10);
+        for (minute, band) in ["20m", "70cm", "160m", "2m", "40m"].iter().enumerate() {
+            history.update(decode("K1ABC", band, minute as u32, 0.0));
+        }
+        assert_eq!(history.bands(), vec!["160m", "40m", "20m", "2m", "70cm"]);
+        assert!(band_order("160m") < band_order("20m"));
+        assert!(band_order("2m") < band_order("70cm"));
+        assert!(band_order("70cm") < band_order("23cm"));
+    }
+}
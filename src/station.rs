@@ -0,0 +1,324 @@
+use chrono::{DateTime,Utc};
+use std::collections::{BTreeSet,HashMap,VecDeque};
+
+use ham_rs::{Band};
+use sparkplug::{Spot};
+
+use crate::spot_history::{Heard};
+
+// SNR change (dB per minute) shown as rising/falling
+const TREND_THRESHOLD: f32 = 0.5;
+// Most recent decodes the trend is fitted to
+const TREND_POINTS: usize = 20;
+// Frequencies listed for a station before eliding the rest
+pub const MAX_FREQUENCIES_SHOWN: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Trend {
+    Rising,
+    Steady,
+    Falling,
+}
+
+impl Trend {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Trend::Rising => "\u{2191}",
+            Trend::Steady => "\u{2192}",
+            Trend::Falling => "\u{2193}",
+        }
+    }
+}
+
+// SNR of the most recent decodes of a station
+#[derive(Default)]
+pub struct SnrHistory {
+    points: VecDeque<(DateTime<Utc>, f32)>,
+}
+
+impl SnrHistory {
+    pub fn push(&mut self, time: DateTime<Utc>, snr: f32) {
+        if self.points.len() == TREND_POINTS {
+            self.points.pop_front();
+        }
+        self.points.push_back((time, snr));
+    }
+
+    // Least squares slope of the SNR over time
+    pub fn trend(&self) -> Trend {
+        let start =
+            match self.points.iter().map(|(time, _)| *time).min() {
+                Some(start) if self.points.len() >= 2 => start,
+                _ => return Trend::Steady,
+            };
+        let n = self.points.len() as f32;
+        let points: Vec<(f32, f32)> = self.points.iter()
+            .map(|(time, snr)| ((*time - start).num_seconds() as f32 / 60.0, *snr))
+            .collect();
+        let mean_t = points.iter().map(|(t, _)| t).sum::<f32>() / n;
+        let mean_snr = points.iter().map(|(_, snr)| snr).sum::<f32>() / n;
+        let covariance = points.iter().map(|(t, snr)| (t - mean_t) * (snr - mean_snr)).sum::<f32>();
+        let variance = points.iter().map(|(t, _)| (t - mean_t) * (t - mean_t)).sum::<f32>();
+        if variance == 0.0 {
+            return Trend::Steady;
+        }
+        let slope = covariance / variance;
+        if slope > TREND_THRESHOLD {
+            Trend::Rising
+        } else if slope < -TREND_THRESHOLD {
+            Trend::Falling
+        } else {
+            Trend::Steady
+        }
+    }
+}
+
+// Decodes of one callsign on one band and mode, kept independently of
+// the retained spots.  `heard` sums them up the way the spot history
+// does, `spot` is the most recent decode.
+pub struct Station<D = Spot> {
+    pub spot: D,
+    pub heard: Heard,
+    pub best_snr: f32,
+    // spot frequencies seen (Hz)
+    pub frequencies: BTreeSet<i64>,
+    snrs: SnrHistory,
+}
+
+impl<D> Station<D> {
+    fn new(decode: D, heard: Heard, frequency: i64) -> Station<D> {
+        let mut frequencies = BTreeSet::new();
+        frequencies.insert(frequency);
+        let mut snrs = SnrHistory::default();
+        snrs.push(heard.last, heard.snr);
+        Station {
+            spot: decode,
+            best_snr: heard.snr,
+            heard,
+            frequencies,
+            snrs,
+        }
+    }
+
+    fn add(&mut self, decode: D, heard: Heard, frequency: i64) {
+        if heard.last >= self.heard.last {
+            self.spot = decode;
+        }
+        self.best_snr = self.best_snr.max(heard.snr);
+        self.frequencies.insert(frequency);
+        self.snrs.push(heard.last, heard.snr);
+        self.heard.merge(heard);
+    }
+
+    pub fn last(&self) -> DateTime<Utc> {
+        self.heard.last
+    }
+
+    pub fn last_snr(&self) -> f32 {
+        self.heard.snr
+    }
+
+    pub fn trend(&self) -> Trend {
+        self.snrs.trend()
+    }
+}
+
+// Every station heard, keyed by callsign, band and mode
+pub struct Stations<D = Spot> {
+    stations: HashMap<(String, String, String), Station<D>>,
+}
+
+impl<D> Default for Stations<D> {
+    fn default() -> Stations<D> {
+        Stations { stations: HashMap::new() }
+    }
+}
+
+impl Stations<Spot> {
+    pub fn add(&mut self, spot: &Spot) {
+        let band =
+            match Band::new(spot.tuned_frequency as i32).band() {
+                Some(band) => band.to_string(),
+                None => String::new(),
+            };
+        self.record(Heard::from_spot(spot, band), spot.frequency as i64, spot.clone());
+    }
+}
+
+impl<D> Stations<D> {
+    // Add a decode of the station `heard` describes
+    fn record(&mut self, heard: Heard, frequency: i64, decode: D) {
+        let key = (heard.call.clone(), heard.band.clone(), heard.mode.clone());
+        match self.stations.get_mut(&key) {
+            Some(station) => station.add(decode, heard, frequency),
+            None => {
+                self.stations.insert(key, Station::new(decode, heard, frequency));
+            },
+        }
+    }
+
+    // Forget stations last heard before `oldest`, then all but the `max`
+    // most recently heard
+    pub fn prune(&mut self, oldest: DateTime<Utc>, max: usize) -> bool {
+        let before = self.stations.len();
+        self.stations.retain(|_, station| station.last() >= oldest);
+        if self.stations.len() > max {
+            let mut keys: Vec<(DateTime<Utc>, (String, String, String))> = self.stations.iter()
+                .map(|(key, station)| (station.last(), key.clone()))
+                .collect();
+            keys.sort_by(|a, b| b.0.cmp(&a.0));
+            for (_, key) in keys.into_iter().skip(max) {
+                self.stations.remove(&key);
+            }
+        }
+        self.stations.len() != before
+    }
+
+    pub fn clear(&mut self) {
+        self.stations.clear();
+    }
+
+    // Stations `include` accepts, most recently heard first
+    pub fn sorted<F: Fn(&Station<D>) -> bool>(&self, include: F) -> Vec<&Station<D>> {
+        let mut stations: Vec<&Station<D>> = self.stations.values().filter(|station| include(station)).collect();
+        stations.sort_by(|a, b| b.last().cmp(&a.last()));
+        stations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration,TimeZone};
+
+    fn snrs(points: &[(i64, f32)]) -> SnrHistory {
+        let start = Utc.ymd(2020, 12, 1).and_hms(12, 0, 0);
+        let mut snrs = SnrHistory::default();
+        for (seconds, snr) in points {
+            snrs.push(start + Duration::seconds(*seconds), *snr);
+        }
+        snrs
+    }
+
+    fn heard(call: &str, band: &str, mode: &str, minute: u32, snr: f32) -> Heard {
+        let time = Utc.ymd(2020, 12, 1).and_hms(12, minute, 0);
+        Heard {
+            call: call.to_string(),
+            band: band.to_string(),
+            first: time,
+            last: time,
+            frequency: 14074000.0,
+            mode: mode.to_string(),
+            snr,
+            count: 1,
+        }
+    }
+
+    fn minute(minute: u32) -> DateTime<Utc> {
+        Utc.ymd(2020, 12, 1).and_hms(12, minute, 0)
+    }
+
+    // decodes are identified by the minute they were heard in
+    fn record(stations: &mut Stations<u32>, call: &str, band: &str, mode: &str, at: u32, snr: f32) {
+        stations.record(heard(call, band, mode, at, snr), 14074000 + at as i64, at);
+    }
+
+    fn calls(stations: &Stations<u32>) -> Vec<String> {
+        stations.sorted(|_| true).iter().map(|station| station.heard.call.clone()).collect()
+    }
+
+    #[test]
+    fn trend() {
+        assert_eq!(snrs(&[]).trend(), Trend::Steady);
+        assert_eq!(snrs(&[(0, -10.0)]).trend(), Trend::Steady);
+        // 2 dB per minute
+        assert_eq!(snrs(&[(0, -20.0), (60, -18.0), (120, -16.0)]).trend(), Trend::Rising);
+        assert_eq!(snrs(&[(0, -16.0), (60, -18.0), (120, -20.0)]).trend(), Trend::Falling);
+        // 0.25 dB per minute is within the threshold
+        assert_eq!(snrs(&[(0, -10.0), (240, -9.0)]).trend(), Trend::Steady);
+        // decodes in the same FT8 slot
+        assert_eq!(snrs(&[(15, -10.0), (15, 0.0)]).trend(), Trend::Steady);
+        // out of order decodes fit the same line
+        assert_eq!(snrs(&[(120, -16.0), (0, -20.0), (60, -18.0)]).trend(), Trend::Rising);
+    }
+
+    #[test]
+    fn trend_uses_recent_decodes() {
+        let mut points: Vec<(i64, f32)> = (0..10).map(|i| (i * 60, -20.0 + i as f32 * 2.0)).collect();
+        points.extend((10..10 + TREND_POINTS as i64).map(|i| (i * 60, 0.0 - (i - 10) as f32 * 2.0)));
+        let snrs = snrs(&points);
+        assert_eq!(snrs.points.len(), TREND_POINTS);
+        assert_eq!(snrs.trend(), Trend::Falling);
+    }
+
+    #[test]
+    fn add_merges_decodes() {
+        let mut stations = Stations::default();
+        record(&mut stations, "K1ABC", "20m", "FT8", 5, -10.0);
+        record(&mut stations, "K1ABC", "20m", "FT8", 1, 2.0);
+        record(&mut stations, "K1ABC", "20m", "FT8", 9, -3.0);
+
+        let sorted = stations.sorted(|_| true);
+        assert_eq!(sorted.len(), 1);
+        let station = sorted[0];
+        assert_eq!(station.heard.count, 3);
+        assert_eq!(station.heard.first, minute(1));
+        assert_eq!(station.last(), minute(9));
+        // the latest decode, not the last one added
+        assert_eq!(station.spot, 9);
+        assert_eq!(station.last_snr(), -3.0);
+        assert_eq!(station.best_snr, 2.0);
+        assert_eq!(station.frequencies.iter().cloned().collect::<Vec<i64>>(), vec![14074001, 14074005, 14074009]);
+    }
+
+    #[test]
+    fn older_decodes_keep_the_latest() {
+        let mut stations = Stations::default();
+        record(&mut stations, "K1ABC", "20m", "FT8", 5, -10.0);
+        record(&mut stations, "K1ABC", "20m", "FT8", 1, 0.0);
+        let station = stations.sorted(|_| true)[0];
+        assert_eq!(station.spot, 5);
+        assert_eq!(station.last_snr(), -10.0);
+        assert_eq!(station.last(), minute(5));
+    }
+
+    #[test]
+    fn stations_by_call_band_and_mode() {
+        let mut stations = Stations::default();
+        record(&mut stations, "K1ABC", "20m", "FT8", 1, 0.0);
+        record(&mut stations, "K1ABC", "40m", "FT8", 2, 0.0);
+        record(&mut stations, "K1ABC", "20m", "FT4", 3, 0.0);
+        record(&mut stations, "W1AW", "20m", "FT8", 4, 0.0);
+        assert_eq!(calls(&stations), vec!["W1AW", "K1ABC", "K1ABC", "K1ABC"]);
+        let ft4: Vec<u32> = stations.sorted(|station| station.heard.mode == "FT4").iter().map(|station| station.spot).collect();
+        assert_eq!(ft4, vec![3]);
+    }
+
+    #[test]
+    fn prune_by_age() {
+        let mut stations = Stations::default();
+        record(&mut stations, "K1ABC", "20m", "FT8", 1, 0.0);
+        record(&mut stations, "W1AW", "20m", "FT8", 5, 0.0);
+        // heard again, stays
+        record(&mut stations, "K1ABC", "20m", "FT8", 6, 0.0);
+        record(&mut stations, "N0CALL", "20m", "FT8", 2, 0.0);
+        assert!(stations.prune(minute(5), 10));
+        assert_eq!(calls(&stations), vec!["K1ABC", "W1AW"]);
+        // nothing left to prune
+        assert!(!stations.prune(minute(5), 10));
+    }
+
+    #[test]
+    fn prune_to_most_recent() {
+        let mut stations = Stations::default();
+        for (at, call) in ["K1ABC", "W1AW", "N0CALL", "DL1ABC"].iter().enumerate() {
+            record(&mut stations, call, "20m", "FT8", at as u32, 0.0);
+        }
+        assert!(stations.prune(minute(0), 2));
+        assert_eq!(calls(&stations), vec!["DL1ABC", "N0CALL"]);
+        assert!(!stations.prune(minute(0), 2));
+
+        stations.clear();
+        assert!(calls(&stations).is_empty());
+    }
+}
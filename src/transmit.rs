@@ -0,0 +1,415 @@
+use std::collections::{HashSet};
+use std::fmt;
+use std::time::Duration;
+use chrono::{DateTime,Utc};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{spawn_local,JsFuture};
+use web_sys::{AudioContext,AudioContextOptions,AudioProcessingEvent,MediaStream,MediaStreamAudioSourceNode,MediaStreamConstraints,MediaStreamTrack,ScriptProcessorNode,WebSocket};
+use yew::{ComponentLink};
+use yew::services::{ConsoleService};
+use yew::services::interval::{IntervalService, IntervalTask};
+use yew::services::timeout::{TimeoutService, TimeoutTask};
+
+use crate::commands::{RadioInfo};
+use crate::frame;
+use crate::model::{Model,Msg};
+use crate::pcm::{RAW_SAMPLE_RATE};
+
+// Longest transmission before PTT is released for the operator
+pub const PTT_TIMEOUT_SECS: u64 = 180;
+// Microphone samples per transmitted frame
+const FRAME_SAMPLES: u32 = 2048;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TxError {
+    // transmitting needs the experimental protocol extensions
+    Disabled,
+    NotConnected,
+    // the default receiver does not belong to a known radio
+    NoRadio,
+    NotCapable(u32),
+    Microphone(String),
+    Timeout,
+}
+
+impl fmt::Display for TxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TxError::Disabled => write!(f, "experimental protocol extensions are disabled"),
+            TxError::NotConnected => write!(f, "not connected to SparkSDR"),
+            TxError::NoRadio => write!(f, "unable to tell which radio the selected receiver belongs to"),
+            TxError::NotCapable(radio_id) => write!(f, "radio {} does not report transmit capability", radio_id),
+            TxError::Microphone(err) => write!(f, "microphone unavailable: {}", err),
+            TxError::Timeout => write!(f, "transmit timeout ({}s), PTT released", PTT_TIMEOUT_SECS),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TxState {
+    Idle,
+    // PTT pressed, waiting for microphone access
+    Starting { radio_id: u32 },
+    Transmitting { radio_id: u32, since: DateTime<Utc> },
+}
+
+// Microphone -> script processor, each processed block is sent to
+// SparkSDR as an audio frame
+struct Capture {
+    audio_ctx: AudioContext,
+    stream: MediaStream,
+    source: MediaStreamAudioSourceNode,
+    processor: ScriptProcessorNode,
+    _onaudioprocess: Closure<dyn FnMut(AudioProcessingEvent)>,
+}
+
+impl Capture {
+    fn new(stream: MediaStream, ws: WebSocket, radio_id: u32) -> Result<Capture, JsValue> {
+        // let the browser resample the microphone to the rate SparkSDR uses
+        let mut options = AudioContextOptions::new();
+        options.sample_rate(RAW_SAMPLE_RATE);
+        let audio_ctx = AudioContext::new_with_context_options(&options)?;
+        let source = audio_ctx.create_media_stream_source(&stream)?;
+        let processor = audio_ctx.create_script_processor_with_buffer_size_and_number_of_input_channels_and_number_of_output_channels(FRAME_SAMPLES, 1, 1)?;
+
+        let onaudioprocess = Closure::wrap(Box::new(move |e: AudioProcessingEvent| {
+            let samples = e.input_buffer().and_then(|buffer| buffer.get_channel_data(0));
+            if let Ok(samples) = samples {
+                let data = frame::encode_audio(radio_id, &samples);
+                if let Err(err) = ws.send_with_u8_array(&data) {
+                    ConsoleService::error(&format!("unable to send transmit audio: {:?}", err));
+                }
+            }
+        }) as Box<dyn FnMut(AudioProcessingEvent)>);
+        processor.set_onaudioprocess(Some(onaudioprocess.as_ref().unchecked_ref()));
+
+        source.connect_with_audio_node(&processor)?;
+        // the processor only runs while connected, its output is silent
+        processor.connect_with_audio_node(&audio_ctx.destination())?;
+
+        Ok(Capture {
+            audio_ctx,
+            stream,
+            source,
+            processor,
+            _onaudioprocess: onaudioprocess,
+        })
+    }
+
+    fn stop(&self) {
+        self.processor.set_onaudioprocess(None);
+        let _ = self.source.disconnect();
+        let _ = self.processor.disconnect();
+        let _ = self.audio_ctx.close();
+        stop_stream(&self.stream);
+    }
+}
+
+// Release the microphone
+fn stop_stream(stream: &MediaStream) {
+    for track in stream.get_tracks().iter() {
+        if let Ok(track) = track.dyn_into::<MediaStreamTrack>() {
+            track.stop();
+        }
+    }
+}
+
+async fn request_microphone() -> Result<MediaStream, JsValue> {
+    let media_devices = web_sys::window()
+        .ok_or_else(|| JsValue::from_str("no window"))?
+        .navigator()
+        .media_devices()?;
+    let mut constraints = MediaStreamConstraints::new();
+    constraints.audio(&JsValue::TRUE);
+    let stream = JsFuture::from(media_devices.get_user_media_with_constraints(&constraints)?).await?;
+    stream.dyn_into::<MediaStream>()
+}
+
+// PTT state, kept apart from the microphone capture and timers
+struct Ptt {
+    state: TxState,
+    // radios that reported they can transmit
+    capable: HashSet<u32>,
+    error: Option<TxError>,
+    // radio that was keyed when the connection dropped, SparkSDR is told
+    // to unkey it as soon as we are connected again
+    unkey_pending: Option<u32>,
+}
+
+impl Ptt {
+    fn new() -> Ptt {
+        Ptt {
+            state: TxState::Idle,
+            capable: HashSet::new(),
+            error: None,
+            unkey_pending: None,
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.state != TxState::Idle
+    }
+
+    fn remaining(&self, now: DateTime<Utc>) -> Option<i64> {
+        match self.state {
+            TxState::Transmitting { since, .. } => Some(PTT_TIMEOUT_SECS as i64 - (now - since).num_seconds()),
+            _ => None,
+        }
+    }
+
+    // PTT pressed, false if already transmitting or starting
+    fn start(&mut self, radio_id: u32) -> bool {
+        if self.is_active() {
+            return false;
+        }
+        self.error = None;
+        self.state = TxState::Starting { radio_id };
+        true
+    }
+
+    // Radio waiting for the microphone, None if PTT was released
+    fn starting(&self) -> Option<u32> {
+        match self.state {
+            TxState::Starting { radio_id } => Some(radio_id),
+            _ => None,
+        }
+    }
+
+    // Microphone capture running, returns the radio to key
+    fn begin(&mut self, now: DateTime<Utc>) -> Option<u32> {
+        let radio_id = self.starting()?;
+        self.state = TxState::Transmitting { radio_id, since: now };
+        Some(radio_id)
+    }
+
+    // Back to idle, returns the radio to unkey if it was keyed
+    fn stop(&mut self) -> Option<u32> {
+        let keyed =
+            match self.state {
+                TxState::Transmitting { radio_id, .. } => Some(radio_id),
+                _ => None,
+            };
+        self.state = TxState::Idle;
+        keyed
+    }
+
+    fn connection_lost(&mut self) {
+        if let Some(radio_id) = self.stop() {
+            self.unkey_pending = Some(radio_id);
+        }
+    }
+}
+
+pub struct Transmitter {
+    ptt: Ptt,
+    capture: Option<Capture>,
+    _timeout: Option<TimeoutTask>,
+    // re-renders the TX indicator while transmitting
+    _tick: Option<IntervalTask>,
+}
+
+impl Transmitter {
+    pub fn new() -> Transmitter {
+        Transmitter {
+            ptt: Ptt::new(),
+            capture: None,
+            _timeout: None,
+            _tick: None,
+        }
+    }
+
+    pub fn state(&self) -> TxState {
+        self.ptt.state
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.ptt.is_active()
+    }
+
+    pub fn error(&self) -> Option<&TxError> {
+        self.ptt.error.as_ref()
+    }
+
+    pub fn set_capabilities(&mut self, radios: &[RadioInfo]) {
+        self.ptt.capable = radios.iter().filter(|r| r.can_transmit).map(|r| r.id).collect();
+    }
+
+    pub fn clear_capabilities(&mut self) {
+        self.ptt.capable.clear();
+    }
+
+    pub fn is_capable(&self, radio_id: u32) -> bool {
+        self.ptt.capable.contains(&radio_id)
+    }
+
+    // Seconds left before the PTT timeout
+    pub fn remaining(&self) -> Option<i64> {
+        self.ptt.remaining(Utc::now())
+    }
+
+    // PTT pressed: ask for the microphone (answered with
+    // Msg::MicrophoneReady or Msg::TransmitError) and arm the PTT timeout
+    pub fn start(&mut self, link: &ComponentLink<Model>, radio_id: u32) {
+        if !self.ptt.start(radio_id) {
+            return;
+        }
+        self._timeout = Some(TimeoutService::spawn(Duration::from_secs(PTT_TIMEOUT_SECS), link.callback(|_| Msg::PttTimeout)));
+
+        let ready = link.callback(Msg::MicrophoneReady);
+        let failed = link.callback(Msg::TransmitError);
+        spawn_local(async move {
+            match request_microphone().await {
+                Ok(stream) => ready.emit(stream),
+                Err(err) => failed.emit(format!("{:?}", err)),
+            }
+        });
+    }
+
+    // Microphone access granted, start sending audio.  Returns the radio
+    // to key, None if PTT was released while we waited.
+    pub fn begin(&mut self, link: &ComponentLink<Model>, stream: MediaStream, ws: &WebSocket) -> Result<Option<u32>, TxError> {
+        let radio_id =
+            match self.ptt.starting() {
+                Some(radio_id) => radio_id,
+                None => {
+                    stop_stream(&stream);
+                    return Ok(None);
+                }
+            };
+        match Capture::new(stream.clone(), ws.clone(), radio_id) {
+            Ok(capture) => {
+                ConsoleService::log(&format!("transmitting on radio {}", radio_id));
+                self.capture = Some(capture);
+                self._tick = Some(IntervalService::spawn(Duration::from_secs(1), link.callback(|_| Msg::TransmitTick)));
+                Ok(self.ptt.begin(Utc::now()))
+            },
+            Err(err) => {
+                stop_stream(&stream);
+                Err(TxError::Microphone(format!("{:?}", err)))
+            }
+        }
+    }
+
+    // Release the microphone and timers
+    fn release(&mut self) {
+        if let Some(capture) = self.capture.take() {
+            capture.stop();
+        }
+        self._timeout = None;
+        self._tick = None;
+    }
+
+    // PTT released, returns the radio to unkey if audio was being sent
+    pub fn stop(&mut self) -> Option<u32> {
+        self.release();
+        let keyed = self.ptt.stop();
+        if keyed.is_some() {
+            ConsoleService::log("transmit stopped");
+        }
+        keyed
+    }
+
+    // The connection dropped, stop and remember what to unkey
+    pub fn connection_lost(&mut self) {
+        self.release();
+        self.ptt.connection_lost();
+    }
+
+    pub fn take_unkey_pending(&mut self) -> Option<u32> {
+        self.ptt.unkey_pending.take()
+    }
+
+    pub fn fail(&mut self, err: TxError) {
+        ConsoleService::error(&format!("transmit: {}", err));
+        self.ptt.error = Some(err);
+    }
+
+    pub fn clear_error(&mut self) {
+        self.ptt.error = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn now() -> DateTime<Utc> {
+        Utc.ymd(2026, 10, 18).and_hms(12, 0, 0)
+    }
+
+    #[test]
+    fn start_begin_stop() {
+        let mut ptt = Ptt::new();
+        assert!(!ptt.is_active());
+        assert!(ptt.start(2));
+        assert_eq!(ptt.state, TxState::Starting { radio_id: 2 });
+        assert_eq!(ptt.starting(), Some(2));
+        // a second press while starting is ignored
+        assert!(!ptt.start(3));
+
+        assert_eq!(ptt.begin(now()), Some(2));
+        assert_eq!(ptt.state, TxState::Transmitting { radio_id: 2, since: now() });
+        assert!(!ptt.start(3));
+        assert_eq!(ptt.remaining(now() + chrono::Duration::seconds(30)), Some(PTT_TIMEOUT_SECS as i64 - 30));
+
+        assert_eq!(ptt.stop(), Some(2));
+        assert_eq!(ptt.state, TxState::Idle);
+        assert_eq!(ptt.remaining(now()), None);
+        // nothing left to unkey
+        assert_eq!(ptt.stop(), None);
+        assert_eq!(ptt.unkey_pending, None);
+    }
+
+    #[test]
+    fn released_while_waiting_for_the_microphone() {
+        let mut ptt = Ptt::new();
+        ptt.start(2);
+        // not keyed yet, nothing to unkey
+        assert_eq!(ptt.stop(), None);
+        // permission granted after the release
+        assert_eq!(ptt.starting(), None);
+        assert_eq!(ptt.begin(now()), None);
+        assert_eq!(ptt.state, TxState::Idle);
+    }
+
+    #[test]
+    fn start_clears_the_last_error() {
+        let mut ptt = Ptt::new();
+        ptt.error = Some(TxError::Timeout);
+        ptt.start(2);
+        assert_eq!(ptt.error, None);
+    }
+
+    #[test]
+    fn unkeys_after_connection_lost() {
+        let mut ptt = Ptt::new();
+        ptt.start(2);
+        ptt.begin(now());
+        ptt.connection_lost();
+        assert_eq!(ptt.state, TxState::Idle);
+        assert_eq!(ptt.unkey_pending.take(), Some(2));
+        assert_eq!(ptt.unkey_pending.take(), None);
+    }
+
+    #[test]
+    fn nothing_to_unkey_if_not_keyed() {
+        let mut ptt = Ptt::new();
+        ptt.start(2);
+        ptt.connection_lost();
+        assert_eq!(ptt.state, TxState::Idle);
+        assert_eq!(ptt.unkey_pending, None);
+    }
+
+    #[test]
+    fn transmitter_tracks_capable_radios() {
+        let radios: Vec<RadioInfo> = serde_json::from_str(r#"[{"ID": 1, "CanTransmit": true}, {"ID": 2}]"#).unwrap();
+        let mut transmitter = Transmitter::new();
+        transmitter.set_capabilities(&radios);
+        assert!(transmitter.is_capable(1));
+        assert!(!transmitter.is_capable(2));
+        transmitter.clear_capabilities();
+        assert!(!transmitter.is_capable(1));
+    }
+}
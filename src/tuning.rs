@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+use yew::prelude::*;
+use yew::services::timeout::{TimeoutService, TimeoutTask};
+use wasm_bindgen::JsCast;
+
+use crate::model::{Model,Msg};
+
+// Minimum time between SetFrequency commands for a receiver (ms),
+// requests in between are coalesced into the most recent frequency
+const MIN_COMMAND_INTERVAL_MS: f64 = 100.0;
+
+// Selectable tuning steps (Hz) for the mouse wheel and keyboard
+pub const TUNING_STEPS: [f64; 10] = [1.0, 10.0, 50.0, 100.0, 500.0, 1000.0, 2500.0, 5000.0, 10000.0, 100000.0];
+const DEFAULT_TUNING_STEP: f64 = 100.0;
+// Page Up/Down move this many steps
+pub const PAGE_STEPS: i32 = 10;
+
+// Tuning range of a radio (Hz)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrequencyRange {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl FrequencyRange {
+    pub fn contains(&self, frequency: f64) -> bool {
+        frequency >= self.min && frequency <= self.max
+    }
+}
+
+// Range accepted for every radio, SparkSDR does not report the tuning
+// range of a radio
+pub const DEFAULT_RANGE: FrequencyRange = FrequencyRange { min: 10000.0, max: 450000000.0 };
+
+// Tuning step size and rate limiting of outgoing SetFrequency commands
+pub struct Tuner {
+    step: f64,
+    pending: HashMap<u32, f64>,
+    last_flush: f64,
+    task: Option<TimeoutTask>,
+}
+
+impl Tuner {
+    pub fn new() -> Tuner {
+        Tuner {
+            step: DEFAULT_TUNING_STEP,
+            pending: HashMap::new(),
+            last_flush: 0.0,
+            task: None,
+        }
+    }
+
+    pub fn step(&self) -> f64 {
+        self.step
+    }
+
+    pub fn set_step(&mut self, step: f64) {
+        self.step = step;
+    }
+
+    // Frequency `steps` tuning steps away, aligned to the step size.  An
+    // off-grid frequency counts the nearest grid point in the direction of
+    // travel as the first step.
+    pub fn step_frequency(&self, frequency: f64, steps: i32) -> f64 {
+        step_frequency(frequency, self.step, steps)
+    }
+
+    // Queue a frequency change, the command is sent from `take_pending`
+    // once the rate limit allows (Msg::FlushFrequency)
+    pub fn request(&mut self, link: &ComponentLink<Model>, receiver_id: u32, frequency: f64) {
+        self.pending.insert(receiver_id, frequency);
+        if self.task.is_none() {
+            let elapsed = js_sys::Date::now() - self.last_flush;
+            let delay = (MIN_COMMAND_INTERVAL_MS - elapsed).max(0.0);
+            let callback = link.callback(|_| Msg::FlushFrequency);
+            self.task = Some(TimeoutService::spawn(Duration::from_millis(delay as u64), callback));
+        }
+    }
+
+    pub fn take_pending(&mut self) -> Vec<(u32, f64)> {
+        self.task = None;
+        self.last_flush = js_sys::Date::now();
+        self.pending.drain().collect()
+    }
+}
+
+// Step size for the per digit up/down controls (digit 0 - 8)
+pub fn digit_step(digit: i32) -> f64 {
+    10f64.powi(8 - digit.max(0).min(8))
+}
+
+pub fn step_name(step: f64) -> String {
+    match step {
+        step if step >= 1000000.0 => format!("{} MHz", step / 1000000.0),
+        step if step >= 1000.0 => format!("{} kHz", step / 1000.0),
+        step => format!("{} Hz", step),
+    }
+}
+
+// Wheel up tunes up
+pub fn wheel_steps(e: &WheelEvent) -> i32 {
+    match e.delta_y() {
+        delta if delta < 0.0 => 1,
+        delta if delta > 0.0 => -1,
+        _ => 0,
+    }
+}
+
+pub fn step_frequency(frequency: f64, step: f64, steps: i32) -> f64 {
+    if step <= 0.0 {
+        return frequency;
+    }
+    let grid =
+        match steps {
+            steps if steps > 0 => (frequency / step).floor() * step,
+            steps if steps < 0 => (frequency / step).ceil() * step,
+            _ => snap(frequency, step),
+        };
+    grid + step * steps as f64
+}
+
+// Keyboard shortcuts for tuning the default receiver, only while the
+// frequency display or a waterfall has focus so the keys still scroll
+// the page and move between controls elsewhere
+pub fn key_steps(e: &KeyboardEvent) -> Option<i32> {
+    let tuning =
+        e.target()
+            .and_then(|t| t.dyn_into::<web_sys::Element>().ok())
+            .and_then(|el| el.closest(".frequency, .tunable").ok())
+            .map(|el| el.is_some())
+            .unwrap_or(false);
+    if !tuning {
+        return None;
+    }
+    match e.key().as_str() {
+        "ArrowUp" | "ArrowRight" => Some(1),
+        "ArrowDown" | "ArrowLeft" => Some(-1),
+        "PageUp" => Some(PAGE_STEPS),
+        "PageDown" => Some(-PAGE_STEPS),
+        _ => None,
+    }
+}
+
+// Click-to-tune snapping for a receiver mode (Hz)
+pub fn snap_step(mode: &str) -> f64 {
+    match mode {
+        "CW" | "CWL" | "CWU" => 10.0,
+        "LSB" | "USB" | "DigiL" | "DigiU" => 1000.0,
+        "AM" | "SAM" | "FM" | "NFM" => 5000.0,
+        _ => 100.0,
+    }
+}
+
+// Round a frequency to the nearest multiple of `step`
+pub fn snap(frequency: f64, step: f64) -> f64 {
+    if step <= 0.0 {
+        return frequency;
+    }
+    (frequency / step).round() * step
+}
+
+// Horizontal pointer position (0..1) over the element that
+// received a mouse event
+pub fn pointer_ratio(e: &MouseEvent) -> Option<f64> {
+    let element = e.target()?.dyn_into::<web_sys::Element>().ok()?;
+    match element.client_width() {
+        width if width > 0 => Some((e.offset_x() as f64 / width as f64).max(0.0).min(1.0)),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrequencyError {
+    Empty,
+    Invalid(String),
+    UnknownUnit(String),
+    OutOfRange(f64, FrequencyRange),
+}
+
+impl fmt::Display for FrequencyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrequencyError::Empty => write!(f, "enter a frequency"),
+            FrequencyError::Invalid(value) => write!(f, "not a frequency: {}", value),
+            FrequencyError::UnknownUnit(unit) => write!(f, "unknown unit: {}", unit),
+            FrequencyError::OutOfRange(frequency, range) =>
+                write!(f, "{} Hz is outside {} - {} Hz", frequency, range.min, range.max),
+        }
+    }
+}
+
+// Parse a typed frequency into Hz.  Accepts an optional unit
+// (`14.074 MHz`, `7074k`, `14074000 Hz`), without a unit decimals are
+// taken as MHz (`14.074`), whole numbers below 1000 as MHz (`14`),
+// below 1000000 as kHz (`14074`) and anything larger as Hz.  The result
+// must be within the radio's `range`.
+pub fn parse_frequency(input: &str, range: FrequencyRange) -> Result<f64, FrequencyError> {
+    let input: String = input.chars().filter(|c| !c.is_whitespace() && *c != ',' && *c != '_').collect();
+    if input.is_empty() {
+        return Err(FrequencyError::Empty);
+    }
+
+    let split = input.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let value = number.parse::<f64>().map_err(|_| FrequencyError::Invalid(input.to_string()))?;
+
+    let frequency =
+        match unit.to_lowercase().as_str() {
+            "" if number.contains('.') => value * 1000000.0,
+            "" if value < 1000.0 => value * 1000000.0,
+            "" if value < 1000000.0 => value * 1000.0,
+            "" | "hz" => value,
+            "k" | "khz" => value * 1000.0,
+            "m" | "mhz" => value * 1000000.0,
+            "g" | "ghz" => value * 1000000000.0,
+            _ => return Err(FrequencyError::UnknownUnit(unit.to_string())),
+        };
+
+    check_frequency(frequency.round(), range)
+}
+
+// Frequencies tuned to by other means (band presets) get the same check
+// as typed ones
+pub fn check_frequency(frequency: f64, range: FrequencyRange) -> Result<f64, FrequencyError> {
+    match range.contains(frequency) {
+        true => Ok(frequency),
+        false => Err(FrequencyError::OutOfRange(frequency, range)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_along_the_grid() {
+        assert_eq!(step_frequency(14074000.0, 1000.0, 1), 14075000.0);
+        assert_eq!(step_frequency(14074000.0, 1000.0, -1), 14073000.0);
+        assert_eq!(step_frequency(14074000.0, 100.0, 10), 14075000.0);
+    }
+
+    #[test]
+    fn off_grid_steps_to_the_next_grid_point() {
+        assert_eq!(step_frequency(14074600.0, 1000.0, 1), 14075000.0);
+        assert_eq!(step_frequency(14074600.0, 1000.0, -1), 14074000.0);
+        assert_eq!(step_frequency(14074400.0, 1000.0, 2), 14076000.0);
+        assert_eq!(step_frequency(14074400.0, 1000.0, -2), 14073000.0);
+    }
+
+    #[test]
+    fn zero_steps_snaps() {
+        assert_eq!(step_frequency(14074600.0, 1000.0, 0), 14075000.0);
+    }
+
+    #[test]
+    fn parses_frequencies() {
+        assert_eq!(parse_frequency("14074", DEFAULT_RANGE), Ok(14074000.0));
+        assert_eq!(parse_frequency("14.074 MHz", DEFAULT_RANGE), Ok(14074000.0));
+        assert_eq!(parse_frequency("14.074", DEFAULT_RANGE), Ok(14074000.0));
+        assert_eq!(parse_frequency("7074k", DEFAULT_RANGE), Ok(7074000.0));
+        assert_eq!(parse_frequency("14,074,000 Hz", DEFAULT_RANGE), Ok(14074000.0));
+        assert_eq!(parse_frequency("14", DEFAULT_RANGE), Ok(14000000.0));
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert_eq!(parse_frequency("", DEFAULT_RANGE), Err(FrequencyError::Empty));
+        assert_eq!(parse_frequency("   ", DEFAULT_RANGE), Err(FrequencyError::Empty));
+        assert_eq!(parse_frequency("abc", DEFAULT_RANGE), Err(FrequencyError::Invalid("abc".to_string())));
+        assert_eq!(parse_frequency("1.2.3", DEFAULT_RANGE), Err(FrequencyError::Invalid("1.2.3".to_string())));
+        assert_eq!(parse_frequency("14074 furlongs", DEFAULT_RANGE), Err(FrequencyError::UnknownUnit("furlongs".to_string())));
+    }
+
+    #[test]
+    fn checks_the_range() {
+        let range = FrequencyRange { min: 1800000.0, max: 54000000.0 };
+        assert_eq!(parse_frequency("50.313", range), Ok(50313000.0));
+        assert_eq!(parse_frequency("144.174", range), Err(FrequencyError::OutOfRange(144174000.0, range)));
+        assert_eq!(parse_frequency("5k", DEFAULT_RANGE), Err(FrequencyError::OutOfRange(5000.0, DEFAULT_RANGE)));
+    }
+
+    #[test]
+    fn band_presets_are_in_range() {
+        for preset in crate::bands::BAND_PRESETS.iter() {
+            assert_eq!(check_frequency(preset.frequency, DEFAULT_RANGE), Ok(preset.frequency), "{}", preset.name);
+        }
+        assert_eq!(check_frequency(1.0, DEFAULT_RANGE), Err(FrequencyError::OutOfRange(1.0, DEFAULT_RANGE)));
+    }
+}
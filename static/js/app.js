@@ -1,6 +1,6 @@
 var mapView = null;
-var markers = [];
-var lastTime = null;
+// spot markers by spot id
+var markers = new Map();
 
 var icons = {};
 var bands = ["160m","80m","40m","30m","20m","17m","15m","12m","10m","6m","2m","1.25cm","70cm","33cm","unknown"];
@@ -90,30 +90,10 @@ function initMap() {
 
 var currentPopup = null;
 
-// add spot to map
-function addMarker(call, lat, lon, spotOn, freq, bandName, lotw, cq, mode) {
+// add spot to map, old markers are removed by the spot retention
+// (removeMarkers)
+function addMarker(id, call, lat, lon, spotOn, freq, bandName, lotw, cq, mode) {
     var bandIcon = bandName;
-    if (lastTime == null || lastTime != spotOn) {
-        // update previous spotOn period with new icons
-        if (markers.length > 0) {
-            markers[markers.length-1].forEach(function (marker) {
-
-            });
-        }
-
-        // start a new time period first run or if new spotOn
-        lastTime = spotOn;
-        let tmp = [];
-        markers.push(tmp);
-
-        // remove spots > 10 time periods back
-        if (markers.length > 10) {
-            let tmp = markers.shift();
-            tmp.forEach(function(entry) {
-                entry.remove();
-            });
-        }
-    }
 
     // lotw users get an icon with a dot
     if (cq && !lotw) {
@@ -128,7 +108,26 @@ function addMarker(call, lat, lon, spotOn, freq, bandName, lotw, cq, mode) {
     // add spot marker to map
     let marker = L.marker([lat, lon], {icon: icons[bandIcon]}).addTo(mapView);
     marker.bindPopup("<p><b>"+call+"</b></p><p>" + bandName + " " + mode + "</p><p>LoTW: " + (lotw ? "Yes" : "No") + "</p");
-    markers[markers.length-1].push(marker);
+    markers.set(id, marker);
+}
+
+// remove the markers of the given spots, spots without one are skipped
+function removeMarkers(ids) {
+    ids.forEach(function (id) {
+        let marker = markers.get(id);
+        if (marker !== undefined) {
+            marker.remove();
+            markers.delete(id);
+        }
+    });
+}
+
+// remove all spot markers, e.g. before redrawing them after a filter change
+function clearMarkers() {
+    markers.forEach(function (marker) {
+        marker.remove();
+    });
+    markers.clear();
 }
 
 var frequency = null;
@@ -181,6 +180,12 @@ function initWaterfallNav(mode, freq, high, low) {
 }
 
 function updateWaterfallNav() {
+    // the primary waterfall may have been re-rendered
+    marker = document.getElementById("receiver-marker");
+    waterfall = document.getElementById("waterfall");
+    if (marker == null || waterfall == null) {
+        return;
+    }
     if (frequencyStart == null || frequencyStop == null || filterHigh == null || filterLow == null) {
         marker.style.display = "none";
         return;